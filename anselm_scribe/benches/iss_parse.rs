@@ -0,0 +1,133 @@
+//! # ISS trades page parsing benchmarks
+//!
+//! Compares parsing through `HashMap<String, serde_json::Value>` with the typed borrowing
+//! deserializer in `iss`. Payload is the full TQBR trades page (5000 rows) from the ISS cassette
+//! in `tests/fixtures/iss`, another saved `trades.json` response can be set in
+//! `ISS_BENCH_PAYLOAD`. CSV variant of the same page is derived from the JSON payload.
+//!
+//! ```sh
+//! ISS_BENCH_PAYLOAD=./trades.json cargo bench -p anselm_scribe --bench iss_parse
+//! ```
+
+use anselm_scribe::iss::{self, TradeRow};
+use anselm_scribe::models::Trade;
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+/// Full TQBR trades page stored in the ISS cassette fixture
+const PAYLOAD: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/fixtures/iss/engines_stock_markets_shares_boards_TQBR_trades.json__start=0__limit=5000"
+);
+
+/// Convert JSON trades page into ISS CSV layout
+fn json_to_csv(body: &[u8]) -> String {
+    let resp: serde_json::Value = serde_json::from_slice(body).unwrap();
+    let columns: Vec<&str> = resp["trades"]["columns"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|c| c.as_str().unwrap())
+        .collect();
+    let rows: Vec<String> = resp["trades"]["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|row| {
+            row.as_array()
+                .unwrap()
+                .iter()
+                .map(|cell| match cell {
+                    serde_json::Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                })
+                .collect::<Vec<String>>()
+                .join(";")
+        })
+        .collect();
+    format!("trades\n\n{}\n{}\n\n", columns.join(";"), rows.join("\n"))
+}
+
+/// Load payload from `ISS_BENCH_PAYLOAD` or the committed TQBR trades page
+fn payload() -> Vec<u8> {
+    let path = std::env::var("ISS_BENCH_PAYLOAD").unwrap_or_else(|_| PAYLOAD.to_string());
+    std::fs::read(&path).unwrap_or_else(|e| panic!("Error reading payload '{}': {}", path, e))
+}
+
+/// Parse trades by walking `serde_json::Value` cells
+fn parse_value(body: &[u8]) -> Vec<Trade> {
+    let resp: HashMap<String, serde_json::Value> = serde_json::from_slice(body).unwrap();
+    resp["trades"]["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|x| {
+            let row = TradeRow {
+                tradeno: x[0].as_i64().unwrap(),
+                tradetime: Cow::Borrowed(x[1].as_str().unwrap()),
+                boardid: Cow::Borrowed(x[2].as_str().unwrap()),
+                secid: Cow::Borrowed(x[3].as_str().unwrap()),
+                price: x[4].as_f64().unwrap(),
+                quantity: x[5].as_i64().unwrap(),
+                value: x[6].as_f64().unwrap(),
+                period: Cow::Borrowed(x[7].as_str().unwrap()),
+                tradetime_grp: x[8].as_i64().unwrap(),
+                systime: Cow::Borrowed(x[9].as_str().unwrap()),
+                buysell: Cow::Borrowed(x[10].as_str().unwrap()),
+                decimals: x[11].as_u64().unwrap() as u8,
+            };
+            Trade::from_iss_row("stock", "shares", &row).unwrap()
+        })
+        .collect()
+}
+
+/// Parse trades with typed borrowing deserializer
+fn parse_typed(body: &[u8]) -> Vec<Trade> {
+    let rows: Vec<TradeRow> = iss::parse_block(body).unwrap();
+    rows.iter()
+        .map(|row| Trade::from_iss_row("stock", "shares", row).unwrap())
+        .collect()
+}
+
+fn bench_trades(c: &mut Criterion) {
+    let body = payload();
+    let csv = json_to_csv(&body);
+    let csv_rows: Vec<TradeRow> = iss::parse_csv_block(&csv).unwrap();
+    assert_eq!(parse_value(&body).len(), parse_typed(&body).len());
+    assert_eq!(parse_typed(&body).len(), csv_rows.len());
+
+    let mut group = c.benchmark_group("iss_trades_page");
+    group.throughput(Throughput::Bytes(body.len() as u64));
+
+    group.bench_function("value_rows", |b| {
+        b.iter(|| {
+            let resp: HashMap<String, serde_json::Value> =
+                serde_json::from_slice(black_box(&body)).unwrap();
+            black_box(resp)
+        })
+    });
+    group.bench_function("typed_rows", |b| {
+        b.iter(|| {
+            let rows: Vec<TradeRow> = iss::parse_block(black_box(&body)).unwrap();
+            black_box(rows)
+        })
+    });
+    group.bench_function("typed_csv_rows", |b| {
+        b.iter(|| {
+            let rows: Vec<TradeRow> = iss::parse_csv_block(black_box(&csv)).unwrap();
+            black_box(rows)
+        })
+    });
+    group.bench_function("value_trades", |b| {
+        b.iter(|| black_box(parse_value(black_box(&body))))
+    });
+    group.bench_function("typed_trades", |b| {
+        b.iter(|| black_box(parse_typed(black_box(&body))))
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_trades);
+criterion_main!(benches);
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS {db}.engines(
+    id               UInt32,
+    name             String,
+    title            String
+)
+ENGINE = MergeTree
+PRIMARY KEY (id)
+ORDER BY (id,name);
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS {db}.markets(
+    engine           LowCardinality(String) Codec(ZSTD(1)),
+    id               UInt32,
+    name             String,
+    title            String
+)
+ENGINE = MergeTree
+PRIMARY KEY (id)
+ORDER BY (id,name);
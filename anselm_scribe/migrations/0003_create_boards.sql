@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS {db}.boards(
+    engine           LowCardinality(String) Codec(ZSTD(1)),
+    market           LowCardinality(String) Codec(ZSTD(1)),
+    id               UInt32,
+    board_group_id   UInt32,
+    boardid          LowCardinality(String) Codec(ZSTD(1)),
+    title            String,
+    is_traded        Boolean,
+)
+ENGINE = MergeTree
+PRIMARY KEY (engine, market, boardid)
+ORDER BY (engine, market, boardid);
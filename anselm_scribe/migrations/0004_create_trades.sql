@@ -0,0 +1,18 @@
+-- TODO: enum
+--  buysell    Enum8('B' = 1, 'S' = 2) Codec(ZSTD(1)),
+CREATE TABLE IF NOT EXISTS {db}.trades(
+    engine     LowCardinality(String) Codec(ZSTD(1)),
+    market     LowCardinality(String) Codec(ZSTD(1)),
+    secid      LowCardinality(String) Codec(ZSTD(1)),
+    boardid    LowCardinality(String) Codec(ZSTD(1)),
+    tradeid    UInt64 Codec(Delta, Default),
+    buysell    LowCardinality(String) Codec(ZSTD(1)),
+    quantity   UInt32,
+    price      Float64 Codec(Gorilla, ZSTD(1)),
+    value      Float64 Codec(Gorilla, ZSTD(1)),
+    tradetime  DateTime Codec(DoubleDelta, ZSTD(1)),
+    systime    DateTime Codec(DoubleDelta, ZSTD(1)),
+)
+ENGINE = MergeTree
+PARTITION BY toYYYYMM(tradetime)
+ORDER BY (engine, market, secid, boardid, tradeid, tradetime, systime);
@@ -0,0 +1,8 @@
+-- Typed trade side and fixed-point prices, see models::Side and models::decimal64
+ALTER TABLE {db}.trades MODIFY COLUMN buysell Enum8('B' = 1, 'S' = 2) Codec(ZSTD(1));
+--> statement-breakpoint
+ALTER TABLE {db}.trades MODIFY COLUMN price Decimal64(6) Codec(ZSTD(1));
+--> statement-breakpoint
+ALTER TABLE {db}.trades MODIFY COLUMN value Decimal64(6) Codec(ZSTD(1));
+--> statement-breakpoint
+ALTER TABLE {db}.trades ADD COLUMN IF NOT EXISTS decimals UInt8 AFTER value;
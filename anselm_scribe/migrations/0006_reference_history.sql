@@ -0,0 +1,68 @@
+-- Versioned reference data, see reference::VersionedRecord
+-- History before this migration is unknown, existing records are valid from epoch
+-- Tables are swapped by the final RENAME only, every statement before it is safe to re-run
+-- if the migration fails partway: tables are created if missing and copied only while empty
+CREATE TABLE IF NOT EXISTS {db}.engines_history(
+    id               UInt32,
+    name             String,
+    title            String,
+    valid_from       DateTime,
+    valid_to         Nullable(DateTime),
+    version          UInt64
+)
+ENGINE = ReplacingMergeTree(version)
+ORDER BY (name, valid_from);
+--> statement-breakpoint
+
+CREATE TABLE IF NOT EXISTS {db}.markets_history(
+    engine           LowCardinality(String) Codec(ZSTD(1)),
+    id               UInt32,
+    name             String,
+    title            String,
+    valid_from       DateTime,
+    valid_to         Nullable(DateTime),
+    version          UInt64
+)
+ENGINE = ReplacingMergeTree(version)
+ORDER BY (engine, name, valid_from);
+--> statement-breakpoint
+
+CREATE TABLE IF NOT EXISTS {db}.boards_history(
+    engine           LowCardinality(String) Codec(ZSTD(1)),
+    market           LowCardinality(String) Codec(ZSTD(1)),
+    id               UInt32,
+    board_group_id   UInt32,
+    boardid          LowCardinality(String) Codec(ZSTD(1)),
+    title            String,
+    is_traded        Boolean,
+    valid_from       DateTime,
+    valid_to         Nullable(DateTime),
+    version          UInt64
+)
+ENGINE = ReplacingMergeTree(version)
+ORDER BY (engine, market, boardid, valid_from);
+--> statement-breakpoint
+
+INSERT INTO {db}.engines_history
+SELECT DISTINCT id, name, title, toDateTime(0), NULL, 0 FROM {db}.engines
+WHERE (SELECT count() FROM {db}.engines_history) = 0;
+--> statement-breakpoint
+
+INSERT INTO {db}.markets_history
+SELECT DISTINCT engine, id, name, title, toDateTime(0), NULL, 0 FROM {db}.markets
+WHERE (SELECT count() FROM {db}.markets_history) = 0;
+--> statement-breakpoint
+
+INSERT INTO {db}.boards_history
+SELECT DISTINCT engine, market, id, board_group_id, boardid, title, is_traded, toDateTime(0), NULL, 0
+FROM {db}.boards
+WHERE (SELECT count() FROM {db}.boards_history) = 0;
+--> statement-breakpoint
+
+RENAME TABLE
+    {db}.engines TO {db}.engines_legacy,
+    {db}.engines_history TO {db}.engines,
+    {db}.markets TO {db}.markets_legacy,
+    {db}.markets_history TO {db}.markets,
+    {db}.boards TO {db}.boards_legacy,
+    {db}.boards_history TO {db}.boards;
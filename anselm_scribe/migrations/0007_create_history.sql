@@ -0,0 +1,20 @@
+-- Daily ISS history per security loaded with `backfill`, see models::History
+-- Backfilling the same date again replaces its records
+CREATE TABLE IF NOT EXISTS {db}.history(
+    engine     LowCardinality(String) Codec(ZSTD(1)),
+    market     LowCardinality(String) Codec(ZSTD(1)),
+    secid      LowCardinality(String) Codec(ZSTD(1)),
+    boardid    LowCardinality(String) Codec(ZSTD(1)),
+    tradedate  Date Codec(DoubleDelta, ZSTD(1)),
+    numtrades  UInt32,
+    volume     UInt64,
+    value      Decimal64(6) Codec(ZSTD(1)),
+    open       Nullable(Decimal64(6)) Codec(ZSTD(1)),
+    low        Nullable(Decimal64(6)) Codec(ZSTD(1)),
+    high       Nullable(Decimal64(6)) Codec(ZSTD(1)),
+    close      Nullable(Decimal64(6)) Codec(ZSTD(1)),
+    waprice    Nullable(Decimal64(6)) Codec(ZSTD(1))
+)
+ENGINE = ReplacingMergeTree
+PARTITION BY toYYYYMM(tradedate)
+ORDER BY (engine, market, boardid, secid, tradedate);
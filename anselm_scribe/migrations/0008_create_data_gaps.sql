@@ -0,0 +1,20 @@
+-- Gaps in stored trades found by `verify`, see gaps::DataGap
+CREATE TABLE IF NOT EXISTS {db}.data_gaps(
+    engine           LowCardinality(String) Codec(ZSTD(1)),
+    market           LowCardinality(String) Codec(ZSTD(1)),
+    boardid          LowCardinality(String) Codec(ZSTD(1)),
+    secid            LowCardinality(String) Codec(ZSTD(1)),
+    day              Date,
+    kind             Enum8('tradeid' = 1, 'interval' = 2),
+    after_tradeid    UInt64,
+    before_tradeid   UInt64,
+    after_time       DateTime,
+    before_time      DateTime,
+    status           Enum8('open' = 1, 'repaired' = 2, 'empty' = 3),
+    repaired_trades  UInt32,
+    updated_at       DateTime,
+    version          UInt64
+)
+ENGINE = ReplacingMergeTree(version)
+PARTITION BY toYYYYMM(day)
+ORDER BY (engine, market, boardid, secid, kind, after_tradeid);
@@ -0,0 +1,18 @@
+-- Daily trade totals checked against ISS history by `reconcile`, see reconcile::Reconciliation
+CREATE TABLE IF NOT EXISTS {db}.reconciliation(
+    engine      LowCardinality(String) Codec(ZSTD(1)),
+    market      LowCardinality(String) Codec(ZSTD(1)),
+    boardid     LowCardinality(String) Codec(ZSTD(1)),
+    day         Date,
+    secid       LowCardinality(String) Codec(ZSTD(1)),
+    metric      LowCardinality(String),
+    ours        Nullable(Decimal64(6)),
+    official    Nullable(Decimal64(6)),
+    deviation   Float64,
+    matched     Boolean,
+    checked_at  DateTime,
+    version     UInt64
+)
+ENGINE = ReplacingMergeTree(version)
+PARTITION BY toYYYYMM(day)
+ORDER BY (engine, market, boardid, day, secid, metric);
@@ -0,0 +1,25 @@
+-- Ingestion lineage, see ingest::IngestLog
+ALTER TABLE {db}.trades ADD COLUMN IF NOT EXISTS run_id LowCardinality(String) Codec(ZSTD(1)) AFTER systime;
+--> statement-breakpoint
+CREATE TABLE IF NOT EXISTS {db}.ingest_log(
+    run_id         String,
+    logged_at      DateTime,
+    engine         LowCardinality(String) Codec(ZSTD(1)),
+    market         LowCardinality(String) Codec(ZSTD(1)),
+    boardid        LowCardinality(String) Codec(ZSTD(1)),
+    secid          LowCardinality(String) Codec(ZSTD(1)),
+    url            String Codec(ZSTD(1)),
+    params         String Codec(ZSTD(1)),
+    start          UInt64,
+    status         UInt16,
+    bytes          UInt64,
+    replayed       Bool,
+    rows           UInt32,
+    first_tradeid  UInt64,
+    last_tradeid   UInt64,
+    fetch_us       UInt64,
+    parse_us       UInt64
+)
+ENGINE = MergeTree
+PARTITION BY toYYYYMM(logged_at)
+ORDER BY (run_id, engine, market, boardid, first_tradeid);
@@ -0,0 +1,31 @@
+-- Gaps of whole security days and session starts share lower bound 0 across days, so day is
+-- part of the gap key. Table is swapped by the final RENAME only, every statement before it is
+-- safe to re-run if the migration fails partway
+CREATE TABLE IF NOT EXISTS {db}.data_gaps_by_day(
+    engine           LowCardinality(String) Codec(ZSTD(1)),
+    market           LowCardinality(String) Codec(ZSTD(1)),
+    boardid          LowCardinality(String) Codec(ZSTD(1)),
+    secid            LowCardinality(String) Codec(ZSTD(1)),
+    day              Date,
+    kind             Enum8('tradeid' = 1, 'interval' = 2),
+    after_tradeid    UInt64,
+    before_tradeid   UInt64,
+    after_time       DateTime,
+    before_time      DateTime,
+    status           Enum8('open' = 1, 'repaired' = 2, 'empty' = 3),
+    repaired_trades  UInt32,
+    updated_at       DateTime,
+    version          UInt64
+)
+ENGINE = ReplacingMergeTree(version)
+PARTITION BY toYYYYMM(day)
+ORDER BY (engine, market, boardid, secid, kind, day, after_tradeid);
+--> statement-breakpoint
+
+INSERT INTO {db}.data_gaps_by_day SELECT * FROM {db}.data_gaps
+WHERE (SELECT count() FROM {db}.data_gaps_by_day) = 0;
+--> statement-breakpoint
+
+RENAME TABLE
+    {db}.data_gaps TO {db}.data_gaps_legacy,
+    {db}.data_gaps_by_day TO {db}.data_gaps;
@@ -0,0 +1,2 @@
+-- Failed requests are logged too, see ingest::IngestLog
+ALTER TABLE {db}.ingest_log ADD COLUMN IF NOT EXISTS error String Codec(ZSTD(1)) AFTER replayed;
@@ -0,0 +1,19 @@
+use anselm_scribe::config::MirrorCli;
+use anselm_scribe::mirror;
+use anselm_scribe::telemetry;
+
+use clap::Parser;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Load config from CLI arguments and env variables
+    let cli = MirrorCli::parse();
+    let telemetry = telemetry::init_telemetry(&cli.conf, "iss_mirror")?;
+
+    // Serve market data from the selected source
+    let store = mirror::open_store(&cli.conf, cli.source).await?;
+    let served = mirror::serve(&cli.listen, store).await;
+
+    telemetry.shutdown().await;
+    served
+}
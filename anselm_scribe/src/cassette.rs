@@ -0,0 +1,90 @@
+use crate::config::Config;
+use crate::disk::write_file_atomic;
+use crate::iss::IssFormat;
+use std::io;
+use std::path::PathBuf;
+
+/// # Cassette mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CassetteMode {
+    /// Request ISS and store every response
+    Record,
+    /// Serve every request from stored responses without network access
+    Replay,
+}
+
+/// # ISS traffic cassette
+///
+/// Directory with raw ISS response bodies, one file per request. File name is derived from
+/// endpoint path, format and query so the same request always maps to the same file
+/// regardless of ISS base URL:
+///
+/// ```text
+/// engines.json
+/// engines_stock_markets_shares_boards_TQBR_trades.json__start=5000__limit=5000
+/// ```
+///
+/// `tests/fixtures/iss` is a cassette of JSON and CSV responses replayed by the tests.
+#[derive(Debug, Clone)]
+pub struct Cassette {
+    dir: PathBuf,
+    mode: CassetteMode,
+}
+
+/// Implementation for Cassette struct
+impl Cassette {
+    /// # Cassette instance factory
+    pub fn new(dir: &str, mode: CassetteMode) -> Self {
+        Self {
+            dir: PathBuf::from(dir),
+            mode,
+        }
+    }
+
+    /// Cassette selected with `--iss-replay` or `--iss-record`
+    pub fn from_config(conf: &Config) -> Option<Self> {
+        match (&conf.iss_replay, &conf.iss_record) {
+            (Some(dir), _) => Some(Self::new(dir, CassetteMode::Replay)),
+            (None, Some(dir)) => Some(Self::new(dir, CassetteMode::Record)),
+            (None, None) => None,
+        }
+    }
+
+    pub fn mode(&self) -> CassetteMode {
+        self.mode
+    }
+
+    /// File name of request
+    pub fn key(path: &str, format: IssFormat, query: &[(&str, String)]) -> String {
+        let mut key =
+            format!("{}.{}", path.trim_matches('/'), format.extension()).replace('/', "_");
+        for (name, value) in query {
+            key.push_str(&format!("__{}={}", name, value));
+        }
+        key.chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || "._-=".contains(c) {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect()
+    }
+
+    /// Full path of request file
+    pub fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(key)
+    }
+
+    /// Load stored response body
+    pub async fn load(&self, key: &str) -> io::Result<Vec<u8>> {
+        tokio::fs::read(self.path(key)).await
+    }
+
+    /// Store response body, replacing previously recorded one
+    pub async fn save(&self, key: &str, body: &[u8]) -> io::Result<()> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+        write_file_atomic(self.path(key), body).await
+    }
+}
@@ -0,0 +1,72 @@
+use crate::disk::write_file_atomic;
+use crate::models::{Board, ISS_DATE};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
+use time::{Date, OffsetDateTime};
+
+/// # Follow checkpoint
+///
+/// Start of the next trades page of every followed board on trading day `day`. Saved to
+/// `checkpoint_path` after every poll and before exit, so a restarted follow resumes where the
+/// previous one stopped instead of fetching the whole day again.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Moscow trading day of starts
+    pub day: String,
+    /// Run that saved the checkpoint
+    pub run_id: String,
+    /// Unix time of saving
+    pub saved_at: i64,
+    /// Starts by `engine/market/boardid`
+    pub boards: BTreeMap<String, i32>,
+}
+
+/// Implementation for Checkpoint struct
+impl Checkpoint {
+    /// # Load checkpoint from `path`
+    ///
+    /// `None` if no checkpoint was saved yet
+    pub async fn load(path: &str) -> Result<Option<Self>, Box<dyn std::error::Error>> {
+        match tokio::fs::read(path).await {
+            Ok(data) => Ok(Some(serde_json::from_slice(&data)?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// # Save checkpoint of `boards` with starts `starts` to `path` atomically
+    pub async fn save(
+        path: &str,
+        run_id: &str,
+        day: Date,
+        boards: &[Board],
+        starts: &[i32],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let checkpoint = Checkpoint {
+            day: day.format(ISS_DATE)?,
+            run_id: run_id.to_string(),
+            saved_at: OffsetDateTime::now_utc().unix_timestamp(),
+            boards: boards.iter().map(key).zip(starts.iter().copied()).collect(),
+        };
+        write_file_atomic(path, &serde_json::to_vec_pretty(&checkpoint)?).await?;
+        Ok(())
+    }
+
+    /// Starts of `boards` on `day`, 0 for boards not in checkpoint or checkpoints of other days
+    pub fn starts(&self, day: Date, boards: &[Board]) -> Vec<i32> {
+        let same_day = Date::parse(&self.day, ISS_DATE).is_ok_and(|d| d == day);
+        boards
+            .iter()
+            .map(|b| match same_day {
+                true => self.boards.get(&key(b)).copied().unwrap_or(0),
+                false => 0,
+            })
+            .collect()
+    }
+}
+
+/// Checkpoint key of board
+fn key(board: &Board) -> String {
+    format!("{}/{}/{}", board.engine, board.market, board.boardid)
+}
@@ -1,8 +1,42 @@
-use clap::{arg, ArgAction, Parser};
+use crate::gaps::GapRules;
+use crate::models::{ISS_DATE, MOSCOW_OFFSET};
+use crate::reconcile::Tolerance;
+use crate::report::REPORTS;
+use clap::builder::{PossibleValue, PossibleValuesParser};
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
+use time::macros::format_description;
+use time::{Date, Duration, OffsetDateTime, Time};
 
 /// Anselm Scribe - Stock trading system with a proof for existence of Truth
 #[derive(Parser, Clone, Debug)]
 #[command(author, version, about, long_about = None)]
+pub struct Cli {
+    #[command(flatten)]
+    pub conf: Config,
+
+    /// Command to execute
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+/// Anselm ISS Mirror - Local stand-in for MOEX ISS serving stored market data
+#[derive(Parser, Clone, Debug)]
+#[command(author, version, about, long_about = None)]
+pub struct MirrorCli {
+    #[command(flatten)]
+    pub conf: Config,
+
+    /// Specify address the mirror listens on
+    #[arg(long, env = "MIRROR_LISTEN", default_value = "127.0.0.1:8480")]
+    pub listen: String,
+
+    /// Specify market data source of the mirror
+    #[arg(long, env = "MIRROR_SOURCE", value_enum, default_value_t = DataSource::Clickhouse)]
+    pub source: DataSource,
+}
+
+/// Common configuration
+#[derive(Args, Clone, Debug)]
 pub struct Config {
     /// Specify empty market data threshold in days after which market data gathering for a given
     /// security will be skipped
@@ -17,10 +51,87 @@ pub struct Config {
     #[arg(long, env = "MD_DISK", action=ArgAction::SetTrue)]
     pub md_disk: bool,
 
+    /// Specify whether to also archive market data to disk as json files when saving to db
+    #[arg(long, env = "MD_ARCHIVE", action=ArgAction::SetTrue)]
+    pub md_archive: bool,
+
     /// Specify path to which market data file will be written
     #[arg(short = 'p', long, env = "MD_PATH", default_value = "./")]
     pub md_path: String,
 
+    /// Specify number of trades pages fetched from ISS ahead of the page being written
+    #[arg(long, env = "MD_PREFETCH", default_value_t = 4)]
+    pub md_prefetch: usize,
+
+    /// Specify number of pages buffered between fetch, parse and write stages of a board
+    #[arg(long, env = "MD_PIPELINE_BUFFER", default_value_t = 8)]
+    pub md_pipeline_buffer: usize,
+
+    /// Specify S3 URL (s3://bucket/prefix) to which market data files will be uploaded
+    /// instead of `md_path`, credentials are read from `AWS_*` variables
+    #[arg(long, env = "MD_S3_URL")]
+    pub md_s3_url: Option<String>,
+
+    /// Specify S3 endpoint for S3 compatible object storage such as MinIO
+    #[arg(long, env = "MD_S3_ENDPOINT")]
+    pub md_s3_endpoint: Option<String>,
+
+    /// Specify S3 region
+    #[arg(long, env = "MD_S3_REGION")]
+    pub md_s3_region: Option<String>,
+
+    /// Specify path to SQLite database file, market data is also saved to it if defined
+    #[arg(long, env = "MD_SQLITE")]
+    pub md_sqlite: Option<String>,
+
+    /// Specify ISS base URL
+    #[arg(long, env = "ISS_URL", default_value = "https://iss.moex.com/iss")]
+    pub iss_url: String,
+
+    /// Specify ISS endpoints requested in CSV format instead of JSON
+    #[arg(long, env = "ISS_CSV", value_enum, value_delimiter = ',')]
+    pub iss_csv: Vec<IssEndpoint>,
+
+    /// Specify directory to which every ISS response is recorded
+    #[arg(long, env = "ISS_RECORD")]
+    pub iss_record: Option<String>,
+
+    /// Specify directory with ISS responses recorded with `--iss-record` to replay instead of
+    /// requesting ISS
+    #[arg(long, env = "ISS_REPLAY", conflicts_with = "iss_record")]
+    pub iss_replay: Option<String>,
+
+    /// Specify directory of the ledger of sealed daily Merkle roots of trades
+    #[arg(long, env = "LEDGER_PATH", default_value = "./ledger")]
+    pub ledger_path: String,
+
+    /// Specify path of the follow checkpoint with starts of the next trades page of every board
+    #[arg(long, env = "CHECKPOINT_PATH", default_value = "./checkpoint.json")]
+    pub checkpoint_path: String,
+
+    /// Specify path to file with hex encoded Ed25519 secret key signing daily manifests
+    #[arg(long, env = "SIGNING_KEY")]
+    pub signing_key: Option<String>,
+
+    /// Specify log level filter such as `info` or `anselm_scribe=debug,warn`, overridden by
+    /// `RUST_LOG`
+    #[arg(long, env = "LOG_LEVEL", default_value = "info")]
+    pub log_level: String,
+
+    /// Specify log output format
+    #[arg(long, env = "LOG_FORMAT", value_enum, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+
+    /// Specify OTLP gRPC endpoint of a trace collector such as `http://localhost:4317`, traces
+    /// are not exported if undefined
+    #[arg(long, env = "OTEL_EXPORTER_OTLP_ENDPOINT")]
+    pub otlp_endpoint: Option<String>,
+
+    /// Specify address of the Prometheus `/metrics` endpoint such as `0.0.0.0:9464`, metrics
+    /// are not served if undefined
+    #[arg(long, env = "METRICS_LISTEN")]
+    pub metrics_listen: Option<String>,
+
     /// Specify Clickhouse URL
     #[arg(long, env = "CH_URL", default_value = "http://localhost:8123")]
     pub ch_url: String,
@@ -37,6 +148,47 @@ pub struct Config {
     #[arg(long, env = "CH_DB", default_value = "md_moex")]
     pub ch_db: String,
 
+    /// Specify number of rows after which trades batch is flushed to Clickhouse
+    #[arg(long, env = "CH_BATCH_ROWS", default_value_t = 500_000)]
+    pub ch_batch_rows: u64,
+
+    /// Specify number of uncompressed bytes after which trades batch is flushed to Clickhouse
+    #[arg(long, env = "CH_BATCH_BYTES", default_value_t = 50_000_000)]
+    pub ch_batch_bytes: u64,
+
+    /// Specify period in seconds after which trades batch is flushed to Clickhouse
+    #[arg(long, env = "CH_BATCH_PERIOD", default_value_t = 10)]
+    pub ch_batch_period: u64,
+
+    /// Specify whether to use Clickhouse async inserts
+    #[arg(long, env = "CH_ASYNC_INSERT", action=ArgAction::SetTrue)]
+    pub ch_async_insert: bool,
+
+    /// Specify PostgreSQL URL, market data is also saved to PostgreSQL if defined
+    #[arg(long, env = "PG_URL")]
+    pub pg_url: Option<String>,
+
+    /// Specify PostgreSQL schema
+    #[arg(long, env = "PG_SCHEMA", default_value = "md_moex")]
+    pub pg_schema: String,
+
+    /// Specify whether to create trades table as a TimescaleDB hypertable
+    #[arg(long, env = "PG_TIMESCALE", action=ArgAction::SetTrue)]
+    pub pg_timescale: bool,
+
+    /// Specify Kafka bootstrap servers, market data is also published to Kafka if defined
+    #[arg(long, env = "KAFKA_BROKERS")]
+    pub kafka_brokers: Option<String>,
+
+    /// Specify Kafka topic prefix, records are published to `>prefix<.trades`,
+    /// `>prefix<.securities`, `>prefix<.boards`, `>prefix<.markets` and `>prefix<.engines`
+    #[arg(long, env = "KAFKA_TOPIC_PREFIX", default_value = "md_moex")]
+    pub kafka_topic_prefix: String,
+
+    /// Specify Kafka message serialization format
+    #[arg(long, env = "KAFKA_FORMAT", value_enum, default_value_t = KafkaFormat::Json)]
+    pub kafka_format: KafkaFormat,
+
     /// Specify number of threads to uses, 0 will use all available cores
     #[arg(short, long, env = "MD_THREADS", default_value_t = 1)]
     pub threads: usize,
@@ -45,3 +197,459 @@ pub struct Config {
     #[arg(short, long, env = "MD_THREADS", default_value_t = 1000)]
     pub chunks: usize,
 }
+
+/// # Anselm Scribe commands
+///
+/// Exit codes:
+/// - `0` command succeeded
+/// - `1` command failed with an error
+/// - `2` invalid arguments
+/// - `3` verification found duplicates, open gaps or trades changed after sealing,
+///   reconciliation found mismatches, or inclusion proof is invalid
+/// - `4` Clickhouse schema is behind the binary, run `migrate up`
+#[derive(Subcommand, Clone, Debug)]
+pub enum Command {
+    /// Create Clickhouse database and initialize schema of all configured sinks
+    Init,
+
+    /// Manage Clickhouse schema migrations
+    Migrate {
+        #[command(subcommand)]
+        action: MigrateAction,
+    },
+
+    /// Crawl market data of selected boards once
+    Crawl(CrawlArgs),
+
+    /// Crawl trades of selected boards continuously, polling for new trades
+    Follow(FollowArgs),
+
+    /// Load ISS daily history of selected boards for a date range into Clickhouse
+    Backfill(BackfillArgs),
+
+    /// Reconcile daily trade totals of selected boards against ISS daily history
+    Reconcile(ReconcileArgs),
+
+    /// Import market data files saved to disk with `--md-disk` into market data sinks
+    Import {
+        /// Files or directories with market data files to import
+        #[arg(required = true)]
+        paths: Vec<String>,
+    },
+
+    /// Run analytics report from `sql/` against Clickhouse
+    Report(ReportArgs),
+
+    /// Seal daily Merkle roots of stored trades of selected boards into the ledger
+    Seal(SealArgs),
+
+    /// Generate Ed25519 key signing daily manifests at `--signing-key`
+    Keygen,
+
+    /// Sign manifests of sealed days with `--signing-key`
+    Sign(SignArgs),
+
+    /// Export inclusion proof of a sealed trade in a signed daily root
+    Prove(ProveArgs),
+
+    /// Check inclusion proof offline, without database access
+    VerifyProof(VerifyProofArgs),
+
+    /// Verify integrity of stored market data, find and repair gaps in trades, check trades
+    /// against the ledger
+    Verify(VerifyArgs),
+
+    /// Show schema version and ingestion state of stored boards
+    Status,
+}
+
+/// # Board selection
+///
+/// `*` selects all engines, markets or boards
+#[derive(Args, Clone, Debug)]
+pub struct Selection {
+    /// Engines to crawl
+    #[arg(long, value_delimiter = ',', default_value = "stock")]
+    pub engines: Vec<String>,
+
+    /// Markets to crawl
+    #[arg(long, value_delimiter = ',', default_value = "shares")]
+    pub markets: Vec<String>,
+
+    /// Boards to crawl, only traded boards are crawled
+    #[arg(long, value_delimiter = ',', default_value = "TQBR")]
+    pub boards: Vec<String>,
+}
+
+/// Implementation for Selection struct
+impl Selection {
+    fn matches(list: &[String], name: &str) -> bool {
+        list.iter().any(|s| s == "*" || s == name)
+    }
+
+    pub fn engine(&self, engine: &str) -> bool {
+        Self::matches(&self.engines, engine)
+    }
+
+    pub fn market(&self, market: &str) -> bool {
+        Self::matches(&self.markets, market)
+    }
+
+    pub fn board(&self, board: &str) -> bool {
+        Self::matches(&self.boards, board)
+    }
+}
+
+/// `crawl` options
+#[derive(Args, Clone, Debug)]
+pub struct CrawlArgs {
+    #[command(flatten)]
+    pub selection: Selection,
+
+    /// Datasets to crawl
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_values_t = [Dataset::Reference, Dataset::Trades]
+    )]
+    pub datasets: Vec<Dataset>,
+}
+
+/// `follow` options
+#[derive(Args, Clone, Debug)]
+pub struct FollowArgs {
+    #[command(flatten)]
+    pub selection: Selection,
+
+    /// Seconds to wait between polls of selected boards
+    #[arg(long, env = "MD_FOLLOW_INTERVAL", default_value_t = 10)]
+    pub interval: u64,
+}
+
+/// `backfill` options
+#[derive(Args, Clone, Debug)]
+pub struct BackfillArgs {
+    #[command(flatten)]
+    pub selection: Selection,
+
+    /// First date to backfill, YYYY-MM-DD
+    #[arg(long, value_parser = parse_date)]
+    pub from: Date,
+
+    /// Last date to backfill, YYYY-MM-DD
+    #[arg(long, value_parser = parse_date)]
+    pub to: Date,
+}
+
+/// Parse YYYY-MM-DD date argument
+pub fn parse_date(value: &str) -> Result<Date, String> {
+    Date::parse(value, ISS_DATE).map_err(|e| format!("invalid date '{}': {}", value, e))
+}
+
+/// # Range of Moscow trading days
+///
+/// Shared `--from`/`--till` options of commands processing whole days
+#[derive(Args, Clone, Debug)]
+pub struct DayRange {
+    /// First Moscow date, YYYY-MM-DD, yesterday by default
+    #[arg(long, value_parser = parse_date)]
+    pub from: Option<Date>,
+
+    /// Date after the last Moscow date, YYYY-MM-DD, day after `from` by default
+    #[arg(long, value_parser = parse_date)]
+    pub till: Option<Date>,
+}
+
+/// Implementation for DayRange struct
+impl DayRange {
+    /// Dates `[from, till)`
+    pub fn range(&self) -> (Date, Date) {
+        let today = OffsetDateTime::now_utc().to_offset(MOSCOW_OFFSET).date();
+        let from = self.from.unwrap_or(today - Duration::days(1));
+        let till = self.till.unwrap_or(from + Duration::days(1));
+        (from, till)
+    }
+}
+
+/// `reconcile` options
+#[derive(Args, Clone, Debug)]
+pub struct ReconcileArgs {
+    #[command(flatten)]
+    pub selection: Selection,
+
+    #[command(flatten)]
+    pub days: DayRange,
+
+    /// Allowed relative deviation of number of trades and volume
+    #[arg(long, env = "RECONCILE_COUNT_TOLERANCE", default_value_t = 0.0)]
+    pub count_tolerance: f64,
+
+    /// Allowed relative deviation of value, VWAP and OHLC prices
+    #[arg(long, env = "RECONCILE_PRICE_TOLERANCE", default_value_t = 0.0001)]
+    pub price_tolerance: f64,
+}
+
+/// Implementation for ReconcileArgs struct
+impl ReconcileArgs {
+    /// Reconciliation tolerances
+    pub fn tolerance(&self) -> Tolerance {
+        Tolerance {
+            counts: self.count_tolerance,
+            prices: self.price_tolerance,
+        }
+    }
+}
+
+/// `seal` options
+#[derive(Args, Clone, Debug)]
+pub struct SealArgs {
+    #[command(flatten)]
+    pub selection: Selection,
+
+    #[command(flatten)]
+    pub days: DayRange,
+
+    /// Market data source of sealed trades
+    #[arg(long, value_enum, default_value_t = DataSource::Clickhouse)]
+    pub source: DataSource,
+}
+
+/// `sign` options
+#[derive(Args, Clone, Debug)]
+pub struct SignArgs {
+    #[command(flatten)]
+    pub days: DayRange,
+}
+
+/// `prove` options
+#[derive(Args, Clone, Debug)]
+pub struct ProveArgs {
+    /// Board of the trade
+    #[arg(long)]
+    pub board: String,
+
+    /// Security of the trade
+    #[arg(long)]
+    pub secid: String,
+
+    /// Trade id
+    #[arg(long)]
+    pub tradeid: i64,
+
+    /// Market data source the trade is read from
+    #[arg(long, value_enum, default_value_t = DataSource::Clickhouse)]
+    pub source: DataSource,
+
+    /// File the proof is written to, stdout by default
+    #[arg(short, long)]
+    pub output: Option<String>,
+}
+
+/// `verify-proof` options
+#[derive(Args, Clone, Debug)]
+pub struct VerifyProofArgs {
+    /// Proof file exported with `prove`
+    pub proof: String,
+
+    /// Hex encoded Ed25519 public key the daily root must be signed with
+    #[arg(long, env = "SIGNING_PUBLIC_KEY")]
+    pub public_key: String,
+}
+
+/// `verify` options
+#[derive(Args, Clone, Debug)]
+pub struct VerifyArgs {
+    /// First Moscow date checked, YYYY-MM-DD, today for gaps and all sealed days for ledger by
+    /// default
+    #[arg(long, value_parser = parse_date)]
+    pub from: Option<Date>,
+
+    /// Date after the last Moscow date checked, YYYY-MM-DD, day after `from` by default
+    #[arg(long, value_parser = parse_date)]
+    pub till: Option<Date>,
+
+    /// Checks to run
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_values_t = [VerifyCheck::Duplicates, VerifyCheck::Gaps, VerifyCheck::Ledger]
+    )]
+    pub checks: Vec<VerifyCheck>,
+
+    /// Market data source checked against the ledger
+    #[arg(long, value_enum, default_value_t = DataSource::Clickhouse)]
+    pub source: DataSource,
+
+    /// Re-fetch trades of open gaps from ISS
+    #[arg(long, action=ArgAction::SetTrue)]
+    pub repair: bool,
+
+    /// Longest allowed interval in minutes without trades of a security during trading session
+    #[arg(long, env = "GAP_INTERVAL", default_value_t = 30)]
+    pub gap_interval: i64,
+
+    /// Trading session start, HH:MM Moscow time
+    #[arg(long, env = "SESSION_START", value_parser = parse_time, default_value = "10:00")]
+    pub session_start: Time,
+
+    /// Trading session end, HH:MM Moscow time
+    #[arg(long, env = "SESSION_END", value_parser = parse_time, default_value = "18:40")]
+    pub session_end: Time,
+}
+
+/// Implementation for VerifyArgs struct
+impl VerifyArgs {
+    /// Checked dates `[from, till)`
+    pub fn range(&self) -> (Date, Date) {
+        let from = self
+            .from
+            .unwrap_or_else(|| OffsetDateTime::now_utc().to_offset(MOSCOW_OFFSET).date());
+        let till = self.till.unwrap_or(from + Duration::days(1));
+        (from, till)
+    }
+
+    /// Dates of sealed days checked against the ledger, all sealed days if `from` is not defined
+    pub fn ledger_range(&self) -> Option<(Date, Date)> {
+        self.from
+            .map(|from| (from, self.till.unwrap_or(from + Duration::days(1))))
+    }
+
+    /// Check whether check was selected
+    pub fn check(&self, check: VerifyCheck) -> bool {
+        self.checks.contains(&check)
+    }
+
+    /// Gap detection rules
+    pub fn rules(&self) -> GapRules {
+        GapRules {
+            interval: Duration::minutes(self.gap_interval),
+            session_start: self.session_start,
+            session_end: self.session_end,
+        }
+    }
+}
+
+/// Parse HH:MM time argument
+pub fn parse_time(value: &str) -> Result<Time, String> {
+    Time::parse(value, format_description!("[hour]:[minute]"))
+        .map_err(|e| format!("invalid time '{}': {}", value, e))
+}
+
+/// `report` options
+#[derive(Args, Clone, Debug)]
+pub struct ReportArgs {
+    /// Report to run
+    #[arg(value_parser = report_names())]
+    pub name: String,
+
+    /// Table queried instead of report default
+    #[arg(long)]
+    pub table: Option<String>,
+
+    /// First date of report range, YYYY-MM-DD
+    #[arg(long, value_parser = parse_date)]
+    pub from: Option<Date>,
+
+    /// Date after the last date of report range, YYYY-MM-DD
+    #[arg(long, value_parser = parse_date)]
+    pub till: Option<Date>,
+
+    /// Report output format
+    #[arg(long, value_enum, default_value_t = ReportFormat::Table)]
+    pub format: ReportFormat,
+}
+
+/// Report names with descriptions shown in help
+fn report_names() -> PossibleValuesParser {
+    PossibleValuesParser::new(
+        REPORTS
+            .iter()
+            .map(|r| PossibleValue::new(r.name).help(r.description)),
+    )
+}
+
+/// Report output formats
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum ReportFormat {
+    /// Aligned text table
+    Table,
+    /// CSV with header
+    Csv,
+    /// JSON array of objects
+    Json,
+}
+
+/// Log output formats
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human readable lines
+    Text,
+    /// JSON object per line for log shippers
+    Json,
+}
+
+/// Crawled datasets
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dataset {
+    /// Engines, markets and boards
+    Reference,
+    /// Board trades
+    Trades,
+}
+
+/// Verification checks
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VerifyCheck {
+    /// Trades stored more than once in Clickhouse
+    Duplicates,
+    /// Gaps in stored trades
+    Gaps,
+    /// Trades altered, removed or added after their day was sealed into the ledger
+    Ledger,
+}
+
+/// Schema migration actions
+#[derive(Subcommand, Clone, Debug)]
+pub enum MigrateAction {
+    /// Apply pending migrations
+    Up,
+    /// Show applied and pending migrations
+    Status,
+}
+
+/// Kafka message serialization formats
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum KafkaFormat {
+    /// JSON, same as disk files
+    Json,
+    /// Compact binary bincode encoding, trades are published as `kafka::KafkaTrade`
+    Bincode,
+}
+
+/// ISS endpoints
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IssEndpoint {
+    /// `/engines`
+    Engines,
+    /// `/engines/>engine</markets`
+    Markets,
+    /// `/engines/>engine</markets/>market</boards`
+    Boards,
+    /// `/engines/>engine</markets/>market</boards/>board</securities`
+    Securities,
+    /// `/engines/>engine</markets/>market</boards/>board</trades`
+    Trades,
+    /// `/history/engines/>engine</markets/>market</boards/>board</securities`
+    History,
+}
+
+/// Stored market data sources
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum DataSource {
+    /// Clickhouse tables written by the scribe
+    Clickhouse,
+    /// Market data files saved to `md_path` with `--md-disk`
+    Disk,
+}
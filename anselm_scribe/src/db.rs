@@ -1,11 +1,118 @@
 use crate::config::Config;
-use crate::models::{Board, Engine, Market, Trade};
-use clickhouse::{error::Result, sql, Client};
+use crate::gaps::{DataGap, GapCandidate, GapStatus, TradeCount};
+use crate::ingest::IngestLog;
+use crate::metrics;
+use crate::migrations::{latest_version, MIGRATIONS};
+use crate::models::{decimal64, enum8, Board, Engine, History, Market, Side, Trade, MOSCOW_OFFSET};
+use crate::reconcile::{DailyTotals, Reconciliation};
+use crate::reference::{BoardVersion, EngineVersion, MarketVersion, VersionedRecord};
+use crate::sink::MarketDataSink;
+use crate::writer::{TradeWriter, WriterConfig};
+use async_trait::async_trait;
+use clickhouse::{
+    error::{Error, Result},
+    sql, Client, Row,
+};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
+use time::{Date, OffsetDateTime};
+use tokio::sync::Mutex;
+use tracing::{debug, info};
 
 /// # Clickhouse Clickhouse Database struct
 pub struct ClickhouseDatabase {
     client: Client,
     db: String,
+    writer_conf: WriterConfig,
+    trades_writer: Mutex<Option<TradeWriter>>,
+}
+
+/// # Trade row of Clickhouse `trades` table
+///
+/// Same fields as `Trade` with side as `Enum8` code and prices as `Decimal64` mantissas
+#[derive(Debug, Clone, Serialize, Deserialize, Row)]
+pub struct ClickhouseTrade {
+    pub engine: String,
+    pub market: String,
+    pub secid: String,
+    pub boardid: String,
+    pub tradeid: i64,
+    #[serde(with = "enum8")]
+    pub buysell: Side,
+    pub quantity: i32,
+    #[serde(with = "decimal64")]
+    pub price: Decimal,
+    #[serde(with = "decimal64")]
+    pub value: Decimal,
+    pub decimals: u8,
+    #[serde(with = "clickhouse::serde::time::datetime")]
+    pub tradetime: OffsetDateTime,
+    #[serde(with = "clickhouse::serde::time::datetime")]
+    pub systime: OffsetDateTime,
+    #[serde(default)]
+    pub run_id: String,
+}
+
+impl From<&Trade> for ClickhouseTrade {
+    fn from(t: &Trade) -> Self {
+        ClickhouseTrade {
+            engine: t.engine.clone(),
+            market: t.market.clone(),
+            secid: t.secid.clone(),
+            boardid: t.boardid.clone(),
+            tradeid: t.tradeid,
+            buysell: t.buysell,
+            quantity: t.quantity,
+            price: t.price,
+            value: t.value,
+            decimals: t.decimals,
+            tradetime: t.tradetime,
+            systime: t.systime,
+            run_id: t.run_id.clone(),
+        }
+    }
+}
+
+impl From<ClickhouseTrade> for Trade {
+    fn from(t: ClickhouseTrade) -> Self {
+        Trade {
+            engine: t.engine,
+            market: t.market,
+            secid: t.secid,
+            boardid: t.boardid,
+            tradeid: t.tradeid,
+            buysell: t.buysell,
+            quantity: t.quantity,
+            price: t.price,
+            value: t.value,
+            decimals: t.decimals,
+            tradetime: t.tradetime,
+            systime: t.systime,
+            run_id: t.run_id,
+        }
+    }
+}
+
+/// Applied schema migration record
+#[derive(Debug, Clone, Serialize, Deserialize, Row)]
+pub struct AppliedMigration {
+    pub version: u32,
+    pub name: String,
+    #[serde(with = "clickhouse::serde::time::datetime")]
+    pub applied_at: OffsetDateTime,
+}
+
+/// Stored trades of a board
+#[derive(Debug, Clone, Serialize, Deserialize, Row)]
+pub struct BoardStatus {
+    pub engine: String,
+    pub market: String,
+    pub boardid: String,
+    pub trades: u64,
+    #[serde(with = "clickhouse::serde::time::datetime")]
+    pub last_trade: OffsetDateTime,
 }
 
 /// # Implementation for ClickhouseDatabase Struct
@@ -20,47 +127,55 @@ impl ClickhouseDatabase {
         Self {
             client,
             db: conf.ch_db.clone(),
+            writer_conf: WriterConfig::new(conf),
+            trades_writer: Mutex::new(None),
         }
     }
 
     /// # Init database with required tables
     ///
     /// ## Steps
-    /// Create defined database tables if they do not exist:
-    /// - `>db_name<.engines`
-    /// - `>db_name<.markets`
-    /// - `>db_name<.boards`
-    /// - `>db_name<.trades`
+    /// - Create `>db_name<` database and `>db_name<.schema_migrations` table if they do not exist
+    /// - Apply all migrations if database is empty
+    /// - Refuse to continue if database schema is older than expected by this binary
     ///
     pub async fn init(&self) -> Result<()> {
+        self.init_database().await?;
+
+        let version = self.schema_version().await?;
+        if version == 0 && !self.table_exists("trades").await? {
+            info!(db = %self.db, "Empty database, applying all migrations");
+            self.migrate_up().await?;
+        } else if version < latest_version() {
+            return Err(Error::Custom(format!(
+                "Schema version {} of database '{}' is older than expected version {}, run `migrate up`",
+                version,
+                self.db,
+                latest_version()
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// # Create database and Schema Migrations table if they do not exist
+    pub async fn init_database(&self) -> Result<()> {
         self.client
             .query("CREATE DATABASE IF NOT EXISTS ?")
             .bind(sql::Identifier(self.db.as_str()))
             .execute()
             .await?;
 
-        // Init DB tables
-        self.init_engines().await?;
-        self.init_markets().await?;
-        self.init_boards().await?;
-        self.init_trades().await?;
-
-        Ok(())
-    }
-
-    /// # Initialize Engine Record table
-    pub async fn init_engines(&self) -> Result<()> {
         self.client
             .query(
                 "
-                CREATE TABLE IF NOT EXISTS ?.engines(
-                    id               UInt32,
+                CREATE TABLE IF NOT EXISTS ?.schema_migrations(
+                    version          UInt32,
                     name             String,
-                    title            String
+                    applied_at       DateTime
                 )
                 ENGINE = MergeTree
-                PRIMARY KEY (id)
-                ORDER BY (id,name);
+                ORDER BY (version);
                 ",
             )
             .bind(sql::Identifier(self.db.as_str()))
@@ -69,181 +184,593 @@ impl ClickhouseDatabase {
         Ok(())
     }
 
-    /// # Initialize Market Record table
-    pub async fn init_markets(&self) -> Result<()> {
+    /// # Check whether table exists in database
+    async fn table_exists(&self, table: &str) -> Result<bool> {
+        let count = self
+            .client
+            .query("SELECT count() FROM system.tables WHERE database=? AND name=?")
+            .bind(&self.db)
+            .bind(table)
+            .fetch_one::<u64>()
+            .await?;
+        Ok(count > 0)
+    }
+
+    /// # Get current schema version, 0 if no migrations were applied
+    pub async fn schema_version(&self) -> Result<u32> {
+        self.client
+            .query("SELECT max(version) FROM ?.schema_migrations")
+            .bind(sql::Identifier(self.db.as_str()))
+            .fetch_one::<u32>()
+            .await
+    }
+
+    /// # Get applied migrations ordered by version
+    pub async fn applied_migrations(&self) -> Result<Vec<AppliedMigration>> {
+        self.client
+            .query("SELECT ?fields FROM ?.schema_migrations ORDER BY version")
+            .bind(sql::Identifier(self.db.as_str()))
+            .fetch_all::<AppliedMigration>()
+            .await
+    }
+
+    /// # Apply pending migrations
+    ///
+    /// Returns versions of applied migrations
+    pub async fn migrate_up(&self) -> Result<Vec<u32>> {
+        let version = self.schema_version().await?;
+        let mut applied: Vec<u32> = Vec::new();
+
+        for migration in MIGRATIONS.iter().filter(|m| m.version > version) {
+            for statement in migration.statements(&self.db) {
+                self.client.query(&statement).execute().await?;
+            }
+
+            let mut insert = self
+                .client
+                .insert(format!("{}.schema_migrations", self.db).as_str())?;
+            insert
+                .write(&AppliedMigration {
+                    version: migration.version,
+                    name: migration.name.to_string(),
+                    applied_at: OffsetDateTime::now_utc(),
+                })
+                .await?;
+            insert.end().await?;
+
+            info!(
+                version = migration.version,
+                name = migration.name,
+                "Migration applied"
+            );
+            applied.push(migration.version);
+        }
+
+        Ok(applied)
+    }
+
+    /// # Insert a batch of Engine Records into database
+    ///
+    /// See `upsert_versioned`
+    pub async fn insert_engines(&self, engines: &[Engine]) -> Result<()> {
+        self.upsert_versioned::<EngineVersion>(engines).await
+    }
+
+    /// # Insert a batch of Market Records into database
+    ///
+    /// See `upsert_versioned`
+    pub async fn insert_markets(&self, markets: &[Market]) -> Result<()> {
+        self.upsert_versioned::<MarketVersion>(markets).await
+    }
+
+    /// # Insert a batch of Board Records into database
+    ///
+    /// See `upsert_versioned`
+    pub async fn insert_boards(&self, boards: &[Board]) -> Result<()> {
+        self.upsert_versioned::<BoardVersion>(boards).await
+    }
+
+    /// # Upsert a batch of reference data records with change history
+    ///
+    /// ## Steps
+    /// - Fetch current versions of all records in a single query
+    /// - Diff batch against current versions
+    /// - Insert new versions and close changed versions in a single insert
+    ///
+    /// Batch must hold the complete ISS listing of every scope it touches, e.g. all boards of a
+    /// market. Open records of these scopes missing from the batch were removed from ISS and
+    /// are closed, records of other scopes are left open.
+    pub async fn upsert_versioned<R: VersionedRecord>(&self, records: &[R::Source]) -> Result<()> {
+        let current: HashMap<String, R> = self
+            .current_versions::<R>()
+            .await?
+            .into_iter()
+            .map(|r| (r.key(), r))
+            .collect();
+        let scopes: HashSet<String> = records.iter().map(R::source_scope).collect();
+        let listed: HashSet<String> = records.iter().map(R::source_key).collect();
+
+        let now = OffsetDateTime::now_utc();
+        let version = now.unix_timestamp_nanos() as u64;
+        let mut rows: Vec<R> = Vec::new();
+        for record in records {
+            let key = R::source_key(record);
+            match current.get(&key) {
+                None => {
+                    debug!(table = R::TABLE, record = %key, "Record inserted");
+                    rows.push(R::open(record, now, version));
+                }
+                Some(existing) if existing.changed(record) => {
+                    info!(table = R::TABLE, record = %key, "Record changed");
+                    let mut closed = existing.clone();
+                    closed.close(now, version);
+                    rows.push(closed);
+                    rows.push(R::open(record, now, version));
+                }
+                Some(_) => debug!(table = R::TABLE, record = %key, "Record unchanged"),
+            }
+        }
+        for (key, existing) in &current {
+            if scopes.contains(&existing.scope()) && !listed.contains(key) {
+                info!(table = R::TABLE, record = %key, "Record removed");
+                let mut closed = existing.clone();
+                closed.close(now, version);
+                rows.push(closed);
+            }
+        }
+
+        if rows.is_empty() {
+            return Ok(());
+        }
+        self.insert_rows(R::TABLE, &rows).await
+    }
+
+    /// # Insert rows into `table` with a single insert
+    ///
+    /// Insert latency and errors are recorded in metrics
+    async fn insert_rows<R: Row + Serialize>(&self, table: &str, rows: &[R]) -> Result<()> {
+        let time_insert = Instant::now();
+        let result = async {
+            let mut insert = self
+                .client
+                .insert(format!("{}.{}", self.db, table).as_str())?;
+            for row in rows {
+                insert.write(row).await?;
+            }
+            insert.end().await
+        }
+        .await;
+        metrics::clickhouse_insert(table, time_insert.elapsed(), result.is_err());
+        result
+    }
+
+    /// Fetch current versions of all reference data records
+    pub async fn current_versions<R: VersionedRecord>(&self) -> Result<Vec<R>> {
+        self.client
+            .query("SELECT ?fields FROM ?.? FINAL WHERE valid_to IS NULL")
+            .bind(sql::Identifier(self.db.as_str()))
+            .bind(sql::Identifier(R::TABLE))
+            .fetch_all::<R>()
+            .await
+    }
+
+    /// # Fetch page of board trades ordered by trade id
+    ///
+    /// Duplicate trades are skipped so pages match ISS pagination, trades of all securities are
+    /// fetched if `secid` is `None`
+    pub async fn fetch_trades_page(
+        &self,
+        engine: &str,
+        market: &str,
+        boardid: &str,
+        secid: Option<&str>,
+        start: u64,
+        limit: u64,
+    ) -> Result<Vec<Trade>> {
         self.client
             .query(
-                "
-                CREATE TABLE IF NOT EXISTS ?.markets(
-                    engine           LowCardinality(String) Codec(ZSTD(1)),
-                    id               UInt32,
-                    name             String,
-                    title            String
-                )
-                ENGINE = MergeTree
-                PRIMARY KEY (id)
-                ORDER BY (id,name);
-                ",
+                "SELECT ?fields FROM ?.trades \
+                WHERE engine = ? AND market = ? AND boardid = ? AND (? = '' OR secid = ?) \
+                ORDER BY tradeid LIMIT 1 BY tradeid LIMIT ? OFFSET ?",
             )
             .bind(sql::Identifier(self.db.as_str()))
-            .execute()
-            .await?;
-        Ok(())
+            .bind(engine)
+            .bind(market)
+            .bind(boardid)
+            .bind(secid.unwrap_or(""))
+            .bind(secid.unwrap_or(""))
+            .bind(limit)
+            .bind(start)
+            .fetch_all::<ClickhouseTrade>()
+            .await
+            .map(|rows| rows.into_iter().map(Trade::from).collect())
     }
 
-    /// # Initialize Boards Record table
-    pub async fn init_boards(&self) -> Result<()> {
+    /// # Fetch board trades with trade id above `after` ordered by trade id
+    ///
+    /// Trades of all securities are fetched if `secid` is `None`
+    pub async fn fetch_trades_after(
+        &self,
+        engine: &str,
+        market: &str,
+        boardid: &str,
+        secid: Option<&str>,
+        after: u64,
+        limit: u64,
+    ) -> Result<Vec<Trade>> {
         self.client
             .query(
-                "
-                CREATE TABLE IF NOT EXISTS ?.boards(
-                    engine           LowCardinality(String) Codec(ZSTD(1)),
-                    market           LowCardinality(String) Codec(ZSTD(1)),
-                    id               UInt32,
-                    board_group_id   UInt32,
-                    boardid          LowCardinality(String) Codec(ZSTD(1)),
-                    title            String,
-                    is_traded        Boolean,
-                )
-                ENGINE = MergeTree
-                PRIMARY KEY (engine, market, boardid)
-                ORDER BY (engine, market, boardid);
-                ",
+                "SELECT ?fields FROM ?.trades \
+                WHERE engine = ? AND market = ? AND boardid = ? AND (? = '' OR secid = ?) \
+                AND tradeid > ? \
+                ORDER BY tradeid LIMIT 1 BY tradeid LIMIT ?",
             )
             .bind(sql::Identifier(self.db.as_str()))
-            .execute()
-            .await?;
-        Ok(())
+            .bind(engine)
+            .bind(market)
+            .bind(boardid)
+            .bind(secid.unwrap_or(""))
+            .bind(secid.unwrap_or(""))
+            .bind(after)
+            .bind(limit)
+            .fetch_all::<ClickhouseTrade>()
+            .await
+            .map(|rows| rows.into_iter().map(Trade::from).collect())
     }
 
-    /// # Initialize Trade Record table
-    pub async fn init_trades(&self) -> Result<()> {
+    /// # Fetch board trades within `[from, till)` ordered by trade id
+    ///
+    /// Trades of all securities are fetched if `secid` is `None`
+    pub async fn fetch_trades_between(
+        &self,
+        engine: &str,
+        market: &str,
+        boardid: &str,
+        secid: Option<&str>,
+        from: OffsetDateTime,
+        till: OffsetDateTime,
+    ) -> Result<Vec<Trade>> {
         self.client
-            // TODO: enum
-            //  buysell    Enum8('B' = 1, 'S' = 2) Codec(ZSTD(1)),
             .query(
-                "
-                CREATE TABLE IF NOT EXISTS ?.trades(
-                    engine     LowCardinality(String) Codec(ZSTD(1)),
-                    market     LowCardinality(String) Codec(ZSTD(1)),
-                    secid      LowCardinality(String) Codec(ZSTD(1)),
-                    boardid    LowCardinality(String) Codec(ZSTD(1)),
-                    tradeid    UInt64 Codec(Delta, Default),
-                    buysell    LowCardinality(String) Codec(ZSTD(1)),
-                    quantity   UInt32,
-                    price      Float64 Codec(Gorilla, ZSTD(1)),
-                    value      Float64 Codec(Gorilla, ZSTD(1)),
-                    tradetime  DateTime Codec(DoubleDelta, ZSTD(1)),
-                    systime    DateTime Codec(DoubleDelta, ZSTD(1)),
-                )
-                ENGINE = MergeTree
-                PARTITION BY toYYYYMM(tradetime)
-                ORDER BY (engine, market, secid, boardid, tradeid, tradetime, systime);
-                ",
+                "SELECT ?fields FROM ?.trades \
+                WHERE engine = ? AND market = ? AND boardid = ? AND (? = '' OR secid = ?) \
+                AND tradetime >= ? AND tradetime < ? \
+                ORDER BY tradeid LIMIT 1 BY tradeid",
             )
             .bind(sql::Identifier(self.db.as_str()))
-            .execute()
-            .await?;
-        Ok(())
+            .bind(engine)
+            .bind(market)
+            .bind(boardid)
+            .bind(secid.unwrap_or(""))
+            .bind(secid.unwrap_or(""))
+            .bind(from.unix_timestamp())
+            .bind(till.unix_timestamp())
+            .fetch_all::<ClickhouseTrade>()
+            .await
+            .map(|rows| rows.into_iter().map(Trade::from).collect())
     }
 
-    /// # Insert a batch of Engine Records into database
-    pub async fn insert_engines(&self, engines: &[Engine]) -> Result<()> {
-        // Create sesstion for multiple insertions
-        let mut insert = self
+    /// Time of the last board trade, `None` if board has no trades
+    pub async fn last_trade_time(
+        &self,
+        engine: &str,
+        market: &str,
+        boardid: &str,
+    ) -> Result<Option<OffsetDateTime>> {
+        let last: u32 = self
             .client
-            .insert(format!("{}.engines", self.db).as_str())?;
+            .query(
+                "SELECT toUnixTimestamp(max(tradetime)) FROM ?.trades \
+                WHERE engine = ? AND market = ? AND boardid = ?",
+            )
+            .bind(sql::Identifier(self.db.as_str()))
+            .bind(engine)
+            .bind(market)
+            .bind(boardid)
+            .fetch_one()
+            .await?;
+        Ok(match last {
+            0 => None,
+            last => OffsetDateTime::from_unix_timestamp(last as i64).ok(),
+        })
+    }
 
-        for engine in engines {
-            // Check if Engine with name already exists
-            let count = self
-                .client
-                .query("SELECT count() FROM ?.engines WHERE name=?")
-                .bind(sql::Identifier(self.db.as_str()))
-                .bind(&engine.name)
-                .fetch_one::<u64>()
-                .await?;
+    /// # Trade ids of board stored within `[from, till]`
+    pub async fn stored_tradeids(
+        &self,
+        engine: &str,
+        market: &str,
+        boardid: &str,
+        from: i64,
+        till: i64,
+    ) -> Result<Vec<i64>> {
+        self.client
+            .query(
+                "SELECT DISTINCT tradeid FROM ?.trades \
+                WHERE engine = ? AND market = ? AND boardid = ? \
+                AND tradeid >= ? AND tradeid <= ?",
+            )
+            .bind(sql::Identifier(self.db.as_str()))
+            .bind(engine)
+            .bind(market)
+            .bind(boardid)
+            .bind(from)
+            .bind(till)
+            .fetch_all::<i64>()
+            .await
+    }
 
-            if count == 0 {
-                println!("Inserting DB: Engine '{}'", engine.name);
-                insert.write(engine).await?;
-            } else {
-                println!("Exists in DB: Engine '{}'", engine.name);
-            }
-        }
-        insert.end().await.unwrap();
-        Ok(())
+    /// # Stored trades per board ordered by board
+    pub async fn board_status(&self) -> Result<Vec<BoardStatus>> {
+        self.client
+            .query(
+                "SELECT engine, market, boardid, count() AS trades, max(tradetime) AS last_trade \
+                FROM ?.trades GROUP BY engine, market, boardid ORDER BY engine, market, boardid",
+            )
+            .bind(sql::Identifier(self.db.as_str()))
+            .fetch_all::<BoardStatus>()
+            .await
     }
 
-    /// # Insert a batch of Market Records into database
-    pub async fn insert_markets(&self, markets: &[Market]) -> Result<()> {
-        // Create sesstion for multiple insertions
-        let mut insert = self
+    /// # Run report query returning every row as `JSONEachRow` object
+    ///
+    /// `params` are passed as Clickhouse query parameters referenced as `{name:Type}`
+    pub async fn fetch_report(
+        &self,
+        query: &str,
+        params: &[(&str, String)],
+    ) -> Result<Vec<String>> {
+        // Keep 64 bit integers as JSON numbers
+        let mut client = self
             .client
-            .insert(format!("{}.markets", self.db).as_str())?;
+            .clone()
+            .with_option("output_format_json_quote_64bit_integers", "0");
+        for (name, value) in params {
+            client = client.with_option(format!("param_{}", name), value);
+        }
 
-        for market in markets {
-            // Check if Market already exists
-            let count = self
-                .client
-                .query("SELECT count() FROM ?.markets WHERE name=? AND engine=?")
-                .bind(sql::Identifier(self.db.as_str()))
-                .bind(&market.name)
-                .bind(&market.engine)
-                .fetch_one::<u64>()
-                .await?;
+        let query = query.trim().trim_end_matches(';');
+        client
+            .query(&format!(
+                "SELECT formatRowNoNewline('JSONEachRow', *) FROM ({}\n)",
+                query
+            ))
+            .fetch_all::<String>()
+            .await
+    }
 
-            if count == 0 {
-                println!(
-                    "Inserting DB: Market '{}' for Engine '{}'",
-                    market.name, market.engine
-                );
-                insert.write(market).await?;
-            } else {
-                println!(
-                    "Exists in DB: Market '{}' for Engine '{}'",
-                    market.name, market.engine
-                );
-            }
-        }
+    /// # Number of trade ids stored more than once within a board
+    pub async fn duplicate_trades(&self) -> Result<u64> {
+        self.client
+            .query(
+                "SELECT count() FROM (SELECT tradeid FROM ?.trades \
+                GROUP BY engine, market, boardid, tradeid HAVING count() > 1)",
+            )
+            .bind(sql::Identifier(self.db.as_str()))
+            .fetch_one::<u64>()
+            .await
+    }
 
-        insert.end().await.unwrap();
-        Ok(())
+    /// # Find security days with fewer stored trades than ISS daily history reports
+    ///
+    /// Trade numbers are shared by all boards of a market, so steps between them say nothing
+    /// about missing trades, `numtrades` of `history` loaded with `backfill` is counted against
+    /// stored trades instead. Only boards with stored trades on a day are checked.
+    pub async fn find_count_gaps(&self, from: Date, till: Date) -> Result<Vec<TradeCount>> {
+        let range_from = from
+            .midnight()
+            .assume_offset(MOSCOW_OFFSET)
+            .unix_timestamp();
+        let range_till = till
+            .midnight()
+            .assume_offset(MOSCOW_OFFSET)
+            .unix_timestamp();
+        self.client
+            .query(
+                "SELECT ?fields FROM ( \
+                    SELECT engine, market, boardid, secid, tradedate AS day, numtrades \
+                    FROM ?.history FINAL \
+                    WHERE tradedate >= toDate(?) AND tradedate < toDate(?) AND numtrades > 0) AS h \
+                INNER JOIN ( \
+                    SELECT DISTINCT engine, market, boardid, toDate(tradetime, 'Europe/Moscow') AS day \
+                    FROM ?.trades WHERE tradetime >= ? AND tradetime < ?) AS b \
+                USING (engine, market, boardid, day) \
+                LEFT JOIN ( \
+                    SELECT engine, market, boardid, secid, \
+                        toDate(tradetime, 'Europe/Moscow') AS day, uniqExact(tradeid) AS stored \
+                    FROM ?.trades WHERE tradetime >= ? AND tradetime < ? \
+                    GROUP BY engine, market, boardid, secid, day) AS t \
+                USING (engine, market, boardid, secid, day) \
+                WHERE stored < numtrades \
+                ORDER BY engine, market, boardid, secid, day",
+            )
+            .bind(sql::Identifier(self.db.as_str()))
+            .bind(from.to_string())
+            .bind(till.to_string())
+            .bind(sql::Identifier(self.db.as_str()))
+            .bind(range_from)
+            .bind(range_till)
+            .bind(sql::Identifier(self.db.as_str()))
+            .bind(range_from)
+            .bind(range_till)
+            .fetch_all::<TradeCount>()
+            .await
     }
 
-    /// # Insert a batch of Board Records into database
-    pub async fn insert_boards(&self, boards: &[Board]) -> Result<()> {
-        // Create sesstion for multiple insertions
-        let mut insert = self.client.insert(format!("{}.boards", self.db).as_str())?;
+    /// Number of ISS daily history records of dates `[from, till)`
+    pub async fn history_records(&self, from: Date, till: Date) -> Result<u64> {
+        self.client
+            .query(
+                "SELECT count() FROM ?.history FINAL \
+                WHERE tradedate >= toDate(?) AND tradedate < toDate(?)",
+            )
+            .bind(sql::Identifier(self.db.as_str()))
+            .bind(from.to_string())
+            .bind(till.to_string())
+            .fetch_one::<u64>()
+            .await
+    }
 
-        for board in boards {
-            // Check if Board already exists
-            let count = self
-                .client
-                .query("SELECT count() FROM ?.boards WHERE boardid=? AND market=? AND engine=?")
-                .bind(sql::Identifier(self.db.as_str()))
-                .bind(&board.boardid)
-                .bind(&board.market)
-                .bind(&board.engine)
-                .fetch_one::<u64>()
-                .await?;
+    /// # Find consecutive security trades more than `seconds` apart
+    ///
+    /// Trades are compared within Moscow date of `[from, till)`. Every security day with
+    /// trades also gets session edges `session_start` and `session_end` seconds after Moscow
+    /// midnight, so intervals before the first and after the last trade are found as well.
+    /// Edges have trade ids `0` and `u64::MAX`.
+    pub async fn find_interval_gaps(
+        &self,
+        from: OffsetDateTime,
+        till: OffsetDateTime,
+        seconds: u64,
+        session_start: u32,
+        session_end: u32,
+    ) -> Result<Vec<GapCandidate>> {
+        self.client
+            .query(
+                "SELECT ?fields FROM ( \
+                    SELECT engine, market, boardid, secid, tradeid, tradetime, \
+                        lagInFrame(tradeid) OVER w AS prev_tradeid, \
+                        lagInFrame(tradetime) OVER w AS prev_time \
+                    FROM ( \
+                        SELECT DISTINCT engine, market, boardid, secid, tradeid, tradetime \
+                        FROM ?.trades WHERE tradetime >= ? AND tradetime < ? \
+                        UNION ALL \
+                        SELECT engine, market, boardid, secid, edge.1 AS tradeid, \
+                            toDateTime(toUnixTimestamp(toDateTime(day, 'Europe/Moscow')) + edge.2) \
+                            AS tradetime \
+                        FROM (SELECT DISTINCT engine, market, boardid, secid, \
+                                toDate(tradetime, 'Europe/Moscow') AS day \
+                            FROM ?.trades WHERE tradetime >= ? AND tradetime < ?) \
+                        ARRAY JOIN [(toUInt64(0), toUInt32(?)), \
+                            (toUInt64(18446744073709551615), toUInt32(?))] AS edge) \
+                    WINDOW w AS (PARTITION BY engine, market, boardid, secid, \
+                        toDate(tradetime, 'Europe/Moscow') \
+                        ORDER BY tradetime, tradeid ROWS BETWEEN 1 PRECEDING AND CURRENT ROW)) \
+                WHERE toUnixTimestamp(prev_time) > 0 AND tradetime - prev_time > ? \
+                ORDER BY engine, market, boardid, secid, tradeid",
+            )
+            .bind(sql::Identifier(self.db.as_str()))
+            .bind(from.unix_timestamp())
+            .bind(till.unix_timestamp())
+            .bind(sql::Identifier(self.db.as_str()))
+            .bind(from.unix_timestamp())
+            .bind(till.unix_timestamp())
+            .bind(session_start)
+            .bind(session_end)
+            .bind(seconds)
+            .fetch_all::<GapCandidate>()
+            .await
+    }
 
-            if count == 0 {
-                println!(
-                    "Inserting DB: Board '{}' for Market '{}' for Engine '{}'",
-                    board.boardid, board.market, board.engine,
-                );
-                insert.write(board).await?;
-            } else {
-                println!(
-                    "Exists in DB: Board '{}' for Market '{}' for Engine '{}'",
-                    board.boardid, board.market, board.engine,
-                );
-            }
+    /// # Stored trade ids within gap
+    ///
+    /// Trade ids between gap bounds of trades within gap times, of gap security if defined
+    pub async fn gap_tradeids(&self, gap: &DataGap) -> Result<Vec<u64>> {
+        self.client
+            .query(
+                "SELECT DISTINCT tradeid FROM ?.trades \
+                WHERE engine = ? AND market = ? AND boardid = ? AND (? = '' OR secid = ?) \
+                AND tradeid > ? AND tradeid < ? AND tradetime >= ? AND tradetime <= ?",
+            )
+            .bind(sql::Identifier(self.db.as_str()))
+            .bind(gap.engine.as_str())
+            .bind(gap.market.as_str())
+            .bind(gap.boardid.as_str())
+            .bind(gap.secid.as_str())
+            .bind(gap.secid.as_str())
+            .bind(gap.after_tradeid)
+            .bind(gap.before_tradeid)
+            .bind(gap.after_time.unix_timestamp())
+            .bind(gap.before_time.unix_timestamp())
+            .fetch_all::<u64>()
+            .await
+    }
+
+    /// # Fetch gaps of days `[from, till)`, all gaps if `status` is `None`
+    pub async fn data_gaps(
+        &self,
+        from: Date,
+        till: Date,
+        status: Option<GapStatus>,
+    ) -> Result<Vec<DataGap>> {
+        self.client
+            .query(
+                "SELECT ?fields FROM ?.data_gaps FINAL \
+                WHERE day >= toDate(?) AND day < toDate(?) AND (? = 0 OR CAST(status, 'Int8') = ?) \
+                ORDER BY engine, market, boardid, secid, kind, day, after_tradeid",
+            )
+            .bind(sql::Identifier(self.db.as_str()))
+            .bind(from.to_string())
+            .bind(till.to_string())
+            .bind(status.map_or(0, |s| s as i8))
+            .bind(status.map_or(0, |s| s as i8))
+            .fetch_all::<DataGap>()
+            .await
+    }
+
+    /// # Insert a batch of gaps, gaps replace earlier records of the same gap
+    pub async fn insert_data_gaps(&self, gaps: &[DataGap]) -> Result<()> {
+        if gaps.is_empty() {
+            return Ok(());
         }
+        self.insert_rows("data_gaps", gaps).await
+    }
 
-        insert.end().await.unwrap();
-        Ok(())
+    /// # Daily totals per security of board trades within `[from, till)`
+    ///
+    /// Days are Moscow dates, volume is in lots, duplicate trades are counted once
+    pub async fn daily_trade_totals(
+        &self,
+        engine: &str,
+        market: &str,
+        boardid: &str,
+        from: OffsetDateTime,
+        till: OffsetDateTime,
+    ) -> Result<Vec<DailyTotals>> {
+        self.client
+            .query(
+                "SELECT secid, toDate(tradetime, 'Europe/Moscow') AS day, \
+                    count() AS numtrades, sum(quantity) AS volume, \
+                    toDecimal64(sum(value), 6) AS value, \
+                    argMin(price, tradeid) AS open, min(price) AS low, \
+                    max(price) AS high, argMax(price, tradeid) AS close \
+                FROM (SELECT DISTINCT secid, tradeid, tradetime, quantity, price, value \
+                    FROM ?.trades \
+                    WHERE engine = ? AND market = ? AND boardid = ? \
+                    AND tradetime >= ? AND tradetime < ?) \
+                GROUP BY secid, day ORDER BY secid, day",
+            )
+            .bind(sql::Identifier(self.db.as_str()))
+            .bind(engine)
+            .bind(market)
+            .bind(boardid)
+            .bind(from.unix_timestamp())
+            .bind(till.unix_timestamp())
+            .fetch_all::<DailyTotals>()
+            .await
+    }
+
+    /// # Insert a batch of Reconciliation Records into database
+    pub async fn insert_reconciliation(&self, rows: &[Reconciliation]) -> Result<()> {
+        if rows.is_empty() {
+            return Ok(());
+        }
+        self.insert_rows("reconciliation", rows).await
+    }
+
+    /// # Insert a batch of ingestion log records into database
+    pub async fn insert_ingest_log(&self, entries: &[IngestLog]) -> Result<()> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+        self.insert_rows("ingest_log", entries).await
+    }
+
+    /// # Insert a batch of daily History Records into database
+    ///
+    /// `history` is a ReplacingMergeTree, records of the same date replace each other
+    pub async fn insert_history(&self, history: &[History]) -> Result<()> {
+        self.insert_rows("history", history).await
     }
 
     /// # Insert a batch of Trade Records into database
@@ -255,11 +782,95 @@ impl ClickhouseDatabase {
     ///  - NSA
     /// ```
     pub async fn insert_trades(&self, trades: &[Trade]) -> Result<()> {
-        let mut insert = self.client.insert(format!("{}.trades", self.db).as_str())?;
-        for trade in trades {
-            insert.write(trade).await?;
+        let rows: Vec<ClickhouseTrade> = trades.iter().map(ClickhouseTrade::from).collect();
+        self.insert_rows("trades", &rows).await
+    }
+}
+
+#[async_trait(?Send)]
+impl MarketDataSink for ClickhouseDatabase {
+    fn name(&self) -> &str {
+        "clickhouse"
+    }
+
+    /// Trades are counted by `TradeWriter` on commit
+    fn counts_inserted(&self) -> bool {
+        true
+    }
+
+    async fn init(&self) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        Ok(ClickhouseDatabase::init(self).await?)
+    }
+
+    async fn write_engines(
+        &self,
+        engines: &[Engine],
+    ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        Ok(self.insert_engines(engines).await?)
+    }
+
+    async fn write_markets(
+        &self,
+        markets: &[Market],
+    ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        Ok(self.insert_markets(markets).await?)
+    }
+
+    async fn write_boards(
+        &self,
+        boards: &[Board],
+    ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        Ok(self.insert_boards(boards).await?)
+    }
+
+    /// Trade ids of board stored in `trades`
+    async fn stored_tradeids(
+        &self,
+        engine: &str,
+        market: &str,
+        boardid: &str,
+        from: i64,
+        till: i64,
+    ) -> std::result::Result<Vec<i64>, Box<dyn std::error::Error>> {
+        Ok(ClickhouseDatabase::stored_tradeids(self, engine, market, boardid, from, till).await?)
+    }
+
+    /// Write trades with a long-lived batching `TradeWriter`
+    async fn write_trades(
+        &self,
+        trades: &[Trade],
+    ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let mut writer = self.trades_writer.lock().await;
+        if writer.is_none() {
+            let table = format!("{}.trades", self.db);
+            *writer = Some(TradeWriter::new(&self.client, &table, &self.writer_conf)?);
+        }
+        if let Some(writer) = writer.as_mut() {
+            writer.write(trades).await?;
+        }
+        Ok(())
+    }
+
+    async fn write_ingest_log(
+        &self,
+        entries: &[IngestLog],
+    ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        Ok(self.insert_ingest_log(entries).await?)
+    }
+
+    /// Flush current trades batch
+    async fn flush(&self) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        if let Some(writer) = self.trades_writer.lock().await.as_mut() {
+            writer.flush().await?;
+        }
+        Ok(())
+    }
+
+    /// Flush current trades batch and end insert
+    async fn close(&self) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        if let Some(writer) = self.trades_writer.lock().await.take() {
+            writer.end().await?;
         }
-        insert.end().await.unwrap();
         Ok(())
     }
 }
@@ -0,0 +1,105 @@
+use crate::config::Config;
+use crate::ingest::new_run_id;
+use crate::models::{Board, Engine, Market, Trade};
+use crate::sink::MarketDataSink;
+use async_trait::async_trait;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::fs::{self, File};
+use tokio::io::AsyncWriteExt;
+use tracing::debug;
+
+/// # Disk sink struct
+///
+/// Saves trades as JSON files to `md_path`, reference data is not saved. Files are named
+/// `<engine>-<market>-<run_id>-<file_num>.json` after the run id trades are stamped with, so
+/// files of earlier runs are never replaced.
+pub struct DiskSink {
+    path: String,
+    /// Run id of trades written without one
+    run_id: String,
+    file_num: AtomicUsize,
+}
+
+/// # Implementation for DiskSink Struct
+impl DiskSink {
+    /// # DiskSink instance factory
+    pub fn new(conf: &Config) -> Self {
+        Self {
+            path: conf.md_path.clone(),
+            run_id: new_run_id(),
+            file_num: AtomicUsize::new(1),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl MarketDataSink for DiskSink {
+    fn name(&self) -> &str {
+        "disk"
+    }
+
+    /// Create market data directory if it does not exist
+    async fn init(&self) -> Result<(), Box<dyn std::error::Error>> {
+        fs::create_dir_all(&self.path).await?;
+        Ok(())
+    }
+
+    async fn write_engines(&self, _engines: &[Engine]) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    async fn write_markets(&self, _markets: &[Market]) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    async fn write_boards(&self, _boards: &[Board]) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    /// Save trades as JSON to a new numbered file
+    async fn write_trades(&self, trades: &[Trade]) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(first) = trades.first() else {
+            return Ok(());
+        };
+        let run_id = match first.run_id.as_str() {
+            "" => &self.run_id,
+            run_id => run_id,
+        };
+        let file_num = self.file_num.fetch_add(1, Ordering::Relaxed);
+        let file_path = format!(
+            "{}/{}-{}-{}-{:06}.json",
+            self.path, first.engine, first.market, run_id, file_num
+        );
+        save_trades_to_file(&file_path, trades).await?;
+        debug!(trades = trades.len(), file = %file_path, "Trades saved to file");
+        Ok(())
+    }
+}
+
+/// Save trades to a JSON file atomically
+pub async fn save_trades_to_file(
+    file_path: &str,
+    trades: &[Trade],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let trades_json = serde_json::to_string(&trades)?;
+    write_file_atomic(file_path, trades_json.as_bytes()).await?;
+    Ok(())
+}
+
+/// # Write file atomically
+///
+/// Data is written to `<path>.tmp` which is synced and renamed over `path`, so an interrupted
+/// write never leaves a partially written file at `path`
+pub async fn write_file_atomic(path: impl AsRef<Path>, data: &[u8]) -> io::Result<()> {
+    let path = path.as_ref();
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+
+    let mut file = File::create(&tmp_path).await?;
+    file.write_all(data).await?;
+    file.sync_all().await?;
+    drop(file);
+    fs::rename(&tmp_path, path).await
+}
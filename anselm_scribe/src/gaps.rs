@@ -0,0 +1,367 @@
+use crate::db::ClickhouseDatabase;
+use crate::ingest::{stamp_trades, IngestLog};
+use crate::iss::IssClient;
+use crate::models::{fetch_trades_after, Board, Trade, MOSCOW_OFFSET};
+use clickhouse::Row;
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+use std::collections::HashSet;
+use std::time::Instant;
+use time::{Date, Duration, OffsetDateTime, Time};
+use tracing::{info, warn};
+
+/// Gap kind, mapped to Clickhouse `Enum8('tradeid' = 1, 'interval' = 2)`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize_repr, Deserialize_repr)]
+#[repr(i8)]
+pub enum GapKind {
+    /// Trades missing from a security day, fewer stored than ISS daily history reports
+    Tradeid = 1,
+    /// Security without trades during trading session, including session edges
+    Interval = 2,
+}
+
+/// Gap status, mapped to Clickhouse `Enum8('open' = 1, 'repaired' = 2, 'empty' = 3)`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize_repr, Deserialize_repr)]
+#[repr(i8)]
+pub enum GapStatus {
+    /// Detected and not repaired yet
+    Open = 1,
+    /// Missing trades were fetched again
+    Repaired = 2,
+    /// ISS has no trades within the gap
+    Empty = 3,
+}
+
+/// # Gap in stored trades
+///
+/// Missing trades have trade ids strictly between `after_tradeid` and `before_tradeid` and
+/// trade times within `[after_time, before_time]`, so a gap can be re-fetched from ISS with
+/// `tradeno`. Bounds are stored trades where known, `0` and `u64::MAX` stand for session
+/// edges and whole security days. Gaps are stored in `data_gaps` table, a
+/// `ReplacingMergeTree(version)` queried with `FINAL`.
+#[derive(Debug, Clone, Serialize, Deserialize, Row)]
+pub struct DataGap {
+    // Identifiers
+    pub engine: String,
+    pub market: String,
+    pub boardid: String,
+    pub secid: String,
+    #[serde(with = "clickhouse::serde::time::date")]
+    pub day: Date,
+    pub kind: GapKind,
+    pub after_tradeid: u64,
+    pub before_tradeid: u64,
+    // Main data
+    #[serde(with = "clickhouse::serde::time::datetime")]
+    pub after_time: OffsetDateTime,
+    #[serde(with = "clickhouse::serde::time::datetime")]
+    pub before_time: OffsetDateTime,
+    pub status: GapStatus,
+    pub repaired_trades: u32,
+    #[serde(with = "clickhouse::serde::time::datetime")]
+    pub updated_at: OffsetDateTime,
+    pub version: u64,
+}
+
+/// Pair of consecutive stored trades or session edges further apart than allowed
+#[derive(Debug, Clone, Serialize, Deserialize, Row)]
+pub struct GapCandidate {
+    pub engine: String,
+    pub market: String,
+    pub boardid: String,
+    pub secid: String,
+    pub prev_tradeid: u64,
+    pub tradeid: u64,
+    #[serde(with = "clickhouse::serde::time::datetime")]
+    pub prev_time: OffsetDateTime,
+    #[serde(with = "clickhouse::serde::time::datetime")]
+    pub tradetime: OffsetDateTime,
+}
+
+/// Stored trades of a security day compared with ISS daily history
+#[derive(Debug, Clone, Serialize, Deserialize, Row)]
+pub struct TradeCount {
+    pub engine: String,
+    pub market: String,
+    pub boardid: String,
+    pub secid: String,
+    #[serde(with = "clickhouse::serde::time::date")]
+    pub day: Date,
+    /// Trades reported by ISS daily history
+    pub numtrades: u32,
+    /// Distinct stored trades
+    pub stored: u64,
+}
+
+/// # Gap detection rules
+#[derive(Debug, Clone, Copy)]
+pub struct GapRules {
+    /// Longest allowed interval without trades of a security during trading session
+    pub interval: Duration,
+    /// Trading session start, Moscow time
+    pub session_start: Time,
+    /// Trading session end, Moscow time
+    pub session_end: Time,
+}
+
+/// Implementation for GapRules struct
+impl GapRules {
+    /// Part of `[from, till]` within trading session of the day `from` belongs to
+    fn session_overlap(&self, from: OffsetDateTime, till: OffsetDateTime) -> Duration {
+        let from = from.to_offset(MOSCOW_OFFSET);
+        let start = from.replace_time(self.session_start);
+        let end = from.replace_time(self.session_end);
+        let till = till.to_offset(MOSCOW_OFFSET).min(end);
+        till - from.max(start)
+    }
+
+    /// Session start and end as seconds after midnight
+    fn session_seconds(&self) -> (u32, u32) {
+        let seconds = |time: Time| {
+            let (hour, minute, second) = time.as_hms();
+            hour as u32 * 3600 + minute as u32 * 60 + second as u32
+        };
+        (seconds(self.session_start), seconds(self.session_end))
+    }
+}
+
+/// Implementation for DataGap struct
+impl DataGap {
+    /// Create open gap from candidate
+    fn open(kind: GapKind, candidate: GapCandidate, now: OffsetDateTime) -> Self {
+        DataGap {
+            day: candidate.prev_time.to_offset(MOSCOW_OFFSET).date(),
+            engine: candidate.engine,
+            market: candidate.market,
+            boardid: candidate.boardid,
+            secid: candidate.secid,
+            kind,
+            after_tradeid: candidate.prev_tradeid,
+            before_tradeid: candidate.tradeid,
+            after_time: candidate.prev_time,
+            before_time: candidate.tradetime,
+            status: GapStatus::Open,
+            repaired_trades: 0,
+            updated_at: now,
+            version: now.unix_timestamp_nanos() as u64,
+        }
+    }
+
+    /// Create open gap over the whole security day with missing trades
+    fn missing(count: TradeCount, now: OffsetDateTime) -> Self {
+        let day_start = count.day.midnight().assume_offset(MOSCOW_OFFSET);
+        DataGap {
+            engine: count.engine,
+            market: count.market,
+            boardid: count.boardid,
+            secid: count.secid,
+            day: count.day,
+            kind: GapKind::Tradeid,
+            after_tradeid: 0,
+            before_tradeid: u64::MAX,
+            after_time: day_start,
+            before_time: day_start + Duration::days(1),
+            status: GapStatus::Open,
+            repaired_trades: 0,
+            updated_at: now,
+            version: now.unix_timestamp_nanos() as u64,
+        }
+    }
+
+    /// Key identifying the same gap across detections
+    fn key(&self) -> (String, String, String, String, GapKind, Date, u64) {
+        (
+            self.engine.clone(),
+            self.market.clone(),
+            self.boardid.clone(),
+            self.secid.clone(),
+            self.kind,
+            self.day,
+            self.after_tradeid,
+        )
+    }
+
+    /// Check whether trade falls within gap
+    fn contains(&self, trade: &Trade) -> bool {
+        let tradeid = trade.tradeid as u64;
+        tradeid > self.after_tradeid
+            && tradeid < self.before_tradeid
+            && trade.tradetime >= self.after_time
+            && trade.tradetime <= self.before_time
+            && (self.secid.is_empty() || trade.secid == self.secid)
+    }
+}
+
+/// # Detect gaps in trades of Moscow dates `[from, till)`
+///
+/// Trade numbers are shared by all boards of a market, so missing trades are found by
+/// counting stored trades of every security day against `numtrades` of ISS daily history
+/// loaded with `backfill`. Intervals without trades are checked per security and day from
+/// session start to session end, only the part of an interval within trading session counts.
+/// Gaps already stored in `data_gaps` are skipped, new gaps are saved as open and returned.
+pub async fn detect_gaps(
+    db: &ClickhouseDatabase,
+    rules: &GapRules,
+    from: Date,
+    till: Date,
+) -> Result<Vec<DataGap>, Box<dyn std::error::Error>> {
+    let range_from = from.midnight().assume_offset(MOSCOW_OFFSET);
+    let range_till = till.midnight().assume_offset(MOSCOW_OFFSET);
+    let now = OffsetDateTime::now_utc();
+
+    let known: HashSet<_> = db
+        .data_gaps(from, till, None)
+        .await?
+        .iter()
+        .map(DataGap::key)
+        .collect();
+
+    if db.history_records(from, till).await? == 0 {
+        warn!(%from, %till, "No ISS daily history to count trades against, run backfill");
+    }
+    let counts = db.find_count_gaps(from, till).await?;
+    for count in &counts {
+        warn!(
+            engine = %count.engine,
+            market = %count.market,
+            boardid = %count.boardid,
+            secid = %count.secid,
+            day = %count.day,
+            numtrades = count.numtrades,
+            stored = count.stored,
+            "Stored trades fewer than ISS daily history"
+        );
+    }
+    let mut gaps: Vec<DataGap> = counts
+        .into_iter()
+        .map(|c| DataGap::missing(c, now))
+        .collect();
+
+    let interval = rules.interval.whole_seconds().max(0) as u64;
+    let (session_start, session_end) = rules.session_seconds();
+    gaps.extend(
+        db.find_interval_gaps(range_from, range_till, interval, session_start, session_end)
+            .await?
+            .into_iter()
+            // Session of the current day is not over yet
+            .filter(|c| rules.session_overlap(c.prev_time, c.tradetime.min(now)) > rules.interval)
+            .map(|c| DataGap::open(GapKind::Interval, c, now)),
+    );
+
+    gaps.retain(|gap| !known.contains(&gap.key()));
+    for gap in &gaps {
+        warn!(
+            kind = ?gap.kind,
+            engine = %gap.engine,
+            market = %gap.market,
+            boardid = %gap.boardid,
+            secid = %gap.secid,
+            day = %gap.day,
+            after_tradeid = gap.after_tradeid,
+            before_tradeid = gap.before_tradeid,
+            after_time = %gap.after_time,
+            before_time = %gap.before_time,
+            "Gap detected"
+        );
+    }
+    db.insert_data_gaps(&gaps).await?;
+
+    Ok(gaps)
+}
+
+/// # Repair gap by fetching trades within it from ISS
+///
+/// Trades are requested from the trade bounding the gap with `tradeno`, page after page until
+/// a page passes the gap or comes back empty. Only trades within the gap that are not stored
+/// yet are saved, gap is saved as repaired or as empty if ISS has no such trades. ISS serves
+/// trades of the current trading day only, gaps of earlier days can be repaired when
+/// replaying a cassette. Saved trades are stamped with `run_id` and every requested page is
+/// written to the ingestion log, failed pages with their error.
+pub async fn repair_gap(
+    db: &ClickhouseDatabase,
+    iss: &IssClient,
+    gap: &DataGap,
+    run_id: &str,
+) -> Result<DataGap, Box<dyn std::error::Error>> {
+    let secid = match gap.secid.as_str() {
+        "" => None,
+        secid => Some(secid),
+    };
+    let mut stored: HashSet<u64> = db.gap_tradeids(gap).await?.into_iter().collect();
+    let mut tradeno = gap.after_tradeid;
+    let mut repaired: u32 = 0;
+
+    loop {
+        let log = IngestLog::new(run_id, &gap.engine, &gap.market, &gap.boardid, secid);
+        let page =
+            match fetch_trades_after(iss, &gap.engine, &gap.market, &gap.boardid, secid, tradeno)
+                .await
+            {
+                Ok(page) => page,
+                Err(failed) => {
+                    let log = log
+                        .request(failed.start, &failed.meta, failed.time_req)
+                        .error(&failed);
+                    db.insert_ingest_log(&[log]).await?;
+                    return Err(failed.into());
+                }
+            };
+        let time_parse = Instant::now();
+        let trades = match Board::parse_trades_page(&gap.engine, &gap.market, &gap.boardid, &page) {
+            Ok(trades) => trades,
+            Err(e) => {
+                let log = log.trades_page(&page, &[], time_parse.elapsed()).error(&e);
+                db.insert_ingest_log(&[log]).await?;
+                return Err(e);
+            }
+        };
+        let log = log.trades_page(&page, &trades, time_parse.elapsed());
+
+        let mut missing: Vec<Trade> = trades
+            .iter()
+            .filter(|t| gap.contains(t) && stored.insert(t.tradeid as u64))
+            .cloned()
+            .collect();
+        if !missing.is_empty() {
+            stamp_trades(&mut missing, run_id);
+            db.insert_trades(&missing).await?;
+            repaired += missing.len() as u32;
+        }
+        db.insert_ingest_log(&[log]).await?;
+
+        // ISS may return fewer trades than requested, only an empty page ends the day
+        match trades.last() {
+            Some(last)
+                if (last.tradeid as u64) < gap.before_tradeid
+                    && last.tradetime <= gap.before_time =>
+            {
+                tradeno = last.tradeid as u64
+            }
+            _ => break,
+        }
+    }
+
+    let now = OffsetDateTime::now_utc();
+    let mut gap = gap.clone();
+    gap.status = match repaired {
+        0 => GapStatus::Empty,
+        _ => GapStatus::Repaired,
+    };
+    gap.repaired_trades = repaired;
+    gap.updated_at = now;
+    gap.version = now.unix_timestamp_nanos() as u64;
+    db.insert_data_gaps(std::slice::from_ref(&gap)).await?;
+
+    info!(
+        kind = ?gap.kind,
+        status = ?gap.status,
+        boardid = %gap.boardid,
+        secid = %gap.secid,
+        day = %gap.day,
+        after_tradeid = gap.after_tradeid,
+        before_tradeid = gap.before_tradeid,
+        repaired,
+        "Gap repaired"
+    );
+    Ok(gap)
+}
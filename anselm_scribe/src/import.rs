@@ -0,0 +1,238 @@
+use crate::config::Config;
+use crate::db::ClickhouseTrade;
+use crate::ingest::{new_run_id, stamp_trades};
+use crate::models::{decimal64, Trade};
+use crate::sink::MarketDataSink;
+use rust_decimal::prelude::FromPrimitive;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use time::OffsetDateTime;
+use tokio::fs;
+use tracing::{info, warn};
+
+/// # Import runner for loading market data files from disk into market data sinks
+///
+/// Accepts files and directories, directories are scanned (non-recursively) for supported
+/// market data files which are imported in name order.
+///
+/// Trades are written in chunks of `conf.chunks` to every sink, through the same batching
+/// writer `run_board` uses during live ingestion for Clickhouse. `trades` does not deduplicate
+/// rows, so trades a sink already stores are skipped. Sinks are flushed after every file, so
+/// trades repeated across imported files are found stored too and importing a file twice does
+/// not duplicate rows. Sinks are closed by the caller.
+///
+/// Imported trades are stamped with the run id of the import, the run that stored them.
+pub async fn import_runner(
+    conf: &Config,
+    sinks: &[Box<dyn MarketDataSink>],
+    paths: &[String],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let run_id = new_run_id();
+    let files = collect_files(paths).await?;
+    info!(%run_id, files = files.len(), "Import files found");
+
+    let time_import: Instant = Instant::now();
+    let mut total_trades: usize = 0;
+    let mut total_skipped: usize = 0;
+    for (file_num, file_path) in files.iter().enumerate() {
+        let time_file: Instant = Instant::now();
+        let mut trades = unique_trades(load_trades_from_file(file_path).await?);
+        stamp_trades(&mut trades, &run_id);
+
+        for sink in sinks {
+            let new = new_trades(sink.as_ref(), &trades).await?;
+            let skipped = trades.len() - new.len();
+            if skipped > 0 {
+                warn!(
+                    sink = sink.name(),
+                    skipped,
+                    file = %file_path.display(),
+                    "Import skipped trades already stored"
+                );
+            }
+            for chunk in new.chunks(conf.chunks) {
+                sink.write_trades(chunk).await?;
+            }
+            sink.flush().await?;
+            total_skipped += skipped;
+        }
+        total_trades += trades.len();
+
+        info!(
+            file_num = file_num + 1,
+            files = files.len(),
+            trades = trades.len(),
+            file = %file_path.display(),
+            time_ms = time_file.elapsed().as_secs_f64() * 1e3,
+            total_trades,
+            elapsed_ms = time_import.elapsed().as_secs_f64() * 1e3,
+            "Trades imported from file"
+        );
+    }
+
+    info!(
+        %run_id,
+        trades = total_trades,
+        skipped = total_skipped,
+        files = files.len(),
+        time_ms = time_import.elapsed().as_secs_f64() * 1e3,
+        "Import done"
+    );
+    Ok(())
+}
+
+/// Board and trade id of a trade
+type TradeKey = (String, String, String, i64);
+
+/// Drop trades repeated within a file, first occurrence is kept
+fn unique_trades(trades: Vec<Trade>) -> Vec<Trade> {
+    let mut seen: HashSet<TradeKey> = HashSet::with_capacity(trades.len());
+    trades
+        .into_iter()
+        .filter(|t| {
+            seen.insert((
+                t.engine.clone(),
+                t.market.clone(),
+                t.boardid.clone(),
+                t.tradeid,
+            ))
+        })
+        .collect()
+}
+
+/// # Trades not stored in sink yet
+///
+/// Stored trade ids are looked up per board within the trade id range of the file
+async fn new_trades(
+    sink: &dyn MarketDataSink,
+    trades: &[Trade],
+) -> Result<Vec<Trade>, Box<dyn std::error::Error>> {
+    let mut ranges: HashMap<(&str, &str, &str), (i64, i64)> = HashMap::new();
+    for t in trades {
+        let range = ranges
+            .entry((&t.engine, &t.market, &t.boardid))
+            .or_insert((t.tradeid, t.tradeid));
+        range.0 = range.0.min(t.tradeid);
+        range.1 = range.1.max(t.tradeid);
+    }
+
+    let mut stored: HashSet<(&str, &str, &str, i64)> = HashSet::new();
+    for ((engine, market, boardid), (from, till)) in ranges {
+        for tradeid in sink
+            .stored_tradeids(engine, market, boardid, from, till)
+            .await?
+        {
+            stored.insert((engine, market, boardid, tradeid));
+        }
+    }
+
+    Ok(trades
+        .iter()
+        .filter(|t| !stored.contains(&(&*t.engine, &*t.market, &*t.boardid, t.tradeid)))
+        .cloned()
+        .collect())
+}
+
+/// Resolve paths into a sorted list of supported market data files
+pub async fn collect_files(paths: &[String]) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let mut files: Vec<PathBuf> = Vec::new();
+    for path in paths {
+        let path = PathBuf::from(path);
+        if fs::metadata(&path).await?.is_dir() {
+            let mut dir_files: Vec<PathBuf> = Vec::new();
+            let mut entries = fs::read_dir(&path).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let entry_path = entry.path();
+                if entry.file_type().await?.is_file() && is_supported(&entry_path) {
+                    dir_files.push(entry_path);
+                }
+            }
+            dir_files.sort();
+            files.extend(dir_files);
+        } else if is_supported(&path) {
+            files.push(path);
+        } else {
+            warn!(file = %path.display(), "Import skipped unsupported file format");
+        }
+    }
+    Ok(files)
+}
+
+/// Check whether file has a market data format that can be imported
+fn is_supported(path: &Path) -> bool {
+    matches!(path.extension().and_then(|e| e.to_str()), Some("json"))
+}
+
+/// Load trades from a file saved by `disk::save_trades_to_file`
+///
+/// Files saved before prices became decimals are converted from `LegacyTrade`, files saved
+/// with Clickhouse mantissas and side codes are read as `ClickhouseTrade`
+pub async fn load_trades_from_file(
+    file_path: &Path,
+) -> Result<Vec<Trade>, Box<dyn std::error::Error>> {
+    let contents = fs::read(file_path).await?;
+    let e = match serde_json::from_slice::<Vec<Trade>>(&contents) {
+        Ok(trades) => return Ok(trades),
+        Err(e) => e,
+    };
+    if let Ok(rows) = serde_json::from_slice::<Vec<ClickhouseTrade>>(&contents) {
+        return Ok(rows.into_iter().map(Trade::from).collect());
+    }
+    match serde_json::from_slice::<Vec<LegacyTrade>>(&contents) {
+        Ok(legacy) => Ok(legacy
+            .into_iter()
+            .map(Trade::try_from)
+            .collect::<Result<Vec<Trade>, _>>()?),
+        Err(_) => Err(e.into()),
+    }
+}
+
+/// Trade Record as saved to disk with `f64` prices and `String` side
+#[derive(Debug, Deserialize)]
+struct LegacyTrade {
+    engine: String,
+    market: String,
+    secid: String,
+    boardid: String,
+    tradeid: i64,
+    buysell: String,
+    quantity: i32,
+    price: f64,
+    value: f64,
+    #[serde(with = "clickhouse::serde::time::datetime")]
+    tradetime: OffsetDateTime,
+    #[serde(with = "clickhouse::serde::time::datetime")]
+    systime: OffsetDateTime,
+}
+
+impl TryFrom<LegacyTrade> for Trade {
+    type Error = String;
+
+    /// Security decimals are not known for legacy trades, they are taken from price
+    fn try_from(t: LegacyTrade) -> Result<Self, Self::Error> {
+        let price = Decimal::from_f64(t.price)
+            .ok_or_else(|| format!("Invalid price {} of trade {}", t.price, t.tradeid))?
+            .normalize();
+        let value = Decimal::from_f64(t.value)
+            .ok_or_else(|| format!("Invalid value {} of trade {}", t.value, t.tradeid))?
+            .round_dp(decimal64::DECIMAL_SCALE);
+        Ok(Trade {
+            engine: t.engine,
+            market: t.market,
+            secid: t.secid,
+            boardid: t.boardid,
+            tradeid: t.tradeid,
+            buysell: t.buysell.parse()?,
+            quantity: t.quantity,
+            decimals: price.scale() as u8,
+            price,
+            value,
+            tradetime: t.tradetime,
+            systime: t.systime,
+            run_id: String::new(),
+        })
+    }
+}
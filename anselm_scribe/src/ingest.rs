@@ -0,0 +1,141 @@
+use crate::iss::IssResponseMeta;
+use crate::models::{Trade, TradesPage};
+use clickhouse::Row;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use time::OffsetDateTime;
+
+/// # Ingestion log record of an ISS trades page request
+///
+/// Stored in `ingest_log` table, every request is logged including failed ones: `status` is
+/// `0` if no response was received and `error` describes why the page was not stored. Every stored trade carries `run_id` of the run that fetched
+/// it, so a trade traces back to its request by run id, board and trade id within
+/// `[first_tradeid, last_tradeid]`.
+#[derive(Debug, Clone, Serialize, Deserialize, Row)]
+pub struct IngestLog {
+    // Identifiers
+    pub run_id: String,
+    #[serde(with = "clickhouse::serde::time::datetime")]
+    pub logged_at: OffsetDateTime,
+    pub engine: String,
+    pub market: String,
+    pub boardid: String,
+    /// Empty for board trades pages
+    pub secid: String,
+    // Request
+    pub url: String,
+    pub params: String,
+    /// Offset of the page, 0 for pages requested after a trade number
+    pub start: u64,
+    pub status: u16,
+    pub bytes: u64,
+    pub replayed: bool,
+    /// Empty if the page was fetched and parsed
+    pub error: String,
+    // Result
+    pub rows: u32,
+    pub first_tradeid: u64,
+    pub last_tradeid: u64,
+    pub fetch_us: u64,
+    pub parse_us: u64,
+}
+
+/// Implementation for IngestLog struct
+impl IngestLog {
+    /// Create record of a request for board trades, trades of a single security if `secid` is
+    /// defined
+    pub fn new(
+        run_id: &str,
+        engine: &str,
+        market: &str,
+        boardid: &str,
+        secid: Option<&str>,
+    ) -> Self {
+        IngestLog {
+            run_id: run_id.to_string(),
+            logged_at: OffsetDateTime::now_utc(),
+            engine: engine.to_string(),
+            market: market.to_string(),
+            boardid: boardid.to_string(),
+            secid: secid.unwrap_or_default().to_string(),
+            url: String::new(),
+            params: String::new(),
+            start: 0,
+            status: 0,
+            bytes: 0,
+            replayed: false,
+            error: String::new(),
+            rows: 0,
+            first_tradeid: 0,
+            last_tradeid: 0,
+            fetch_us: 0,
+            parse_us: 0,
+        }
+    }
+
+    /// Record request of a page starting from `start`, successful or not
+    pub fn request(mut self, start: i32, meta: &IssResponseMeta, time_req: Duration) -> Self {
+        self.url = meta.url.clone();
+        self.params = meta.params.clone();
+        self.start = start.max(0) as u64;
+        self.status = meta.status;
+        self.bytes = meta.bytes;
+        self.replayed = meta.replayed;
+        self.fetch_us = time_req.as_micros() as u64;
+        self
+    }
+
+    /// Record why the page was not stored
+    pub fn error(mut self, error: impl std::fmt::Display) -> Self {
+        self.error = error.to_string();
+        self
+    }
+
+    /// Record fetched page and trades parsed from it
+    pub fn trades_page(
+        mut self,
+        page: &TradesPage,
+        trades: &[Trade],
+        time_parse: Duration,
+    ) -> Self {
+        self = self.request(page.start, &page.meta, page.time_req);
+        self.rows = trades.len() as u32;
+        self.first_tradeid = trades.first().map_or(0, |t| t.tradeid as u64);
+        self.last_tradeid = trades.last().map_or(0, |t| t.tradeid as u64);
+        self.parse_us = time_parse.as_micros() as u64;
+        self
+    }
+}
+
+/// # Generate run id
+///
+/// UUIDv7, ids of later runs sort after ids of earlier runs
+pub fn new_run_id() -> String {
+    let mut bytes = [0u8; 16];
+    let millis = OffsetDateTime::now_utc().unix_timestamp_nanos() / 1_000_000;
+    bytes[..6].copy_from_slice(&(millis as u64).to_be_bytes()[2..]);
+    // Fall back to process id and time when system randomness is unavailable
+    if getrandom::getrandom(&mut bytes[6..]).is_err() {
+        let fallback = (std::process::id() as u64) << 32 ^ (millis as u64);
+        bytes[6..14].copy_from_slice(&fallback.to_be_bytes());
+    }
+    bytes[6] = (bytes[6] & 0x0f) | 0x70;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// Stamp trades with run id
+pub fn stamp_trades(trades: &mut [Trade], run_id: &str) {
+    for trade in trades {
+        trade.run_id = run_id.to_string();
+    }
+}
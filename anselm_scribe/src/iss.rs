@@ -0,0 +1,707 @@
+use crate::cassette::{Cassette, CassetteMode};
+use crate::config::{Config, IssEndpoint};
+use crate::metrics;
+use encoding_rs::WINDOWS_1251;
+use serde::de::value::{Error as ValueError, SeqAccessDeserializer};
+use serde::de::{self, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::{forward_to_deserialize_any, Deserialize, Deserializer};
+use std::borrow::Cow;
+use std::fmt;
+use std::marker::PhantomData;
+use std::time::Instant;
+
+/// # ISS response format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssFormat {
+    /// `.json`, UTF-8 blocks with `columns` and `data`
+    Json,
+    /// `.csv`, Windows-1251 `;` separated blocks, each preceded by block name and header
+    Csv,
+}
+
+/// Implementation for IssFormat enum
+impl IssFormat {
+    /// URL extension of format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            IssFormat::Json => "json",
+            IssFormat::Csv => "csv",
+        }
+    }
+}
+
+/// # ISS HTTP client
+///
+/// Requests every endpoint in the format selected with `--iss-csv`, responses of both
+/// formats are parsed into the same rows. With a cassette responses are recorded to or
+/// replayed from disk.
+#[derive(Debug, Clone)]
+pub struct IssClient {
+    client: reqwest::Client,
+    base_url: String,
+    csv: Vec<IssEndpoint>,
+    cassette: Option<Cassette>,
+}
+
+/// Implementation for IssClient struct
+impl IssClient {
+    /// # IssClient instance factory
+    pub fn new(conf: &Config) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url: conf.iss_url.trim_end_matches('/').to_string(),
+            csv: conf.iss_csv.clone(),
+            cassette: Cassette::from_config(conf),
+        }
+    }
+
+    /// Format used for endpoint
+    pub fn format(&self, endpoint: IssEndpoint) -> IssFormat {
+        if self.csv.contains(&endpoint) {
+            IssFormat::Csv
+        } else {
+            IssFormat::Json
+        }
+    }
+
+    /// # Request endpoint
+    ///
+    /// `path` is relative to ISS base URL and has no extension, e.g. `engines/stock/markets`
+    pub async fn get(
+        &self,
+        endpoint: IssEndpoint,
+        path: &str,
+        query: &[(&str, String)],
+    ) -> Result<IssBody, IssError> {
+        let (_, body) = self.get_with_meta(endpoint, path, query).await;
+        body
+    }
+
+    /// # Request endpoint, also returning request metadata recorded in ingestion log
+    ///
+    /// Metadata is returned for failed requests too, status is `0` if no response was received
+    pub async fn get_with_meta(
+        &self,
+        endpoint: IssEndpoint,
+        path: &str,
+        query: &[(&str, String)],
+    ) -> (IssResponseMeta, Result<IssBody, IssError>) {
+        let format = self.format(endpoint);
+        let key = Cassette::key(path, format, query);
+        let mut meta = IssResponseMeta {
+            url: format!("{}/{}.{}", self.base_url, path, format.extension()),
+            params: query
+                .iter()
+                .map(|(name, value)| format!("{}={}", name, value))
+                .collect::<Vec<String>>()
+                .join("&"),
+            status: 0,
+            bytes: 0,
+            replayed: false,
+        };
+        let body = self.request(endpoint, format, key, query, &mut meta).await;
+        (meta, body)
+    }
+
+    /// Request endpoint or replay it from the cassette, filling in response metadata
+    async fn request(
+        &self,
+        endpoint: IssEndpoint,
+        format: IssFormat,
+        key: String,
+        query: &[(&str, String)],
+        meta: &mut IssResponseMeta,
+    ) -> Result<IssBody, IssError> {
+        if let Some(ref cassette) = self.cassette {
+            if cassette.mode() == CassetteMode::Replay {
+                meta.replayed = true;
+                let bytes = cassette
+                    .load(&key)
+                    .await
+                    .map_err(|e| IssError::Cassette(key, e))?;
+                // Only successful responses are recorded
+                meta.status = 200;
+                meta.bytes = bytes.len() as u64;
+                return Ok(IssBody::decode(format, bytes));
+            }
+        }
+
+        let time_req = Instant::now();
+        let response = match self
+            .client
+            .get(&meta.url)
+            .query(query)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+        {
+            Ok(response) => response,
+            Err(e) => {
+                let status = e.status().map(|s| s.as_u16());
+                meta.status = status.unwrap_or(0);
+                metrics::iss_request(endpoint, status, time_req.elapsed());
+                return Err(e.into());
+            }
+        };
+        meta.status = response.status().as_u16();
+        let bytes = match response.bytes().await {
+            Ok(bytes) => bytes.to_vec(),
+            Err(e) => {
+                metrics::iss_request(endpoint, None, time_req.elapsed());
+                return Err(e.into());
+            }
+        };
+        meta.bytes = bytes.len() as u64;
+        metrics::iss_request(endpoint, Some(meta.status), time_req.elapsed());
+
+        if let Some(ref cassette) = self.cassette {
+            cassette
+                .save(&key, &bytes)
+                .await
+                .map_err(|e| IssError::Cassette(key, e))?;
+        }
+
+        Ok(IssBody::decode(format, bytes))
+    }
+}
+
+/// # ISS response metadata
+#[derive(Debug, Clone)]
+pub struct IssResponseMeta {
+    /// Request URL without query
+    pub url: String,
+    /// Query parameters, e.g. `start=0&limit=5000`
+    pub params: String,
+    /// HTTP status, `0` if no response was received
+    pub status: u16,
+    /// Response size in bytes
+    pub bytes: u64,
+    /// Whether response was replayed from a cassette
+    pub replayed: bool,
+}
+
+/// # ISS response body
+#[derive(Debug, Clone)]
+pub enum IssBody {
+    /// Raw JSON response
+    Json(Vec<u8>),
+    /// CSV response decoded from Windows-1251
+    Csv(String),
+}
+
+/// Implementation for IssBody enum
+impl IssBody {
+    /// Decode raw response of format
+    pub fn decode(format: IssFormat, bytes: Vec<u8>) -> Self {
+        match format {
+            IssFormat::Json => IssBody::Json(bytes),
+            IssFormat::Csv => {
+                let (text, _) = WINDOWS_1251.decode_without_bom_handling(&bytes);
+                IssBody::Csv(text.into_owned())
+            }
+        }
+    }
+
+    /// Parse rows of `R::BLOCK` block, rows borrow strings from the body
+    pub fn rows<'a, R: IssRow<'a>>(&'a self) -> Result<Vec<R>, IssError> {
+        match self {
+            IssBody::Json(bytes) => parse_block(bytes).map_err(IssError::Json),
+            IssBody::Csv(text) => parse_csv_block(text).map_err(IssError::Csv),
+        }
+    }
+}
+
+/// # ISS request error
+#[derive(Debug)]
+pub enum IssError {
+    Http(reqwest::Error),
+    Cassette(String, std::io::Error),
+    Json(serde_json::Error),
+    Csv(ValueError),
+}
+
+impl fmt::Display for IssError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IssError::Http(e) => write!(f, "Error requesting ISS: {}", e),
+            IssError::Cassette(key, e) => write!(f, "Error accessing cassette '{}': {}", key, e),
+            IssError::Json(e) => write!(f, "Error parsing ISS JSON: {}", e),
+            IssError::Csv(e) => write!(f, "Error parsing ISS CSV: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for IssError {}
+
+impl From<reqwest::Error> for IssError {
+    fn from(e: reqwest::Error) -> Self {
+        IssError::Http(e)
+    }
+}
+
+/// # ISS data block row
+///
+/// ISS responses consist of named blocks, each holding `columns` and `data` with rows as
+/// arrays of cells:
+///
+/// ```json
+/// {"trades": {"metadata": {...}, "columns": ["TRADENO", ...], "data": [[1, ...], ...]}}
+/// ```
+///
+/// Rows are deserialized straight from the response bytes, strings are borrowed from the
+/// response unless they contain escapes, cells after `COLUMNS` are skipped.
+pub trait IssRow<'de>: Deserialize<'de> {
+    /// Block name in ISS response
+    const BLOCK: &'static str;
+    /// Leading columns of the block in the order they are read
+    const COLUMNS: &'static [&'static str];
+}
+
+/// Parse rows of `R::BLOCK` block from ISS JSON response, other blocks are skipped
+pub fn parse_block<'de, R: IssRow<'de>>(body: &'de [u8]) -> Result<Vec<R>, serde_json::Error> {
+    let mut deserializer = serde_json::Deserializer::from_slice(body);
+    let rows = ResponseSeed(PhantomData).deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok(rows)
+}
+
+/// # Parse rows of `R::BLOCK` block from decoded ISS CSV response
+///
+/// ISS CSV consists of blocks separated by empty lines, block name is followed by an empty
+/// line and the header:
+///
+/// ```text
+/// trades
+///
+/// TRADENO;TRADETIME;BOARDID;SECID;PRICE;...
+/// 11076497011;09:59:49;TQBR;SBER;291.28;...
+///
+/// dataversion
+/// ...
+/// ```
+///
+/// Cells of unquoted lines are borrowed from the response.
+pub fn parse_csv_block<'de, R: IssRow<'de>>(text: &'de str) -> Result<Vec<R>, ValueError> {
+    let mut lines = text.lines();
+
+    // Find block by its name line
+    loop {
+        match lines.next() {
+            Some(line) if line == R::BLOCK => break,
+            Some(_) => continue,
+            None => {
+                return Err(de::Error::custom(format!(
+                    "ISS block '{}' not found",
+                    R::BLOCK
+                )))
+            }
+        }
+    }
+
+    let header = lines
+        .find(|line| !line.is_empty())
+        .ok_or_else(|| de::Error::missing_field("columns"))?;
+    let columns: Vec<Cow<str>> = header.split(';').map(Cow::Borrowed).collect();
+    check_columns(R::BLOCK, &columns, R::COLUMNS)?;
+
+    lines
+        .take_while(|line| !line.is_empty())
+        .map(|line| {
+            let cells = csv_cells(line)?;
+            R::deserialize(SeqAccessDeserializer::new(CsvRow {
+                cells: cells.into_iter(),
+            }))
+        })
+        .collect()
+}
+
+/// Split CSV line into cells, quoted lines are parsed by the csv reader
+fn csv_cells(line: &str) -> Result<Vec<Cow<'_, str>>, ValueError> {
+    if !line.contains('"') {
+        return Ok(line.split(';').map(Cow::Borrowed).collect());
+    }
+
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(b';')
+        .has_headers(false)
+        .from_reader(line.as_bytes());
+    let mut record = csv::StringRecord::new();
+    reader.read_record(&mut record).map_err(de::Error::custom)?;
+    Ok(record
+        .iter()
+        .map(|cell| Cow::Owned(cell.to_string()))
+        .collect())
+}
+
+/// Cells of a single CSV row
+struct CsvRow<'de> {
+    cells: std::vec::IntoIter<Cow<'de, str>>,
+}
+
+impl<'de> SeqAccess<'de> for CsvRow<'de> {
+    type Error = ValueError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Self::Error> {
+        self.cells
+            .next()
+            .map(|cell| seed.deserialize(CsvCell(cell)))
+            .transpose()
+    }
+}
+
+/// Single CSV cell, numbers are parsed from text
+struct CsvCell<'de>(Cow<'de, str>);
+
+/// Deserialize number by parsing cell text
+macro_rules! csv_number {
+    ($($method:ident => $visit:ident: $ty:ty),*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                let value = self.0.parse::<$ty>().map_err(|e| {
+                    de::Error::custom(format!("invalid number '{}': {}", self.0, e))
+                })?;
+                visitor.$visit(value)
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for CsvCell<'de> {
+    type Error = ValueError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Cow::Borrowed(cell) => visitor.visit_borrowed_str(cell),
+            Cow::Owned(cell) => visitor.visit_string(cell),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        if self.0.is_empty() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    csv_number!(
+        deserialize_i32 => visit_i32: i32,
+        deserialize_i64 => visit_i64: i64,
+        deserialize_u8 => visit_u8: u8,
+        deserialize_f64 => visit_f64: f64
+    );
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i128 u16 u32 u64 u128 f32 char str string bytes byte_buf unit unit_struct
+        newtype_struct seq tuple tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+/// Check that block columns start with expected columns
+fn check_columns<E: de::Error>(
+    block: &str,
+    columns: &[Cow<str>],
+    expected: &[&str],
+) -> Result<(), E> {
+    let matches = columns.len() >= expected.len()
+        && columns
+            .iter()
+            .zip(expected)
+            .all(|(column, expected)| column.eq_ignore_ascii_case(expected));
+    if matches {
+        Ok(())
+    } else {
+        Err(E::custom(format!(
+            "unexpected columns in ISS block '{}': {:?}, expected {:?}",
+            block, columns, expected
+        )))
+    }
+}
+
+/// Seed for the whole response, finds the requested block
+struct ResponseSeed<R>(PhantomData<R>);
+
+impl<'de, R: IssRow<'de>> DeserializeSeed<'de> for ResponseSeed<R> {
+    type Value = Vec<R>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, R: IssRow<'de>> Visitor<'de> for ResponseSeed<R> {
+    type Value = Vec<R>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ISS response with '{}' block", R::BLOCK)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut rows = None;
+        while let Some(key) = map.next_key_seed(CowStr)? {
+            if key == R::BLOCK && rows.is_none() {
+                rows = Some(map.next_value_seed(BlockSeed::<R>(PhantomData))?);
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        rows.ok_or_else(|| de::Error::custom(format!("ISS block '{}' not found", R::BLOCK)))
+    }
+}
+
+/// Seed for a single block
+struct BlockSeed<R>(PhantomData<R>);
+
+impl<'de, R: IssRow<'de>> DeserializeSeed<'de> for BlockSeed<R> {
+    type Value = Vec<R>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, R: IssRow<'de>> Visitor<'de> for BlockSeed<R> {
+    type Value = Vec<R>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ISS block with columns and data")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut columns: Option<Vec<Cow<str>>> = None;
+        let mut data: Option<Vec<R>> = None;
+        while let Some(key) = map.next_key_seed(CowStr)? {
+            match key.as_ref() {
+                "columns" => {
+                    let names: Vec<Cow<str>> = map.next_value_seed(CowStrs)?;
+                    check_columns(R::BLOCK, &names, R::COLUMNS)?;
+                    columns = Some(names);
+                }
+                "data" => data = Some(map.next_value()?),
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        if columns.is_none() {
+            return Err(de::Error::missing_field("columns"));
+        }
+        data.ok_or_else(|| de::Error::missing_field("data"))
+    }
+}
+
+/// Seed for a string borrowed from the response whenever possible
+#[derive(Clone, Copy)]
+struct CowStr;
+
+impl<'de> DeserializeSeed<'de> for CowStr {
+    type Value = Cow<'de, str>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_str(self)
+    }
+}
+
+impl<'de> Visitor<'de> for CowStr {
+    type Value = Cow<'de, str>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a string")
+    }
+
+    fn visit_borrowed_str<E: de::Error>(self, v: &'de str) -> Result<Self::Value, E> {
+        Ok(Cow::Borrowed(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(Cow::Owned(v.to_owned()))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Self::Value, E> {
+        Ok(Cow::Owned(v))
+    }
+}
+
+/// Seed for a list of borrowed strings
+struct CowStrs;
+
+impl<'de> DeserializeSeed<'de> for CowStrs {
+    type Value = Vec<Cow<'de, str>>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for CowStrs {
+    type Value = Vec<Cow<'de, str>>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a list of strings")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut values = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(value) = seq.next_element_seed(CowStr)? {
+            values.push(value);
+        }
+        Ok(values)
+    }
+}
+
+/// # Single cell of ISS row
+pub trait IssCell<'de>: Sized {
+    /// Read next cell of row
+    fn next<A: SeqAccess<'de>>(seq: &mut A) -> Result<Option<Self>, A::Error>;
+}
+
+impl<'de> IssCell<'de> for Cow<'de, str> {
+    fn next<A: SeqAccess<'de>>(seq: &mut A) -> Result<Option<Self>, A::Error> {
+        seq.next_element_seed(CowStr)
+    }
+}
+
+/// Implement IssCell for cells deserialized by value
+macro_rules! iss_cell {
+    ($($ty:ty),*) => {
+        $(
+            impl<'de> IssCell<'de> for $ty {
+                fn next<A: SeqAccess<'de>>(seq: &mut A) -> Result<Option<Self>, A::Error> {
+                    seq.next_element()
+                }
+            }
+        )*
+    };
+}
+
+iss_cell!(i32, i64, u8, f64, Option<f64>);
+
+/// Define ISS row struct read positionally from leading block columns
+macro_rules! iss_row {
+    (
+        $(#[$meta:meta])*
+        pub struct $name:ident<'a>: $block:literal {
+            $($(#[$fmeta:meta])* pub $field:ident: $ty:ty => $column:literal,)*
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone)]
+        pub struct $name<'a> {
+            $($(#[$fmeta])* pub $field: $ty,)*
+        }
+
+        impl<'de> IssRow<'de> for $name<'de> {
+            const BLOCK: &'static str = $block;
+            const COLUMNS: &'static [&'static str] = &[$($column),*];
+        }
+
+        impl<'de> Deserialize<'de> for $name<'de> {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                struct RowVisitor;
+
+                impl<'de> Visitor<'de> for RowVisitor {
+                    type Value = $name<'de>;
+
+                    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                        write!(f, "ISS '{}' row", $block)
+                    }
+
+                    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                        let mut index = 0;
+                        $(
+                            index += 1;
+                            let $field = IssCell::next(&mut seq)?
+                                .ok_or_else(|| de::Error::invalid_length(index - 1, &self))?;
+                        )*
+                        // Skip cells after known columns
+                        while seq.next_element::<IgnoredAny>()?.is_some() {}
+                        Ok($name { $($field),* })
+                    }
+                }
+
+                deserializer.deserialize_seq(RowVisitor)
+            }
+        }
+    };
+}
+
+iss_row! {
+    /// Row of `engines` block
+    pub struct EngineRow<'a>: "engines" {
+        pub id: i32 => "id",
+        pub name: Cow<'a, str> => "name",
+        pub title: Cow<'a, str> => "title",
+    }
+}
+
+iss_row! {
+    /// Row of `markets` block
+    pub struct MarketRow<'a>: "markets" {
+        pub id: i32 => "id",
+        pub name: Cow<'a, str> => "NAME",
+        pub title: Cow<'a, str> => "title",
+    }
+}
+
+iss_row! {
+    /// Row of `boards` block
+    pub struct BoardRow<'a>: "boards" {
+        pub id: i32 => "id",
+        pub board_group_id: i32 => "board_group_id",
+        pub boardid: Cow<'a, str> => "boardid",
+        pub title: Cow<'a, str> => "title",
+        pub is_traded: i64 => "is_traded",
+    }
+}
+
+iss_row! {
+    /// Row of board `securities` block, columns are selected with `securities.columns` as
+    /// their order differs between markets
+    pub struct SecurityRow<'a>: "securities" {
+        pub secid: Cow<'a, str> => "SECID",
+        pub boardid: Cow<'a, str> => "BOARDID",
+        pub shortname: Cow<'a, str> => "SHORTNAME",
+        pub decimals: u8 => "DECIMALS",
+        pub lotsize: i64 => "LOTSIZE",
+    }
+}
+
+iss_row! {
+    /// Row of `trades` block
+    pub struct TradeRow<'a>: "trades" {
+        pub tradeno: i64 => "TRADENO",
+        pub tradetime: Cow<'a, str> => "TRADETIME",
+        pub boardid: Cow<'a, str> => "BOARDID",
+        pub secid: Cow<'a, str> => "SECID",
+        pub price: f64 => "PRICE",
+        pub quantity: i64 => "QUANTITY",
+        pub value: f64 => "VALUE",
+        pub period: Cow<'a, str> => "PERIOD",
+        pub tradetime_grp: i64 => "TRADETIME_GRP",
+        pub systime: Cow<'a, str> => "SYSTIME",
+        pub buysell: Cow<'a, str> => "BUYSELL",
+        pub decimals: u8 => "DECIMALS",
+    }
+}
+
+iss_row! {
+    /// Row of daily `history` block, prices are empty for securities without trades
+    pub struct HistoryRow<'a>: "history" {
+        pub boardid: Cow<'a, str> => "BOARDID",
+        pub tradedate: Cow<'a, str> => "TRADEDATE",
+        pub shortname: Cow<'a, str> => "SHORTNAME",
+        pub secid: Cow<'a, str> => "SECID",
+        pub numtrades: i64 => "NUMTRADES",
+        pub value: f64 => "VALUE",
+        pub open: Option<f64> => "OPEN",
+        pub low: Option<f64> => "LOW",
+        pub high: Option<f64> => "HIGH",
+        pub legalcloseprice: Option<f64> => "LEGALCLOSEPRICE",
+        pub waprice: Option<f64> => "WAPRICE",
+        pub close: Option<f64> => "CLOSE",
+        pub volume: i64 => "VOLUME",
+    }
+}
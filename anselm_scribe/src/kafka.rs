@@ -0,0 +1,327 @@
+use crate::config::{Config, KafkaFormat};
+use crate::models::{decimal64, enum8, Board, Engine, Market, Security, Side, Trade};
+use crate::sink::MarketDataSink;
+use async_trait::async_trait;
+use futures::future::try_join_all;
+use rdkafka::config::ClientConfig;
+use rdkafka::producer::{FutureProducer, FutureRecord, Producer};
+use rdkafka::util::Timeout;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use time::OffsetDateTime;
+use tracing::debug;
+
+/// Delivery timeout of a message, also bounds waiting for room in a full producer queue
+const MESSAGE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// # Bincode Trade Record
+///
+/// Schema of trades published in `KafkaFormat::Bincode`. Bincode is not self-describing, so
+/// fields are fixed-size little endian integers or `u64` length-prefixed UTF-8 strings, in
+/// this order:
+///
+/// | field       | type   | notes                                 |
+/// |-------------|--------|---------------------------------------|
+/// | `engine`    | string |                                       |
+/// | `market`    | string |                                       |
+/// | `secid`     | string |                                       |
+/// | `boardid`   | string |                                       |
+/// | `tradeid`   | i64    |                                       |
+/// | `buysell`   | i8     | 1 buy, 2 sell                         |
+/// | `quantity`  | i32    | lots                                  |
+/// | `scale`     | u32    | decimal places of `price` and `value` |
+/// | `price`     | i64    | mantissa, `price / 10^scale`          |
+/// | `value`     | i64    | mantissa, `value / 10^scale`, RUB     |
+/// | `decimals`  | u8     | price decimals of the security        |
+/// | `tradetime` | i64    | unix seconds                          |
+/// | `systime`   | i64    | unix seconds                          |
+/// | `run_id`    | string |                                       |
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KafkaTrade {
+    pub engine: String,
+    pub market: String,
+    pub secid: String,
+    pub boardid: String,
+    pub tradeid: i64,
+    #[serde(with = "enum8")]
+    pub buysell: Side,
+    pub quantity: i32,
+    pub scale: u32,
+    pub price: i64,
+    pub value: i64,
+    pub decimals: u8,
+    pub tradetime: i64,
+    pub systime: i64,
+    pub run_id: String,
+}
+
+/// Encode Trade with mantissas at `decimal64::DECIMAL_SCALE`
+impl TryFrom<&Trade> for KafkaTrade {
+    type Error = String;
+
+    fn try_from(trade: &Trade) -> Result<Self, Self::Error> {
+        Ok(Self {
+            engine: trade.engine.clone(),
+            market: trade.market.clone(),
+            secid: trade.secid.clone(),
+            boardid: trade.boardid.clone(),
+            tradeid: trade.tradeid,
+            buysell: trade.buysell,
+            quantity: trade.quantity,
+            scale: decimal64::DECIMAL_SCALE,
+            price: decimal64::mantissa(&trade.price)?,
+            value: decimal64::mantissa(&trade.value)?,
+            decimals: trade.decimals,
+            tradetime: trade.tradetime.unix_timestamp(),
+            systime: trade.systime.unix_timestamp(),
+            run_id: trade.run_id.clone(),
+        })
+    }
+}
+
+/// Decode Trade, decimals keep the published `scale`
+impl TryFrom<KafkaTrade> for Trade {
+    type Error = String;
+
+    fn try_from(trade: KafkaTrade) -> Result<Self, Self::Error> {
+        let decimal = |mantissa: i64| {
+            Decimal::try_from_i128_with_scale(mantissa as i128, trade.scale)
+                .map_err(|e| e.to_string())
+        };
+        let datetime =
+            |seconds: i64| OffsetDateTime::from_unix_timestamp(seconds).map_err(|e| e.to_string());
+        Ok(Self {
+            price: decimal(trade.price)?,
+            value: decimal(trade.value)?,
+            tradetime: datetime(trade.tradetime)?,
+            systime: datetime(trade.systime)?,
+            engine: trade.engine,
+            market: trade.market,
+            secid: trade.secid,
+            boardid: trade.boardid,
+            tradeid: trade.tradeid,
+            buysell: trade.buysell,
+            quantity: trade.quantity,
+            decimals: trade.decimals,
+            run_id: trade.run_id,
+        })
+    }
+}
+
+/// # Kafka sink struct
+///
+/// Publishes market data records to Kafka topics. Trades and securities are keyed by `secid`,
+/// so all trades of a security land in the same partition in order. Writes return only after
+/// every record of the batch was acknowledged by the brokers, so runners never advance their
+/// `start` position past unacknowledged data. A write fails if a record can not be queued or is not
+/// acknowledged within `MESSAGE_TIMEOUT`.
+pub struct KafkaSink {
+    producer: FutureProducer,
+    format: KafkaFormat,
+    topic_engines: String,
+    topic_markets: String,
+    topic_boards: String,
+    topic_securities: String,
+    topic_trades: String,
+}
+
+/// # Implementation for KafkaSink Struct
+impl KafkaSink {
+    /// # KafkaSink instance factory
+    pub fn new(conf: &Config, brokers: &str) -> Result<Self, rdkafka::error::KafkaError> {
+        let producer: FutureProducer = ClientConfig::new()
+            .set("bootstrap.servers", brokers)
+            .set("client.id", "anselm_scribe")
+            .set("acks", "all")
+            .set("enable.idempotence", "true")
+            .set("compression.type", "zstd")
+            .set(
+                "message.timeout.ms",
+                MESSAGE_TIMEOUT.as_millis().to_string(),
+            )
+            .create()?;
+
+        Ok(Self {
+            producer,
+            format: conf.kafka_format,
+            topic_engines: format!("{}.engines", conf.kafka_topic_prefix),
+            topic_markets: format!("{}.markets", conf.kafka_topic_prefix),
+            topic_boards: format!("{}.boards", conf.kafka_topic_prefix),
+            topic_securities: format!("{}.securities", conf.kafka_topic_prefix),
+            topic_trades: format!("{}.trades", conf.kafka_topic_prefix),
+        })
+    }
+
+    /// # Serialize record in configured format
+    fn serialize<T: Serialize>(&self, record: &T) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let payload = match self.format {
+            KafkaFormat::Json => serde_json::to_vec(record)?,
+            KafkaFormat::Bincode => bincode::serialize(record)?,
+        };
+        Ok(payload)
+    }
+
+    /// # Publish a batch of records keyed by `key` and wait for delivery acknowledgement
+    async fn publish<T: Serialize>(
+        &self,
+        topic: &str,
+        records: &[T],
+        key: impl Fn(&T) -> &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let payloads = records
+            .iter()
+            .map(|r| self.serialize(r))
+            .collect::<Result<Vec<Vec<u8>>, _>>()?;
+
+        let deliveries = records
+            .iter()
+            .zip(payloads.iter())
+            .map(|(record, payload)| {
+                self.producer.send(
+                    FutureRecord::to(topic).key(key(record)).payload(payload),
+                    Timeout::After(MESSAGE_TIMEOUT),
+                )
+            });
+        try_join_all(deliveries).await.map_err(|(e, _)| e)?;
+        Ok(())
+    }
+}
+
+#[async_trait(?Send)]
+impl MarketDataSink for KafkaSink {
+    fn name(&self) -> &str {
+        "kafka"
+    }
+
+    fn wants_securities(&self) -> bool {
+        true
+    }
+
+    /// Topics are expected to exist or be auto created by the brokers
+    async fn init(&self) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    async fn write_engines(&self, engines: &[Engine]) -> Result<(), Box<dyn std::error::Error>> {
+        self.publish(&self.topic_engines, engines, |e| &e.name)
+            .await
+    }
+
+    async fn write_markets(&self, markets: &[Market]) -> Result<(), Box<dyn std::error::Error>> {
+        self.publish(&self.topic_markets, markets, |m| &m.name)
+            .await
+    }
+
+    async fn write_boards(&self, boards: &[Board]) -> Result<(), Box<dyn std::error::Error>> {
+        self.publish(&self.topic_boards, boards, |b| &b.boardid)
+            .await
+    }
+
+    async fn write_securities(
+        &self,
+        securities: &[Security],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.publish(&self.topic_securities, securities, |s| &s.secid)
+            .await
+    }
+
+    async fn write_trades(&self, trades: &[Trade]) -> Result<(), Box<dyn std::error::Error>> {
+        let time_publish: Instant = Instant::now();
+        match self.format {
+            KafkaFormat::Json => {
+                self.publish(&self.topic_trades, trades, |t| &t.secid)
+                    .await?
+            }
+            KafkaFormat::Bincode => {
+                let trades = trades
+                    .iter()
+                    .map(KafkaTrade::try_from)
+                    .collect::<Result<Vec<KafkaTrade>, _>>()?;
+                self.publish(&self.topic_trades, &trades, |t| &t.secid)
+                    .await?
+            }
+        }
+        debug!(
+            trades = trades.len(),
+            topic = %self.topic_trades,
+            time_ms = time_publish.elapsed().as_secs_f64() * 1e3,
+            "Trades published to Kafka"
+        );
+        Ok(())
+    }
+
+    /// Wait for all queued messages to be delivered
+    async fn flush(&self) -> Result<(), Box<dyn std::error::Error>> {
+        self.producer
+            .flush(Timeout::After(Duration::from_secs(30)))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use time::macros::datetime;
+
+    fn trade() -> Trade {
+        Trade {
+            engine: "stock".to_string(),
+            market: "shares".to_string(),
+            secid: "SBER".to_string(),
+            boardid: "TQBR".to_string(),
+            tradeid: 9_876_543_210,
+            buysell: Side::S,
+            quantity: 10,
+            price: Decimal::from_str("291.28").unwrap(),
+            value: Decimal::from_str("29128").unwrap(),
+            decimals: 2,
+            tradetime: datetime!(2024-03-01 07:00:01 UTC),
+            systime: datetime!(2024-03-01 07:00:01 UTC),
+            run_id: "run".to_string(),
+        }
+    }
+
+    #[test]
+    fn bincode_round_trip() {
+        let trade = trade();
+        let encoded = KafkaTrade::try_from(&trade).unwrap();
+        assert_eq!(encoded.scale, decimal64::DECIMAL_SCALE);
+        assert_eq!(encoded.price, 291_280_000);
+        assert_eq!(encoded.value, 29_128_000_000);
+
+        let bytes = bincode::serialize(&encoded).unwrap();
+        let decoded: KafkaTrade = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(decoded, encoded);
+
+        let decoded = Trade::try_from(decoded).unwrap();
+        assert_eq!(decoded.engine, trade.engine);
+        assert_eq!(decoded.market, trade.market);
+        assert_eq!(decoded.secid, trade.secid);
+        assert_eq!(decoded.boardid, trade.boardid);
+        assert_eq!(decoded.tradeid, trade.tradeid);
+        assert_eq!(decoded.buysell, trade.buysell);
+        assert_eq!(decoded.quantity, trade.quantity);
+        assert_eq!(decoded.price, trade.price);
+        assert_eq!(decoded.value, trade.value);
+        assert_eq!(decoded.decimals, trade.decimals);
+        assert_eq!(decoded.tradetime, trade.tradetime);
+        assert_eq!(decoded.systime, trade.systime);
+        assert_eq!(decoded.run_id, trade.run_id);
+    }
+
+    #[test]
+    fn bincode_rejects_unknown_side() {
+        let mut bytes = bincode::serialize(&KafkaTrade::try_from(&trade()).unwrap()).unwrap();
+        // buysell follows four length-prefixed strings and tradeid
+        let offset = ["stock", "shares", "SBER", "TQBR"]
+            .iter()
+            .map(|s| 8 + s.len())
+            .sum::<usize>()
+            + 8;
+        assert_eq!(bytes[offset], Side::S as u8);
+        bytes[offset] = 3;
+        assert!(bincode::deserialize::<KafkaTrade>(&bytes).is_err());
+    }
+}
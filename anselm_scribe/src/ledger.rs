@@ -0,0 +1,806 @@
+use crate::config::Selection;
+use crate::disk::write_file_atomic;
+use crate::merkle::{from_hex, leaf_hash, merkle_root, sha256, to_hex, Hash};
+use crate::mirror::MirrorStore;
+use crate::models::{decimal64, Trade, ISS_DATE, MOSCOW_OFFSET};
+use crate::shutdown::Shutdown;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+use time::{Date, Duration, OffsetDateTime};
+use tokio::io::AsyncWriteExt;
+use tracing::{debug, info, info_span, warn, Instrument};
+
+/// Version of canonical trade serialization
+pub const CANONICAL_VERSION: u8 = 1;
+
+/// Previous hash of the first ledger entry
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Append length prefixed string
+fn put_str(buf: &mut Vec<u8>, value: &str) {
+    buf.extend_from_slice(&(value.len() as u32).to_be_bytes());
+    buf.extend_from_slice(value.as_bytes());
+}
+
+/// # Canonical serialization of trade
+///
+/// Stable byte layout hashed into Merkle trees, independent of storage format. Integers are
+/// big-endian, strings are prefixed with `u32` length, decimals are `i64` mantissas at
+/// `decimal64::DECIMAL_SCALE` and times are unix seconds:
+///
+/// ```text
+/// u8 version, str engine, str market, str boardid, str secid, i64 tradeid, u8 buysell,
+/// i32 quantity, i64 price, i64 value, u8 decimals, i64 tradetime, i64 systime
+/// ```
+pub fn canonical_trade(trade: &Trade) -> Result<Vec<u8>, String> {
+    let mut buf = Vec::with_capacity(96);
+    buf.push(CANONICAL_VERSION);
+    put_str(&mut buf, &trade.engine);
+    put_str(&mut buf, &trade.market);
+    put_str(&mut buf, &trade.boardid);
+    put_str(&mut buf, &trade.secid);
+    buf.extend_from_slice(&trade.tradeid.to_be_bytes());
+    buf.push(trade.buysell as u8);
+    buf.extend_from_slice(&trade.quantity.to_be_bytes());
+    buf.extend_from_slice(&decimal64::mantissa(&trade.price)?.to_be_bytes());
+    buf.extend_from_slice(&decimal64::mantissa(&trade.value)?.to_be_bytes());
+    buf.push(trade.decimals);
+    buf.extend_from_slice(&trade.tradetime.unix_timestamp().to_be_bytes());
+    buf.extend_from_slice(&trade.systime.unix_timestamp().to_be_bytes());
+    Ok(buf)
+}
+
+/// Merkle leaf of trade
+pub fn trade_leaf(trade: &Trade) -> Result<Hash, String> {
+    Ok(leaf_hash(&canonical_trade(trade)?))
+}
+
+/// # Ledger entry sealing trades of a security on a board for a day
+///
+/// `root` is the Merkle root of trades ordered by trade id. Entries are chained, `hash`
+/// covers every field of the entry including `prev_hash` of the previous entry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    pub seq: u64,
+    pub engine: String,
+    pub market: String,
+    pub boardid: String,
+    pub secid: String,
+    pub day: String,
+    pub trades: u64,
+    pub first_tradeid: i64,
+    pub last_tradeid: i64,
+    pub root: String,
+    pub sealed_at: i64,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+/// Key of sealed trades
+pub type LedgerKey = (String, String, String, String, String);
+
+/// Implementation for LedgerEntry struct
+impl LedgerEntry {
+    /// Hash of entry fields
+    pub fn compute_hash(&self) -> Result<Hash, String> {
+        let mut buf = Vec::with_capacity(256);
+        buf.extend_from_slice(&self.seq.to_be_bytes());
+        put_str(&mut buf, &self.engine);
+        put_str(&mut buf, &self.market);
+        put_str(&mut buf, &self.boardid);
+        put_str(&mut buf, &self.secid);
+        put_str(&mut buf, &self.day);
+        buf.extend_from_slice(&self.trades.to_be_bytes());
+        buf.extend_from_slice(&self.first_tradeid.to_be_bytes());
+        buf.extend_from_slice(&self.last_tradeid.to_be_bytes());
+        buf.extend_from_slice(&from_hex(&self.root)?);
+        buf.extend_from_slice(&self.sealed_at.to_be_bytes());
+        buf.extend_from_slice(&from_hex(&self.prev_hash)?);
+        Ok(sha256(&buf))
+    }
+
+    pub fn key(&self) -> LedgerKey {
+        (
+            self.engine.clone(),
+            self.market.clone(),
+            self.boardid.clone(),
+            self.secid.clone(),
+            self.day.clone(),
+        )
+    }
+}
+
+/// # Append-only ledger of daily Merkle roots
+///
+/// Directory with `ledger.jsonl`, one chained entry per line, and leaves of every entry used
+/// to find altered trades:
+///
+/// ```text
+/// ledger.jsonl
+/// ledger.lock
+/// leaves/2024-03-01/stock-shares-TQBR-SBER.bin
+/// ```
+///
+/// Leaves files hold `i64` trade id and 32 byte leaf hash per trade in trade id order.
+pub struct Ledger {
+    dir: PathBuf,
+}
+
+/// Implementation for Ledger struct
+impl Ledger {
+    /// # Ledger instance factory
+    pub fn new(dir: &str) -> Self {
+        Self {
+            dir: PathBuf::from(dir),
+        }
+    }
+
+    /// Ledger directory
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// # Lock ledger for appending
+    ///
+    /// Waits for an exclusive lock of `ledger.lock`, held until the returned file is dropped, so
+    /// concurrent seal runs append one after another instead of forking the chain. A partial
+    /// entry left by an interrupted append is truncated once the lock is taken.
+    pub async fn lock(&self) -> io::Result<std::fs::File> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let path = self.dir.join("ledger.lock");
+        let entries_path = self.entries_path();
+        let (file, truncated) = tokio::task::spawn_blocking(move || {
+            let file = std::fs::OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .write(true)
+                .open(path)?;
+            file.lock()?;
+            let truncated = truncate_partial_entry(&entries_path)?;
+            Ok::<_, io::Error>((file, truncated))
+        })
+        .await
+        .map_err(io::Error::other)??;
+        if truncated > 0 {
+            warn!(bytes = truncated, "Partial ledger entry truncated");
+        }
+        Ok(file)
+    }
+
+    fn entries_path(&self) -> PathBuf {
+        self.dir.join("ledger.jsonl")
+    }
+
+    fn leaves_path(&self, entry: &LedgerEntry) -> PathBuf {
+        self.dir.join("leaves").join(&entry.day).join(format!(
+            "{}-{}-{}-{}.bin",
+            entry.engine, entry.market, entry.boardid, entry.secid
+        ))
+    }
+
+    /// # Load all entries in order, no entries if ledger does not exist
+    ///
+    /// An entry is appended once its line ends with a newline, a trailing partial line left by
+    /// an interrupted append is skipped
+    pub async fn entries(&self) -> Result<Vec<LedgerEntry>, Box<dyn std::error::Error>> {
+        let content = match tokio::fs::read(self.entries_path()).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let end = content
+            .iter()
+            .rposition(|b| *b == b'\n')
+            .map_or(0, |i| i + 1);
+        if end < content.len() {
+            warn!(
+                bytes = content.len() - end,
+                "Partial ledger entry skipped, truncated by the next seal"
+            );
+        }
+        let content = std::str::from_utf8(&content[..end])?;
+        let mut entries = Vec::new();
+        for line in content.lines().filter(|l| !l.trim().is_empty()) {
+            entries.push(serde_json::from_str(line)?);
+        }
+        Ok(entries)
+    }
+
+    /// Load leaves of entry as `(tradeid, leaf)`
+    pub async fn leaves(&self, entry: &LedgerEntry) -> io::Result<Vec<(i64, Hash)>> {
+        let data = tokio::fs::read(self.leaves_path(entry)).await?;
+        if data.len() % 40 != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Truncated leaves file",
+            ));
+        }
+        Ok(data
+            .chunks_exact(40)
+            .map(|chunk| {
+                let mut tradeid = [0u8; 8];
+                let mut leaf = [0u8; 32];
+                tradeid.copy_from_slice(&chunk[..8]);
+                leaf.copy_from_slice(&chunk[8..]);
+                (i64::from_be_bytes(tradeid), leaf)
+            })
+            .collect())
+    }
+
+    /// Seal trades of a security ordered by trade id, appending a new entry after `prev`
+    pub async fn append(
+        &self,
+        prev: Option<&LedgerEntry>,
+        day: &str,
+        trades: &[Trade],
+    ) -> Result<LedgerEntry, Box<dyn std::error::Error>> {
+        let (first, last) = match (trades.first(), trades.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Err("No trades to seal".into()),
+        };
+        let leaves: Vec<(i64, Hash)> = trades
+            .iter()
+            .map(|t| Ok((t.tradeid, trade_leaf(t)?)))
+            .collect::<Result<_, String>>()?;
+        let hashes: Vec<Hash> = leaves.iter().map(|(_, leaf)| *leaf).collect();
+
+        let mut entry = LedgerEntry {
+            seq: prev.map_or(0, |p| p.seq + 1),
+            engine: first.engine.clone(),
+            market: first.market.clone(),
+            boardid: first.boardid.clone(),
+            secid: first.secid.clone(),
+            day: day.to_string(),
+            trades: trades.len() as u64,
+            first_tradeid: first.tradeid,
+            last_tradeid: last.tradeid,
+            root: to_hex(&merkle_root(&hashes)),
+            sealed_at: OffsetDateTime::now_utc().unix_timestamp(),
+            prev_hash: prev.map_or(GENESIS_HASH.to_string(), |p| p.hash.clone()),
+            hash: String::new(),
+        };
+        entry.hash = to_hex(&entry.compute_hash()?);
+
+        // Leaves first, an entry is only appended once its leaves are stored
+        let leaves_path = self.leaves_path(&entry);
+        if let Some(dir) = leaves_path.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        let mut data = Vec::with_capacity(leaves.len() * 40);
+        for (tradeid, leaf) in &leaves {
+            data.extend_from_slice(&tradeid.to_be_bytes());
+            data.extend_from_slice(leaf);
+        }
+        write_file_atomic(&leaves_path, &data).await?;
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.entries_path())
+            .await?;
+        file.write_all(format!("{}\n", serde_json::to_string(&entry)?).as_bytes())
+            .await?;
+        file.sync_all().await?;
+
+        Ok(entry)
+    }
+}
+
+/// Truncate ledger file after its last newline, returns number of truncated bytes
+fn truncate_partial_entry(path: &Path) -> io::Result<u64> {
+    let content = match std::fs::read(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+    let end = content
+        .iter()
+        .rposition(|b| *b == b'\n')
+        .map_or(0, |i| i + 1);
+    if end == content.len() {
+        return Ok(0);
+    }
+    let file = std::fs::OpenOptions::new().write(true).open(path)?;
+    file.set_len(end as u64)?;
+    file.sync_all()?;
+    Ok((content.len() - end) as u64)
+}
+
+/// Trades of a board day grouped by security, ordered by trade id
+async fn board_day_trades(
+    store: &dyn MirrorStore,
+    engine: &str,
+    market: &str,
+    boardid: &str,
+    day: Date,
+) -> Result<BTreeMap<String, Vec<Trade>>, Box<dyn std::error::Error>> {
+    let from = day.midnight().assume_offset(MOSCOW_OFFSET);
+    let trades = store
+        .trades_between(
+            engine,
+            market,
+            boardid,
+            None,
+            from,
+            from + Duration::days(1),
+        )
+        .await
+        .map_err(|e| e as Box<dyn std::error::Error>)?;
+
+    let mut securities: BTreeMap<String, Vec<Trade>> = BTreeMap::new();
+    for trade in trades {
+        securities
+            .entry(trade.secid.clone())
+            .or_default()
+            .push(trade);
+    }
+    for trades in securities.values_mut() {
+        trades.sort_by_key(|t| t.tradeid);
+    }
+    Ok(securities)
+}
+
+/// # Seal trades of selected boards for Moscow dates `[from, till)`
+///
+/// Every security and day is sealed once, already sealed days are skipped. Ledger is locked
+/// from reading the last entry until the last append. Once `shutdown` is requested sealing
+/// stops after the entry being appended, the next run continues with unsealed days. Returns
+/// number of appended entries.
+pub async fn seal(
+    store: &dyn MirrorStore,
+    ledger: &Ledger,
+    selection: &Selection,
+    from: Date,
+    till: Date,
+    shutdown: &Shutdown,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let _lock = ledger.lock().await?;
+    let entries = ledger.entries().await?;
+    let sealed: HashSet<LedgerKey> = entries.iter().map(LedgerEntry::key).collect();
+    let mut prev = entries.last().cloned();
+    let mut appended = 0;
+
+    let boards = selected_boards(store, selection).await?;
+    let mut day = from;
+    'days: while day < till {
+        let day_str = day.format(ISS_DATE)?;
+        for (engine, market, boardid) in &boards {
+            for (secid, trades) in board_day_trades(store, engine, market, boardid, day).await? {
+                let key = (
+                    engine.clone(),
+                    market.clone(),
+                    boardid.clone(),
+                    secid.clone(),
+                    day_str.clone(),
+                );
+                if sealed.contains(&key) {
+                    debug!(%boardid, %secid, day = %day_str, "Ledger entry already sealed");
+                    continue;
+                }
+                if shutdown.requested() {
+                    warn!(%boardid, %secid, day = %day_str, "Sealing stopped");
+                    break 'days;
+                }
+                let entry = ledger.append(prev.as_ref(), &day_str, &trades).await?;
+                info!(
+                    seq = entry.seq,
+                    boardid = %entry.boardid,
+                    secid = %entry.secid,
+                    day = %entry.day,
+                    trades = entry.trades,
+                    root = %entry.root,
+                    "Ledger entry sealed"
+                );
+                prev = Some(entry);
+                appended += 1;
+            }
+        }
+        day = match day.next_day() {
+            Some(next) => next,
+            None => break,
+        };
+    }
+
+    Ok(appended)
+}
+
+/// Boards of store matching selection
+async fn selected_boards(
+    store: &dyn MirrorStore,
+    selection: &Selection,
+) -> Result<Vec<(String, String, String)>, Box<dyn std::error::Error>> {
+    let mut boards = Vec::new();
+    for engine in store
+        .engines()
+        .await
+        .map_err(|e| e as Box<dyn std::error::Error>)?
+    {
+        if !selection.engine(&engine.name) {
+            continue;
+        }
+        let markets = store
+            .markets(&engine.name)
+            .await
+            .map_err(|e| e as Box<dyn std::error::Error>)?;
+        for market in markets.iter().filter(|m| selection.market(&m.name)) {
+            let market_boards = store
+                .boards(&engine.name, &market.name)
+                .await
+                .map_err(|e| e as Box<dyn std::error::Error>)?;
+            boards.extend(
+                market_boards
+                    .into_iter()
+                    .filter(|b| selection.board(&b.boardid))
+                    .map(|b| (b.engine, b.market, b.boardid)),
+            );
+        }
+    }
+    boards.dedup();
+    Ok(boards)
+}
+
+/// # Verify ledger chain and recompute roots of sealed trades
+///
+/// Reports broken chain links, altered leaves files and every trade altered, removed or added
+/// after its day was sealed. Entries of Moscow dates `[from, till)` are checked if `range` is
+/// defined, all entries otherwise. Returns number of problems.
+pub async fn verify(
+    store: &dyn MirrorStore,
+    ledger: &Ledger,
+    range: Option<(Date, Date)>,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let entries = ledger.entries().await?;
+    let mut problems = 0;
+
+    // Chain
+    let mut prev_hash = GENESIS_HASH.to_string();
+    for (seq, entry) in entries.iter().enumerate() {
+        if entry.seq != seq as u64 || entry.prev_hash != prev_hash {
+            warn!(
+                seq,
+                "Ledger chain broken, entry does not follow previous entry"
+            );
+            problems += 1;
+        }
+        if to_hex(&entry.compute_hash()?) != entry.hash {
+            warn!(seq, "Ledger entry hash mismatch, entry was altered");
+            problems += 1;
+        }
+        prev_hash = entry.hash.clone();
+    }
+    info!(entries = entries.len(), "Ledger chain checked");
+
+    // Entries grouped by board day
+    let mut days: BTreeMap<(String, String, String, String), Vec<&LedgerEntry>> = BTreeMap::new();
+    for entry in &entries {
+        if let Some((from, till)) = range {
+            let day = Date::parse(&entry.day, ISS_DATE)?;
+            if day < from || day >= till {
+                continue;
+            }
+        }
+        days.entry((
+            entry.day.clone(),
+            entry.engine.clone(),
+            entry.market.clone(),
+            entry.boardid.clone(),
+        ))
+        .or_default()
+        .push(entry);
+    }
+
+    for ((day, engine, market, boardid), sealed) in &days {
+        let mut securities =
+            board_day_trades(store, engine, market, boardid, Date::parse(day, ISS_DATE)?).await?;
+
+        for entry in sealed {
+            let trades = securities.remove(&entry.secid).unwrap_or_default();
+            let span = info_span!(
+                "ledger_entry",
+                seq = entry.seq,
+                boardid = %entry.boardid,
+                secid = %entry.secid,
+                day = %entry.day
+            );
+            problems += verify_entry(ledger, entry, &trades)
+                .instrument(span)
+                .await?;
+        }
+
+        // Securities traded but not sealed that day
+        for (secid, trades) in securities {
+            warn!(
+                %boardid,
+                %secid,
+                %day,
+                trades = trades.len(),
+                "Trades of unsealed security added after sealing"
+            );
+            problems += trades.len();
+        }
+    }
+
+    info!(
+        entries = days.values().map(Vec::len).sum::<usize>(),
+        problems, "Ledger entries checked"
+    );
+    Ok(problems)
+}
+
+/// Compare stored trades of entry with its leaves and root
+async fn verify_entry(
+    ledger: &Ledger,
+    entry: &LedgerEntry,
+    trades: &[Trade],
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let mut problems = 0;
+
+    let sealed = match ledger.leaves(entry).await {
+        Ok(leaves) => leaves,
+        Err(e) => {
+            warn!(error = %e, "Ledger entry leaves unavailable");
+            return Ok(1);
+        }
+    };
+    let sealed_hashes: Vec<Hash> = sealed.iter().map(|(_, leaf)| *leaf).collect();
+    if to_hex(&merkle_root(&sealed_hashes)) != entry.root {
+        warn!("Ledger entry leaves file does not match sealed root");
+        problems += 1;
+    }
+
+    let current: Vec<(i64, Hash)> = trades
+        .iter()
+        .map(|t| Ok((t.tradeid, trade_leaf(t)?)))
+        .collect::<Result<_, String>>()?;
+    let current_hashes: Vec<Hash> = current.iter().map(|(_, leaf)| *leaf).collect();
+    if to_hex(&merkle_root(&current_hashes)) == entry.root {
+        return Ok(problems);
+    }
+
+    // Root differs, find changed trades
+    let sealed: BTreeMap<i64, Hash> = sealed.into_iter().collect();
+    let current: BTreeMap<i64, Hash> = current.into_iter().collect();
+    for (tradeid, leaf) in &sealed {
+        match current.get(tradeid) {
+            None => warn!(tradeid, "Trade removed after sealing"),
+            Some(now) if now != leaf => warn!(tradeid, "Trade altered after sealing"),
+            Some(_) => continue,
+        }
+        problems += 1;
+    }
+    for tradeid in current.keys().filter(|id| !sealed.contains_key(id)) {
+        warn!(tradeid, "Trade added after sealing");
+        problems += 1;
+    }
+    if problems == 0 {
+        warn!("Ledger entry root mismatch");
+        problems += 1;
+    }
+
+    Ok(problems)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mirror::MirrorResult;
+    use crate::models::{Board, Engine, Market, Side};
+    use async_trait::async_trait;
+    use rust_decimal::Decimal;
+    use time::macros::{date, datetime};
+
+    const DAY: Date = date!(2024 - 03 - 01);
+
+    /// Store of board trades for verification
+    struct TradesStore {
+        trades: Vec<Trade>,
+    }
+
+    #[async_trait]
+    impl MirrorStore for TradesStore {
+        async fn engines(&self) -> MirrorResult<Vec<Engine>> {
+            Ok(Vec::new())
+        }
+
+        async fn markets(&self, _engine: &str) -> MirrorResult<Vec<Market>> {
+            Ok(Vec::new())
+        }
+
+        async fn boards(&self, _engine: &str, _market: &str) -> MirrorResult<Vec<Board>> {
+            Ok(Vec::new())
+        }
+
+        async fn trades_page(
+            &self,
+            _engine: &str,
+            _market: &str,
+            _boardid: &str,
+            _secid: Option<&str>,
+            _start: u64,
+            _limit: u64,
+        ) -> MirrorResult<Vec<Trade>> {
+            Ok(Vec::new())
+        }
+
+        async fn trades_after(
+            &self,
+            _engine: &str,
+            _market: &str,
+            _boardid: &str,
+            _secid: Option<&str>,
+            _after: u64,
+            _limit: u64,
+        ) -> MirrorResult<Vec<Trade>> {
+            Ok(Vec::new())
+        }
+
+        async fn trades_between(
+            &self,
+            engine: &str,
+            market: &str,
+            boardid: &str,
+            secid: Option<&str>,
+            from: OffsetDateTime,
+            till: OffsetDateTime,
+        ) -> MirrorResult<Vec<Trade>> {
+            Ok(self
+                .trades
+                .iter()
+                .filter(|t| t.engine == engine && t.market == market && t.boardid == boardid)
+                .filter(|t| secid.is_none_or(|secid| t.secid == secid))
+                .filter(|t| t.tradetime >= from && t.tradetime < till)
+                .cloned()
+                .collect())
+        }
+
+        async fn last_trade_time(
+            &self,
+            _engine: &str,
+            _market: &str,
+            _boardid: &str,
+        ) -> MirrorResult<Option<OffsetDateTime>> {
+            Ok(None)
+        }
+    }
+
+    fn trade(secid: &str, tradeid: i64) -> Trade {
+        Trade {
+            engine: "stock".to_string(),
+            market: "shares".to_string(),
+            secid: secid.to_string(),
+            boardid: "TQBR".to_string(),
+            tradeid,
+            buysell: Side::B,
+            quantity: 10,
+            price: Decimal::new(29128, 2),
+            value: Decimal::new(29128, 0),
+            decimals: 2,
+            tradetime: datetime!(2024-03-01 10:00:00 +3),
+            systime: datetime!(2024-03-01 10:00:00 +3),
+            run_id: String::new(),
+        }
+    }
+
+    /// Empty ledger in a temporary directory
+    fn temp_ledger(name: &str) -> Ledger {
+        let dir =
+            std::env::temp_dir().join(format!("anselm_ledger_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        Ledger::new(dir.to_str().unwrap())
+    }
+
+    /// Seal SBER and GAZP trades of `DAY`, returns the store holding them
+    async fn sealed(ledger: &Ledger) -> TradesStore {
+        let sber = vec![trade("SBER", 1), trade("SBER", 3), trade("SBER", 4)];
+        let gazp = vec![trade("GAZP", 2), trade("GAZP", 5)];
+        let _lock = ledger.lock().await.unwrap();
+        let first = ledger.append(None, "2024-03-01", &sber).await.unwrap();
+        ledger
+            .append(Some(&first), "2024-03-01", &gazp)
+            .await
+            .unwrap();
+        TradesStore {
+            trades: sber.into_iter().chain(gazp).collect(),
+        }
+    }
+
+    /// Rewrite ledger file with entries
+    fn rewrite(ledger: &Ledger, entries: &[LedgerEntry]) {
+        let lines: String = entries
+            .iter()
+            .map(|e| format!("{}\n", serde_json::to_string(e).unwrap()))
+            .collect();
+        std::fs::write(ledger.entries_path(), lines).unwrap();
+    }
+
+    #[tokio::test]
+    async fn verify_sealed_ledger() {
+        let ledger = temp_ledger("sealed");
+        let store = sealed(&ledger).await;
+
+        let entries = ledger.entries().await.unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].prev_hash, GENESIS_HASH);
+        assert_eq!(entries[1].prev_hash, entries[0].hash);
+        assert_eq!((entries[0].first_tradeid, entries[0].last_tradeid), (1, 4));
+        assert_eq!(verify(&store, &ledger, None).await.unwrap(), 0);
+        assert_eq!(
+            verify(&store, &ledger, Some((DAY, DAY.next_day().unwrap())))
+                .await
+                .unwrap(),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn verify_detects_edited_entry() {
+        let ledger = temp_ledger("edited");
+        let store = sealed(&ledger).await;
+        let mut entries = ledger.entries().await.unwrap();
+
+        // Edited entry no longer matches its hash
+        entries[0].sealed_at += 1;
+        rewrite(&ledger, &entries);
+        assert_eq!(verify(&store, &ledger, None).await.unwrap(), 1);
+
+        // Rehashed entry breaks the link of the next entry
+        entries[0].hash = to_hex(&entries[0].compute_hash().unwrap());
+        rewrite(&ledger, &entries);
+        assert_eq!(verify(&store, &ledger, None).await.unwrap(), 1);
+
+        // Removed entry breaks the chain, its trades are no longer sealed
+        rewrite(&ledger, &entries[1..]);
+        assert_eq!(verify(&store, &ledger, None).await.unwrap(), 1 + 3);
+    }
+
+    #[tokio::test]
+    async fn verify_detects_altered_trades() {
+        let ledger = temp_ledger("altered");
+        let mut store = sealed(&ledger).await;
+
+        store.trades[1].quantity += 1;
+        store.trades.remove(2);
+        store.trades.push(trade("SBER", 6));
+        assert_eq!(verify(&store, &ledger, None).await.unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn partial_entry_skipped_and_truncated() {
+        let ledger = temp_ledger("partial");
+        sealed(&ledger).await;
+        let path = ledger.entries_path();
+        let content = std::fs::read(&path).unwrap();
+
+        // Append interrupted halfway through the line
+        let mut torn = content.clone();
+        torn.extend_from_slice(b"{\"seq\":2,\"engine\":\"st");
+        std::fs::write(&path, &torn).unwrap();
+        assert_eq!(ledger.entries().await.unwrap().len(), 2);
+
+        drop(ledger.lock().await.unwrap());
+        assert_eq!(std::fs::read(&path).unwrap(), content);
+    }
+
+    #[test]
+    fn truncate_partial_entry_after_last_newline() {
+        let ledger = temp_ledger("truncate");
+        std::fs::create_dir_all(ledger.dir()).unwrap();
+        let path = ledger.entries_path();
+
+        assert_eq!(truncate_partial_entry(&path).unwrap(), 0);
+
+        std::fs::write(&path, b"{}\n{}\n").unwrap();
+        assert_eq!(truncate_partial_entry(&path).unwrap(), 0);
+        assert_eq!(std::fs::read(&path).unwrap(), b"{}\n{}\n");
+
+        std::fs::write(&path, b"{}\n{\"se").unwrap();
+        assert_eq!(truncate_partial_entry(&path).unwrap(), 4);
+        assert_eq!(std::fs::read(&path).unwrap(), b"{}\n");
+
+        std::fs::write(&path, b"{\"se").unwrap();
+        assert_eq!(truncate_partial_entry(&path).unwrap(), 4);
+        assert_eq!(std::fs::read(&path).unwrap(), b"");
+    }
+}
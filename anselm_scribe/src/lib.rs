@@ -1,4 +1,28 @@
+pub mod cassette;
+pub mod checkpoint;
 pub mod config;
 pub mod db;
+pub mod disk;
+pub mod gaps;
+pub mod import;
+pub mod ingest;
+pub mod iss;
+pub mod kafka;
+pub mod ledger;
+pub mod merkle;
+pub mod metrics;
+pub mod migrations;
+pub mod mirror;
 pub mod models;
+pub mod pg;
+pub mod proof;
+pub mod reconcile;
+pub mod reference;
+pub mod report;
 pub mod runners;
+pub mod s3;
+pub mod shutdown;
+pub mod sink;
+pub mod sqlite;
+pub mod telemetry;
+pub mod writer;
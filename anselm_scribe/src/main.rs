@@ -1,33 +1,186 @@
-use anselm_scribe::config::Config;
+use anselm_scribe::config::{Cli, Command, Config};
 use anselm_scribe::db;
+use anselm_scribe::import;
+use anselm_scribe::metrics;
 use anselm_scribe::runners;
+use anselm_scribe::shutdown::Shutdown;
+use anselm_scribe::sink::{self, MarketDataSink};
+use anselm_scribe::telemetry;
 
 use clap::Parser;
+use std::process::ExitCode;
+use tracing::error;
+
+/// Exit code of `verify` when duplicates, open gaps or trades changed after sealing remain in
+/// stored market data, of `reconcile` when trades mismatch ISS daily history and of
+/// `verify-proof` when proof is invalid
+const EXIT_VERIFY_FAILED: u8 = 3;
+
+/// Exit code of `status` when database schema is older than expected
+const EXIT_SCHEMA_OUTDATED: u8 = 4;
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Load config from CLI arguments and env variables
-    let conf = Config::parse();
-
-    // Initialize database connection and schema market data to disk is false
-    let db = if conf.md_disk {
-        None
-    } else {
-        Some(db::ClickhouseDatabase::new(&conf))
+async fn main() -> ExitCode {
+    // Load config from CLI arguments and env variables, exits with code 2 on invalid arguments
+    let cli = Cli::parse();
+
+    // Logs go to stderr, traces are exported if OTLP endpoint is defined
+    let telemetry = match telemetry::init_telemetry(&cli.conf, "anselm_scribe") {
+        Ok(telemetry) => telemetry,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return ExitCode::FAILURE;
+        }
     };
 
-    // Init db if defined
-    if let Some(ref db) = db {
-        db.init().await?;
+    let code = match run(&cli.conf, &cli.command).await {
+        Ok(code) => code,
+        Err(e) => {
+            error!(error = %e, "Command failed");
+            ExitCode::FAILURE
+        }
+    };
+
+    telemetry.shutdown().await;
+    code
+}
+
+/// Execute command
+async fn run(conf: &Config, command: &Command) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    // Metrics are served while the command runs
+    if let Some(listen) = &conf.metrics_listen {
+        metrics::spawn_server(listen).await?;
     }
 
-    // Execute runners
-    if conf.threads > 1 {
-        //runners::parallel_runner(&conf).await?;
-        runners::base_runner(&conf, &db).await?;
-    } else {
-        runners::base_runner(&conf, &db).await?;
+    match command {
+        // Initialize market data sinks and their schema
+        Command::Init => {
+            let sinks = init_sinks(conf).await?;
+            close_sinks(&sinks).await?;
+        }
+        // Manage database schema
+        Command::Migrate { action } => {
+            let db = db::ClickhouseDatabase::new(conf);
+            runners::migrate_runner(&db, action).await?;
+        }
+        // Crawl market data once, SIGINT or SIGTERM stops after the current board
+        Command::Crawl(args) => {
+            let sinks = init_sinks(conf).await?;
+            let shutdown = Shutdown::listen();
+            let crawled = runners::crawl_runner(conf, args, &sinks, &shutdown).await;
+            // Sinks are closed even if crawling failed, so written trades are flushed
+            let closed = close_sinks(&sinks).await;
+            crawled?;
+            closed?;
+        }
+        // Crawl new trades until SIGINT or SIGTERM
+        Command::Follow(args) => {
+            let sinks = init_sinks(conf).await?;
+            let shutdown = Shutdown::listen();
+            let followed = runners::follow_runner(conf, args, &sinks, &shutdown).await;
+            let closed = close_sinks(&sinks).await;
+            followed?;
+            closed?;
+        }
+        // Load daily history into database, SIGINT or SIGTERM stops after the current day
+        Command::Backfill(args) => {
+            let db = db::ClickhouseDatabase::new(conf);
+            db.init().await?;
+            let shutdown = Shutdown::listen();
+            runners::backfill_runner(conf, args, &db, &shutdown).await?;
+        }
+        // Reconcile trades against ISS daily history, SIGINT or SIGTERM stops after the
+        // current day
+        Command::Reconcile(args) => {
+            let db = db::ClickhouseDatabase::new(conf);
+            db.init().await?;
+            let shutdown = Shutdown::listen();
+            if !runners::reconcile_runner(conf, args, &db, &shutdown).await? {
+                return Ok(ExitCode::from(EXIT_VERIFY_FAILED));
+            }
+        }
+        // Import market data files from disk into market data sinks
+        Command::Import { paths } => {
+            let sinks = init_sinks(conf).await?;
+            let imported = import::import_runner(conf, &sinks, paths).await;
+            let closed = close_sinks(&sinks).await;
+            imported?;
+            closed?;
+        }
+        // Run analytics report
+        Command::Report(args) => {
+            let db = db::ClickhouseDatabase::new(conf);
+            runners::report_runner(conf, &db, args).await?;
+        }
+        // Seal daily Merkle roots of trades into the ledger, SIGINT or SIGTERM stops after the
+        // current entry
+        Command::Seal(args) => {
+            let shutdown = Shutdown::listen();
+            runners::seal_runner(conf, args, &shutdown).await?;
+        }
+        // Generate manifest signing key
+        Command::Keygen => {
+            runners::keygen_runner(conf).await?;
+        }
+        // Sign manifests of sealed days
+        Command::Sign(args) => {
+            runners::sign_runner(conf, args).await?;
+        }
+        // Export inclusion proof of a trade
+        Command::Prove(args) => {
+            runners::prove_runner(conf, args).await?;
+        }
+        // Check inclusion proof offline
+        Command::VerifyProof(args) => {
+            if !runners::verify_proof_runner(args).await? {
+                return Ok(ExitCode::from(EXIT_VERIFY_FAILED));
+            }
+        }
+        // Verify stored market data and repair gaps, SIGINT or SIGTERM stops after the current
+        // check or gap
+        Command::Verify(args) => {
+            let db = db::ClickhouseDatabase::new(conf);
+            let shutdown = Shutdown::listen();
+            if !runners::verify_runner(conf, &db, args, &shutdown).await? {
+                return Ok(ExitCode::from(EXIT_VERIFY_FAILED));
+            }
+        }
+        // Show schema and ingestion state
+        Command::Status => {
+            let db = db::ClickhouseDatabase::new(conf);
+            if !runners::status_runner(&db).await? {
+                return Ok(ExitCode::from(EXIT_SCHEMA_OUTDATED));
+            }
+        }
     }
 
-    Ok(())
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Create market data sinks and initialize their schema
+async fn init_sinks(
+    conf: &Config,
+) -> Result<Vec<Box<dyn MarketDataSink>>, Box<dyn std::error::Error>> {
+    let sinks = sink::sinks_from_config(conf)?;
+    for sink in &sinks {
+        sink.init().await?;
+    }
+    Ok(sinks)
+}
+
+/// # Close market data sinks
+///
+/// Every sink is closed even if closing another one failed, errors of all sinks are combined
+async fn close_sinks(sinks: &[Box<dyn MarketDataSink>]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut errors: Vec<String> = Vec::new();
+    for sink in sinks {
+        if let Err(e) = sink.close().await {
+            error!(sink = sink.name(), error = %e, "Closing sink failed");
+            errors.push(format!("{}: {}", sink.name(), e));
+        }
+    }
+    match errors.is_empty() {
+        true => Ok(()),
+        false => Err(format!("Closing sinks failed: {}", errors.join("; ")).into()),
+    }
 }
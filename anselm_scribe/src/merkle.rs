@@ -0,0 +1,241 @@
+use sha2::{Digest, Sha256};
+
+/// SHA-256 hash
+pub type Hash = [u8; 32];
+
+/// # Hash of a Merkle tree leaf
+///
+/// Leaves and nodes are hashed with different prefixes as in RFC 6962, so a node can not be
+/// passed off as a leaf
+pub fn leaf_hash(data: &[u8]) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([0x00]);
+    hasher.update(data);
+    hasher.finalize().into()
+}
+
+/// Hash of a Merkle tree node
+pub fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([0x01]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// SHA-256 hash of data
+pub fn sha256(data: &[u8]) -> Hash {
+    Sha256::digest(data).into()
+}
+
+/// # Merkle tree hash of ordered leaf hashes
+///
+/// Tree is built as in RFC 6962: leaves are split at the largest power of two smaller than
+/// their number, root of no leaves is the hash of empty data
+pub fn merkle_root(leaves: &[Hash]) -> Hash {
+    match leaves.len() {
+        0 => sha256(&[]),
+        1 => leaves[0],
+        n => {
+            let split = split_point(n);
+            node_hash(
+                &merkle_root(&leaves[..split]),
+                &merkle_root(&leaves[split..]),
+            )
+        }
+    }
+}
+
+/// # Audit path of leaf at `index`
+///
+/// Hashes of sibling subtrees from the leaf up to the root as in RFC 6962, empty if `index` is
+/// out of range
+pub fn audit_path(leaves: &[Hash], index: usize) -> Vec<Hash> {
+    if index >= leaves.len() || leaves.len() == 1 {
+        return Vec::new();
+    }
+    let split = split_point(leaves.len());
+    let (mut path, sibling) = match index < split {
+        true => (
+            audit_path(&leaves[..split], index),
+            merkle_root(&leaves[split..]),
+        ),
+        false => (
+            audit_path(&leaves[split..], index - split),
+            merkle_root(&leaves[..split]),
+        ),
+    };
+    path.push(sibling);
+    path
+}
+
+/// # Root of tree with `size` leaves recomputed from leaf at `index` and its audit path
+///
+/// `None` if the path does not fit the tree
+pub fn root_from_path(leaf: &Hash, index: usize, size: usize, path: &[Hash]) -> Option<Hash> {
+    if index >= size {
+        return None;
+    }
+    if size == 1 {
+        return path.is_empty().then_some(*leaf);
+    }
+    let (sibling, path) = path.split_last()?;
+    let split = split_point(size);
+    match index < split {
+        true => Some(node_hash(
+            &root_from_path(leaf, index, split, path)?,
+            sibling,
+        )),
+        false => Some(node_hash(
+            sibling,
+            &root_from_path(leaf, index - split, size - split, path)?,
+        )),
+    }
+}
+
+/// Largest power of two smaller than `n`, `n` must be above 1
+fn split_point(n: usize) -> usize {
+    let mut split = 1;
+    while split << 1 < n {
+        split <<= 1;
+    }
+    split
+}
+
+/// Lowercase hex encoding of hash
+pub fn to_hex(hash: &[u8]) -> String {
+    hash.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Decode hash from hex
+pub fn from_hex(hex: &str) -> Result<Hash, String> {
+    if hex.len() != 64 || !hex.is_ascii() {
+        return Err(format!("Invalid hash '{}'", hex));
+    }
+    let mut hash = [0u8; 32];
+    for (i, byte) in hash.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)
+            .map_err(|e| format!("Invalid hash '{}': {}", hex, e))?;
+    }
+    Ok(hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Leaf inputs of the RFC 6962 reference test vectors
+    const LEAVES: [&str; 8] = [
+        "",
+        "00",
+        "10",
+        "2021",
+        "3031",
+        "40414243",
+        "5051525354555657",
+        "606162636465666768696a6b6c6d6e6f",
+    ];
+
+    /// Roots of trees of the first 1..=8 `LEAVES`
+    const ROOTS: [&str; 8] = [
+        "6e340b9cffb37a989ca544e6bb780a2c78901d3fb33738768511a30617afa01d",
+        "fac54203e7cc696cf0dfcb42c92a1d9dbaf70ad9e621f4bd8d98662f00e3c125",
+        "aeb6bcfe274b70a14fb067a5e5578264db0fa9b51af5e0ba159158f329e06e77",
+        "d37ee418976dd95753c1c73862b9398fa2a2cf9b4ff0fdfe8b30cd95209614b7",
+        "4e3bbb1f7b478dcfe71fb631631519a3bca12c9aefca1612bfce4c13a86264d4",
+        "76e67dadbcdf1e10e1b74ddc608abd2f98dfb16fbce75277b5232a127f2087ef",
+        "ddb89be403809e325750d3d263cd78929c2942b7942a34b77e122c9594a74c8c",
+        "5dc9da79a70659a9ad559cb701ded9a2ab9d823aad2f4960cfe370eff4604328",
+    ];
+
+    fn decode(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn hashes(n: usize) -> Vec<Hash> {
+        (0..n)
+            .map(|i| leaf_hash(&(i as u64).to_be_bytes()))
+            .collect()
+    }
+
+    #[test]
+    fn merkle_root_matches_rfc6962_vectors() {
+        let leaves: Vec<Hash> = LEAVES.iter().map(|l| leaf_hash(&decode(l))).collect();
+        for (n, root) in ROOTS.iter().enumerate() {
+            assert_eq!(
+                to_hex(&merkle_root(&leaves[..n + 1])),
+                *root,
+                "size {}",
+                n + 1
+            );
+        }
+        assert_eq!(
+            to_hex(&merkle_root(&[])),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn audit_path_round_trip() {
+        for size in 1..=9 {
+            let leaves = hashes(size);
+            let root = merkle_root(&leaves);
+            for (index, leaf) in leaves.iter().enumerate() {
+                let path = audit_path(&leaves, index);
+                assert_eq!(
+                    root_from_path(leaf, index, size, &path),
+                    Some(root),
+                    "leaf {} of {}",
+                    index,
+                    size
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn root_from_path_rejects_wrong_index_or_size() {
+        for size in 2..=9 {
+            let leaves = hashes(size);
+            let root = merkle_root(&leaves);
+            for (index, leaf) in leaves.iter().enumerate() {
+                let path = audit_path(&leaves, index);
+                for other in (0..size).filter(|other| *other != index) {
+                    assert_ne!(root_from_path(leaf, other, size, &path), Some(root));
+                }
+                // Sizes with the same path shape recompute the same root, the size is pinned
+                // by the sealed root, others must not fit the path
+                for other in (1..=9).filter(|other| *other != size) {
+                    if index >= other || audit_path(&hashes(other), index).len() != path.len() {
+                        assert_eq!(root_from_path(leaf, index, other, &path), None);
+                    }
+                }
+                assert_eq!(root_from_path(leaf, size, size, &path), None);
+            }
+        }
+    }
+
+    #[test]
+    fn root_from_path_rejects_wrong_path_length() {
+        let leaves = hashes(5);
+        let mut path = audit_path(&leaves, 2);
+        assert_eq!(root_from_path(&leaves[2], 2, 5, &path[1..]), None);
+        path.push(leaves[0]);
+        assert_ne!(
+            root_from_path(&leaves[2], 2, 5, &path),
+            Some(merkle_root(&leaves))
+        );
+        assert_eq!(audit_path(&leaves, 5), Vec::<Hash>::new());
+    }
+
+    #[test]
+    fn hex_round_trip() {
+        let hash = leaf_hash(b"trade");
+        assert_eq!(from_hex(&to_hex(&hash)), Ok(hash));
+        assert!(from_hex("00").is_err());
+        assert!(from_hex(&"zz".repeat(32)).is_err());
+    }
+}
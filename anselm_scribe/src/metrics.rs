@@ -0,0 +1,318 @@
+use crate::config::IssEndpoint;
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use clap::ValueEnum;
+use prometheus::core::Collector;
+use prometheus::{
+    Gauge, GaugeVec, HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry, TextEncoder,
+    TEXT_FORMAT,
+};
+use std::collections::HashMap;
+use std::sync::LazyLock;
+use std::time::Duration;
+use time::OffsetDateTime;
+use tracing::info;
+
+/// Upper bounds of latency histogram buckets in seconds
+const LATENCY_BUCKETS: [f64; 12] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+/// Labels of board series
+const BOARD: [&str; 3] = ["engine", "market", "boardid"];
+
+/// # Process wide metrics registry
+///
+/// Rendered in Prometheus text exposition format
+pub struct Metrics {
+    registry: Registry,
+    iss_requests: IntCounterVec,
+    iss_request_duration: HistogramVec,
+    iss_parse_duration: HistogramVec,
+    rows_fetched: IntCounterVec,
+    rows_inserted: IntCounterVec,
+    clickhouse_insert_duration: HistogramVec,
+    clickhouse_insert_errors: IntCounterVec,
+    clickhouse_flushes: IntCounterVec,
+    clickhouse_flushed_rows: IntCounterVec,
+    clickhouse_flushed_bytes: IntCounterVec,
+    last_ingest: GaugeVec,
+    last_trade: GaugeVec,
+    checkpoint_lag: GaugeVec,
+    scrape_timestamp: Gauge,
+}
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+/// Global metrics registry
+pub fn metrics() -> &'static Metrics {
+    &METRICS
+}
+
+/// Register collector, metric names and labels are static so registration can not fail
+fn register<C: Collector + Clone + 'static>(registry: &Registry, collector: C) -> C {
+    registry
+        .register(Box::new(collector.clone()))
+        .expect("Metric registered once with valid name and labels");
+    collector
+}
+
+/// Counter registered in `registry`
+fn counter(registry: &Registry, name: &str, help: &str, labels: &[&str]) -> IntCounterVec {
+    let counter = IntCounterVec::new(Opts::new(name, help), labels).expect("Valid counter");
+    register(registry, counter)
+}
+
+/// Gauge registered in `registry`
+fn gauge(registry: &Registry, name: &str, help: &str, labels: &[&str]) -> GaugeVec {
+    let gauge = GaugeVec::new(Opts::new(name, help), labels).expect("Valid gauge");
+    register(registry, gauge)
+}
+
+/// Latency histogram registered in `registry`
+fn histogram(registry: &Registry, name: &str, help: &str, labels: &[&str]) -> HistogramVec {
+    let opts = HistogramOpts::new(name, help).buckets(LATENCY_BUCKETS.to_vec());
+    let histogram = HistogramVec::new(opts, labels).expect("Valid histogram");
+    register(registry, histogram)
+}
+
+/// Implementation for Metrics struct
+impl Metrics {
+    /// # Metrics factory, registers every metric family
+    fn new() -> Self {
+        let registry = Registry::new();
+        Self {
+            iss_requests: counter(
+                &registry,
+                "anselm_iss_requests_total",
+                "ISS requests by endpoint and HTTP status, `error` if no response was received",
+                &["endpoint", "status"],
+            ),
+            iss_request_duration: histogram(
+                &registry,
+                "anselm_iss_request_duration_seconds",
+                "ISS request latency including reading the body",
+                &["endpoint"],
+            ),
+            iss_parse_duration: histogram(
+                &registry,
+                "anselm_iss_parse_duration_seconds",
+                "Parse latency of ISS responses",
+                &["endpoint"],
+            ),
+            rows_fetched: counter(
+                &registry,
+                "anselm_rows_fetched_total",
+                "Trades fetched from ISS per board",
+                &BOARD,
+            ),
+            rows_inserted: counter(
+                &registry,
+                "anselm_rows_inserted_total",
+                "Trades stored by market data sinks per board, batching sinks count on commit",
+                &["engine", "market", "boardid", "sink"],
+            ),
+            clickhouse_insert_duration: histogram(
+                &registry,
+                "anselm_clickhouse_insert_duration_seconds",
+                "Clickhouse insert latency per table",
+                &["table"],
+            ),
+            clickhouse_insert_errors: counter(
+                &registry,
+                "anselm_clickhouse_insert_errors_total",
+                "Failed Clickhouse inserts per table",
+                &["table"],
+            ),
+            clickhouse_flushes: counter(
+                &registry,
+                "anselm_clickhouse_flushes_total",
+                "Batches flushed by the Clickhouse trades writer",
+                &["table"],
+            ),
+            clickhouse_flushed_rows: counter(
+                &registry,
+                "anselm_clickhouse_flushed_rows_total",
+                "Rows flushed by the Clickhouse trades writer",
+                &["table"],
+            ),
+            clickhouse_flushed_bytes: counter(
+                &registry,
+                "anselm_clickhouse_flushed_bytes_total",
+                "Uncompressed bytes flushed by the Clickhouse trades writer",
+                &["table"],
+            ),
+            last_ingest: gauge(
+                &registry,
+                "anselm_last_ingest_timestamp_seconds",
+                "Unix time of the last trades page of board written to every sink",
+                &BOARD,
+            ),
+            last_trade: gauge(
+                &registry,
+                "anselm_last_trade_timestamp_seconds",
+                "Trade time of the last trade of board written to every sink",
+                &BOARD,
+            ),
+            checkpoint_lag: gauge(
+                &registry,
+                "anselm_checkpoint_lag_seconds",
+                "Time since the last trade of board written to every sink",
+                &BOARD,
+            ),
+            scrape_timestamp: register(
+                &registry,
+                Gauge::new(
+                    "anselm_metrics_scrape_timestamp_seconds",
+                    "Unix time of the scrape",
+                )
+                .expect("Valid gauge"),
+            ),
+            registry,
+        }
+    }
+
+    /// # Render metrics in Prometheus text exposition format
+    ///
+    /// Checkpoint lag is derived from last trade time of every board at scrape time
+    pub fn render(&self) -> Result<String, prometheus::Error> {
+        let now = unix_now();
+        for family in self.last_trade.collect() {
+            for metric in family.get_metric() {
+                let labels: HashMap<&str, &str> = metric
+                    .get_label()
+                    .iter()
+                    .map(|label| (label.name(), label.value()))
+                    .collect();
+                let lag = (now - metric.get_gauge().get_value()).max(0.0);
+                self.checkpoint_lag.with(&labels).set(lag);
+            }
+        }
+        self.scrape_timestamp.set(now);
+
+        TextEncoder::new().encode_to_string(&self.registry.gather())
+    }
+}
+
+/// Unix time in seconds
+fn unix_now() -> f64 {
+    OffsetDateTime::now_utc().unix_timestamp_nanos() as f64 / 1e9
+}
+
+/// Name of ISS endpoint as passed to `--iss-csv`
+fn endpoint_name(endpoint: IssEndpoint) -> String {
+    endpoint
+        .to_possible_value()
+        .map_or_else(String::new, |v| v.get_name().to_string())
+}
+
+/// Record ISS request, `status` is `None` if no response was received
+pub fn iss_request(endpoint: IssEndpoint, status: Option<u16>, elapsed: Duration) {
+    let endpoint = endpoint_name(endpoint);
+    let status = status.map_or_else(|| "error".to_string(), |s| s.to_string());
+    metrics()
+        .iss_requests
+        .with_label_values(&[endpoint.as_str(), status.as_str()])
+        .inc();
+    metrics()
+        .iss_request_duration
+        .with_label_values(&[endpoint.as_str()])
+        .observe(elapsed.as_secs_f64());
+}
+
+/// Record parsing of ISS response
+pub fn iss_parse(endpoint: IssEndpoint, elapsed: Duration) {
+    metrics()
+        .iss_parse_duration
+        .with_label_values(&[endpoint_name(endpoint).as_str()])
+        .observe(elapsed.as_secs_f64());
+}
+
+/// Record trades of board fetched from ISS
+pub fn rows_fetched(engine: &str, market: &str, boardid: &str, rows: usize) {
+    metrics()
+        .rows_fetched
+        .with_label_values(&[engine, market, boardid])
+        .inc_by(rows as u64);
+}
+
+/// Record trades of board stored by sink
+pub fn rows_inserted(engine: &str, market: &str, boardid: &str, sink: &str, rows: usize) {
+    metrics()
+        .rows_inserted
+        .with_label_values(&[engine, market, boardid, sink])
+        .inc_by(rows as u64);
+}
+
+/// # Record page of board written to every sink
+///
+/// `last_trade` is the trade time of the last written trade, `None` for empty pages
+pub fn board_ingested(
+    engine: &str,
+    market: &str,
+    boardid: &str,
+    last_trade: Option<OffsetDateTime>,
+) {
+    let labels = [engine, market, boardid];
+    metrics()
+        .last_ingest
+        .with_label_values(&labels)
+        .set(unix_now());
+    if let Some(last_trade) = last_trade {
+        metrics()
+            .last_trade
+            .with_label_values(&labels)
+            .set(last_trade.unix_timestamp() as f64);
+    }
+}
+
+/// Record Clickhouse insert into `table`
+pub fn clickhouse_insert(table: &str, elapsed: Duration, failed: bool) {
+    if failed {
+        metrics()
+            .clickhouse_insert_errors
+            .with_label_values(&[table])
+            .inc();
+    }
+    metrics()
+        .clickhouse_insert_duration
+        .with_label_values(&[table])
+        .observe(elapsed.as_secs_f64());
+}
+
+/// Record batch of `rows` and `bytes` flushed into `table`
+pub fn clickhouse_flush(table: &str, rows: u64, bytes: u64) {
+    metrics()
+        .clickhouse_flushes
+        .with_label_values(&[table])
+        .inc();
+    metrics()
+        .clickhouse_flushed_rows
+        .with_label_values(&[table])
+        .inc_by(rows);
+    metrics()
+        .clickhouse_flushed_bytes
+        .with_label_values(&[table])
+        .inc_by(bytes);
+}
+
+/// Metrics handler
+async fn metrics_handler() -> Response {
+    match metrics().render() {
+        Ok(text) => ([(header::CONTENT_TYPE, TEXT_FORMAT)], text).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+/// # Serve `/metrics` in the background
+///
+/// Fails if `listen` can not be bound
+pub async fn spawn_server(listen: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let listener = tokio::net::TcpListener::bind(listen).await?;
+    info!(listen = %listener.local_addr()?, "Metrics listening");
+    let router = Router::new().route("/metrics", get(metrics_handler));
+    tokio::spawn(async move { axum::serve(listener, router).await });
+    Ok(())
+}
@@ -0,0 +1,197 @@
+/// Line separating statements of a migration
+pub const STATEMENT_BREAK: &str = "--> statement-breakpoint";
+
+/// # Clickhouse schema migration
+///
+/// Migrations are embedded into the binary and applied in `version` order. `{db}` in
+/// migration SQL is replaced with the configured database name as a quoted identifier.
+///
+/// Clickhouse executes one statement per query, so migrations with several statements
+/// separate them with `STATEMENT_BREAK` lines. Statements are never split on `;`, which may
+/// occur in string literals and comments.
+pub struct Migration {
+    pub version: u32,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+/// Implementation for Migration struct
+impl Migration {
+    /// Split migration into statements at `STATEMENT_BREAK` lines, parts holding only
+    /// comments are skipped
+    pub fn statements(&self, db: &str) -> Vec<String> {
+        let mut statements: Vec<Vec<&str>> = vec![Vec::new()];
+        for line in self.sql.lines() {
+            match line.trim() == STATEMENT_BREAK {
+                true => statements.push(Vec::new()),
+                false => statements.last_mut().unwrap().push(line),
+            }
+        }
+
+        statements
+            .into_iter()
+            .filter(|lines| {
+                lines.iter().any(|line| {
+                    let line = line.trim();
+                    !line.is_empty() && !line.starts_with("--")
+                })
+            })
+            .map(|lines| {
+                let statement = lines.join("\n");
+                // Trailing semicolon ends the statement, it is not part of it
+                let statement = statement.trim().trim_end_matches(';').trim_end();
+                statement.replace("{db}", &quote_identifier(db))
+            })
+            .collect()
+    }
+}
+
+/// Backquoted Clickhouse identifier, backslashes and backquotes in `name` are escaped
+pub fn quote_identifier(name: &str) -> String {
+    format!("`{}`", name.replace('\\', "\\\\").replace('`', "\\`"))
+}
+
+/// All schema migrations ordered by version
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "create_engines",
+        sql: include_str!("../migrations/0001_create_engines.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "create_markets",
+        sql: include_str!("../migrations/0002_create_markets.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "create_boards",
+        sql: include_str!("../migrations/0003_create_boards.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "create_trades",
+        sql: include_str!("../migrations/0004_create_trades.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "trades_typed_columns",
+        sql: include_str!("../migrations/0005_trades_typed_columns.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "reference_history",
+        sql: include_str!("../migrations/0006_reference_history.sql"),
+    },
+    Migration {
+        version: 7,
+        name: "create_history",
+        sql: include_str!("../migrations/0007_create_history.sql"),
+    },
+    Migration {
+        version: 8,
+        name: "create_data_gaps",
+        sql: include_str!("../migrations/0008_create_data_gaps.sql"),
+    },
+    Migration {
+        version: 9,
+        name: "create_reconciliation",
+        sql: include_str!("../migrations/0009_create_reconciliation.sql"),
+    },
+    Migration {
+        version: 10,
+        name: "create_ingest_log",
+        sql: include_str!("../migrations/0010_create_ingest_log.sql"),
+    },
+    Migration {
+        version: 11,
+        name: "data_gaps_day_key",
+        sql: include_str!("../migrations/0011_data_gaps_day_key.sql"),
+    },
+    Migration {
+        version: 12,
+        name: "ingest_log_errors",
+        sql: include_str!("../migrations/0012_ingest_log_errors.sql"),
+    },
+];
+
+/// Schema version expected by this binary
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map_or(0, |m| m.version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn migration(sql: &'static str) -> Migration {
+        Migration {
+            version: 1,
+            name: "test",
+            sql,
+        }
+    }
+
+    #[test]
+    fn statements_split_at_breakpoints() {
+        let sql = "-- Leading comment\n\
+                   CREATE TABLE {db}.a(x UInt8) ENGINE = Memory;\n\
+                   --> statement-breakpoint\n\
+                   -- Only a comment\n\
+                   --> statement-breakpoint\n\
+                   CREATE TABLE {db}.b(x UInt8)\n\
+                   ENGINE = Memory;\n";
+        assert_eq!(
+            migration(sql).statements("anselm"),
+            vec![
+                "-- Leading comment\nCREATE TABLE `anselm`.a(x UInt8) ENGINE = Memory",
+                "CREATE TABLE `anselm`.b(x UInt8)\nENGINE = Memory",
+            ]
+        );
+    }
+
+    #[test]
+    fn statements_trim_trailing_whitespace() {
+        let sql = "ALTER TABLE {db}.a DROP COLUMN x;  \t\n\n  --> statement-breakpoint  \n\
+                   ALTER TABLE {db}.a DROP COLUMN y  ;\n\n\n";
+        assert_eq!(
+            migration(sql).statements("anselm"),
+            vec![
+                "ALTER TABLE `anselm`.a DROP COLUMN x",
+                "ALTER TABLE `anselm`.a DROP COLUMN y",
+            ]
+        );
+    }
+
+    #[test]
+    fn statements_keep_semicolons_in_literals() {
+        let sql = "ALTER TABLE {db}.a COMMENT COLUMN x 'first; second';\n\
+                   --> statement-breakpoint\n\
+                   SELECT ';'";
+        assert_eq!(
+            migration(sql).statements("anselm"),
+            vec![
+                "ALTER TABLE `anselm`.a COMMENT COLUMN x 'first; second'",
+                "SELECT ';'",
+            ]
+        );
+    }
+
+    #[test]
+    fn statements_quote_database_name() {
+        let sql = "DROP TABLE {db}.a";
+        assert_eq!(
+            migration(sql).statements("odd`name\\"),
+            vec!["DROP TABLE `odd\\`name\\\\`.a"]
+        );
+    }
+
+    #[test]
+    fn embedded_migrations_are_ordered() {
+        for (i, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version, i as u32 + 1);
+            assert!(!migration.statements("anselm").is_empty());
+        }
+        assert_eq!(latest_version(), MIGRATIONS.len() as u32);
+    }
+}
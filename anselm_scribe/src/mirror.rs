@@ -0,0 +1,1074 @@
+use crate::config::{Config, DataSource};
+use crate::db::ClickhouseDatabase;
+use crate::import::{collect_files, load_trades_from_file};
+use crate::iss::IssFormat;
+use crate::models::{
+    Board, Engine, Market, Trade, ISS_DATE, ISS_DATETIME, MOSCOW_OFFSET, TRADES_PAGE_SIZE,
+};
+use crate::reference::{BoardVersion, EngineVersion, MarketVersion};
+use async_trait::async_trait;
+use axum::extract::{Path, Query, State};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use encoding_rs::WINDOWS_1251;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use time::format_description::FormatItem;
+use time::macros::format_description;
+use time::{Date, Duration, OffsetDateTime, Time};
+use tracing::{error, info};
+
+/// Number of candles ISS returns per page
+pub const CANDLES_PAGE_SIZE: u64 = 500;
+
+/// Number of history records ISS returns per page
+pub const HISTORY_PAGE_SIZE: u64 = 100;
+
+/// ISS time format
+const ISS_TIME: &[FormatItem<'static>] = format_description!("[hour]:[minute]:[second]");
+
+pub type MirrorResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// # Market data store served by the ISS mirror
+#[async_trait]
+pub trait MirrorStore: Send + Sync {
+    async fn engines(&self) -> MirrorResult<Vec<Engine>>;
+
+    async fn markets(&self, engine: &str) -> MirrorResult<Vec<Market>>;
+
+    async fn boards(&self, engine: &str, market: &str) -> MirrorResult<Vec<Board>>;
+
+    /// Board trades ordered by trade id, skipping `start` trades, trades of all securities if
+    /// `secid` is `None`
+    async fn trades_page(
+        &self,
+        engine: &str,
+        market: &str,
+        boardid: &str,
+        secid: Option<&str>,
+        start: u64,
+        limit: u64,
+    ) -> MirrorResult<Vec<Trade>>;
+
+    /// Board trades with trade id above `after` ordered by trade id, trades of all securities
+    /// if `secid` is `None`
+    async fn trades_after(
+        &self,
+        engine: &str,
+        market: &str,
+        boardid: &str,
+        secid: Option<&str>,
+        after: u64,
+        limit: u64,
+    ) -> MirrorResult<Vec<Trade>>;
+
+    /// Board trades within `[from, till)` ordered by trade id
+    async fn trades_between(
+        &self,
+        engine: &str,
+        market: &str,
+        boardid: &str,
+        secid: Option<&str>,
+        from: OffsetDateTime,
+        till: OffsetDateTime,
+    ) -> MirrorResult<Vec<Trade>>;
+
+    /// Time of the last board trade
+    async fn last_trade_time(
+        &self,
+        engine: &str,
+        market: &str,
+        boardid: &str,
+    ) -> MirrorResult<Option<OffsetDateTime>>;
+}
+
+#[async_trait]
+impl MirrorStore for ClickhouseDatabase {
+    async fn engines(&self) -> MirrorResult<Vec<Engine>> {
+        let mut engines: Vec<Engine> = self
+            .current_versions::<EngineVersion>()
+            .await?
+            .into_iter()
+            .map(|e| Engine {
+                id: e.id,
+                name: e.name,
+                title: e.title,
+            })
+            .collect();
+        engines.sort_by_key(|e| e.id);
+        Ok(engines)
+    }
+
+    async fn markets(&self, engine: &str) -> MirrorResult<Vec<Market>> {
+        let mut markets: Vec<Market> = self
+            .current_versions::<MarketVersion>()
+            .await?
+            .into_iter()
+            .filter(|m| m.engine == engine)
+            .map(|m| Market {
+                engine: m.engine,
+                id: m.id,
+                name: m.name,
+                title: m.title,
+            })
+            .collect();
+        markets.sort_by_key(|m| m.id);
+        Ok(markets)
+    }
+
+    async fn boards(&self, engine: &str, market: &str) -> MirrorResult<Vec<Board>> {
+        let mut boards: Vec<Board> = self
+            .current_versions::<BoardVersion>()
+            .await?
+            .into_iter()
+            .filter(|b| b.engine == engine && b.market == market)
+            .map(|b| Board {
+                engine: b.engine,
+                market: b.market,
+                id: b.id,
+                board_group_id: b.board_group_id,
+                boardid: b.boardid,
+                title: b.title,
+                is_traded: b.is_traded,
+            })
+            .collect();
+        boards.sort_by_key(|b| b.id);
+        Ok(boards)
+    }
+
+    async fn trades_page(
+        &self,
+        engine: &str,
+        market: &str,
+        boardid: &str,
+        secid: Option<&str>,
+        start: u64,
+        limit: u64,
+    ) -> MirrorResult<Vec<Trade>> {
+        Ok(self
+            .fetch_trades_page(engine, market, boardid, secid, start, limit)
+            .await?)
+    }
+
+    async fn trades_after(
+        &self,
+        engine: &str,
+        market: &str,
+        boardid: &str,
+        secid: Option<&str>,
+        after: u64,
+        limit: u64,
+    ) -> MirrorResult<Vec<Trade>> {
+        Ok(self
+            .fetch_trades_after(engine, market, boardid, secid, after, limit)
+            .await?)
+    }
+
+    async fn trades_between(
+        &self,
+        engine: &str,
+        market: &str,
+        boardid: &str,
+        secid: Option<&str>,
+        from: OffsetDateTime,
+        till: OffsetDateTime,
+    ) -> MirrorResult<Vec<Trade>> {
+        Ok(self
+            .fetch_trades_between(engine, market, boardid, secid, from, till)
+            .await?)
+    }
+
+    async fn last_trade_time(
+        &self,
+        engine: &str,
+        market: &str,
+        boardid: &str,
+    ) -> MirrorResult<Option<OffsetDateTime>> {
+        Ok(ClickhouseDatabase::last_trade_time(self, engine, market, boardid).await?)
+    }
+}
+
+/// # Open market data store of source
+///
+/// Disk store loads trades files from `md_path`
+pub async fn open_store(
+    conf: &Config,
+    source: DataSource,
+) -> Result<Arc<dyn MirrorStore>, Box<dyn std::error::Error>> {
+    Ok(match source {
+        DataSource::Clickhouse => Arc::new(ClickhouseDatabase::new(conf)),
+        DataSource::Disk => Arc::new(DiskStore::load(&conf.md_path).await?),
+    })
+}
+
+/// # Market data files store
+///
+/// Loads trades files saved with `--md-disk` into memory. Files hold no reference data,
+/// engines, markets and boards are derived from trades.
+pub struct DiskStore {
+    boards: BTreeMap<(String, String, String), Vec<Trade>>,
+}
+
+/// Implementation for DiskStore struct
+impl DiskStore {
+    /// # Load all market data files from directory
+    pub async fn load(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let files = collect_files(&[path.to_string()]).await?;
+        let mut boards: BTreeMap<(String, String, String), Vec<Trade>> = BTreeMap::new();
+        for file_path in &files {
+            for trade in load_trades_from_file(file_path).await? {
+                boards
+                    .entry((
+                        trade.engine.clone(),
+                        trade.market.clone(),
+                        trade.boardid.clone(),
+                    ))
+                    .or_default()
+                    .push(trade);
+            }
+        }
+
+        let mut total = 0;
+        for trades in boards.values_mut() {
+            trades.sort_by_key(|t| t.tradeid);
+            trades.dedup_by_key(|t| t.tradeid);
+            total += trades.len();
+        }
+        info!(
+            trades = total,
+            boards = boards.len(),
+            files = files.len(),
+            "Trades loaded from files"
+        );
+        Ok(Self { boards })
+    }
+
+    fn board_trades(&self, engine: &str, market: &str, boardid: &str) -> &[Trade] {
+        self.boards
+            .get(&(engine.to_string(), market.to_string(), boardid.to_string()))
+            .map_or(&[], |trades| trades.as_slice())
+    }
+}
+
+#[async_trait]
+impl MirrorStore for DiskStore {
+    async fn engines(&self) -> MirrorResult<Vec<Engine>> {
+        let mut names: Vec<&String> = self.boards.keys().map(|(e, _, _)| e).collect();
+        names.dedup();
+        Ok(names
+            .into_iter()
+            .enumerate()
+            .map(|(i, name)| Engine {
+                id: i as i32 + 1,
+                name: name.clone(),
+                title: name.clone(),
+            })
+            .collect())
+    }
+
+    async fn markets(&self, engine: &str) -> MirrorResult<Vec<Market>> {
+        let mut names: Vec<&String> = self
+            .boards
+            .keys()
+            .filter(|(e, _, _)| e == engine)
+            .map(|(_, m, _)| m)
+            .collect();
+        names.dedup();
+        Ok(names
+            .into_iter()
+            .enumerate()
+            .map(|(i, name)| Market {
+                engine: engine.to_string(),
+                id: i as i32 + 1,
+                name: name.clone(),
+                title: name.clone(),
+            })
+            .collect())
+    }
+
+    async fn boards(&self, engine: &str, market: &str) -> MirrorResult<Vec<Board>> {
+        Ok(self
+            .boards
+            .keys()
+            .filter(|(e, m, _)| e == engine && m == market)
+            .enumerate()
+            .map(|(i, (_, _, boardid))| Board {
+                engine: engine.to_string(),
+                market: market.to_string(),
+                id: i as i32 + 1,
+                board_group_id: 0,
+                boardid: boardid.clone(),
+                title: boardid.clone(),
+                is_traded: true,
+            })
+            .collect())
+    }
+
+    async fn trades_page(
+        &self,
+        engine: &str,
+        market: &str,
+        boardid: &str,
+        secid: Option<&str>,
+        start: u64,
+        limit: u64,
+    ) -> MirrorResult<Vec<Trade>> {
+        Ok(self
+            .board_trades(engine, market, boardid)
+            .iter()
+            .filter(|t| secid.is_none_or(|secid| t.secid == secid))
+            .skip(start as usize)
+            .take(limit as usize)
+            .cloned()
+            .collect())
+    }
+
+    async fn trades_after(
+        &self,
+        engine: &str,
+        market: &str,
+        boardid: &str,
+        secid: Option<&str>,
+        after: u64,
+        limit: u64,
+    ) -> MirrorResult<Vec<Trade>> {
+        Ok(self
+            .board_trades(engine, market, boardid)
+            .iter()
+            .filter(|t| t.tradeid as u64 > after)
+            .filter(|t| secid.is_none_or(|secid| t.secid == secid))
+            .take(limit as usize)
+            .cloned()
+            .collect())
+    }
+
+    async fn trades_between(
+        &self,
+        engine: &str,
+        market: &str,
+        boardid: &str,
+        secid: Option<&str>,
+        from: OffsetDateTime,
+        till: OffsetDateTime,
+    ) -> MirrorResult<Vec<Trade>> {
+        Ok(self
+            .board_trades(engine, market, boardid)
+            .iter()
+            .filter(|t| secid.is_none_or(|secid| t.secid == secid))
+            .filter(|t| t.tradetime >= from && t.tradetime < till)
+            .cloned()
+            .collect())
+    }
+
+    async fn last_trade_time(
+        &self,
+        engine: &str,
+        market: &str,
+        boardid: &str,
+    ) -> MirrorResult<Option<OffsetDateTime>> {
+        Ok(self
+            .board_trades(engine, market, boardid)
+            .iter()
+            .map(|t| t.tradetime)
+            .max())
+    }
+}
+
+/// # ISS response block
+pub struct Block {
+    pub name: String,
+    /// Column names and ISS types
+    pub columns: Vec<(&'static str, &'static str)>,
+    pub data: Vec<Vec<Value>>,
+}
+
+/// Implementation for Block struct
+impl Block {
+    /// `>name<.cursor` block ISS adds to paginated responses
+    pub fn cursor(name: &str, index: u64, total: u64, page_size: u64) -> Self {
+        Block {
+            name: format!("{}.cursor", name),
+            columns: vec![
+                ("INDEX", "int64"),
+                ("TOTAL", "int64"),
+                ("PAGESIZE", "int64"),
+            ],
+            data: vec![vec![json!(index), json!(total), json!(page_size)]],
+        }
+    }
+}
+
+/// Render blocks as ISS response of format
+pub fn render(blocks: &[Block], format: IssFormat) -> Response {
+    match format {
+        IssFormat::Json => {
+            let resp: serde_json::Map<String, Value> = blocks
+                .iter()
+                .map(|block| {
+                    let metadata: serde_json::Map<String, Value> = block
+                        .columns
+                        .iter()
+                        .map(|(name, kind)| (name.to_string(), json!({ "type": kind })))
+                        .collect();
+                    let columns: Vec<&str> = block.columns.iter().map(|(name, _)| *name).collect();
+                    (
+                        block.name.clone(),
+                        json!({ "metadata": metadata, "columns": columns, "data": block.data }),
+                    )
+                })
+                .collect();
+            (
+                [(header::CONTENT_TYPE, "application/json; charset=utf-8")],
+                Value::Object(resp).to_string(),
+            )
+                .into_response()
+        }
+        IssFormat::Csv => {
+            let mut text = String::new();
+            for block in blocks {
+                text.push_str(&block.name);
+                text.push_str("\n\n");
+                let columns: Vec<&str> = block.columns.iter().map(|(name, _)| *name).collect();
+                text.push_str(&columns.join(";"));
+                text.push('\n');
+                for row in &block.data {
+                    let cells: Vec<String> = row.iter().map(csv_cell).collect();
+                    text.push_str(&cells.join(";"));
+                    text.push('\n');
+                }
+                text.push('\n');
+            }
+            let (bytes, _, _) = WINDOWS_1251.encode(&text);
+            (
+                [(header::CONTENT_TYPE, "text/csv; charset=windows-1251")],
+                bytes.into_owned(),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Render single CSV cell, cells with separators are quoted
+fn csv_cell(value: &Value) -> String {
+    let cell = match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    if cell.contains([';', '"', '\n']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell
+    }
+}
+
+/// # ISS mirror error response
+pub enum MirrorError {
+    NotFound(String),
+    BadRequest(String),
+    Store(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl IntoResponse for MirrorError {
+    fn into_response(self) -> Response {
+        match self {
+            MirrorError::NotFound(what) => (StatusCode::NOT_FOUND, what).into_response(),
+            MirrorError::BadRequest(what) => (StatusCode::BAD_REQUEST, what).into_response(),
+            MirrorError::Store(e) => {
+                error!(error = %e, "Store request failed");
+                (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+            }
+        }
+    }
+}
+
+impl From<Box<dyn std::error::Error + Send + Sync>> for MirrorError {
+    fn from(e: Box<dyn std::error::Error + Send + Sync>) -> Self {
+        MirrorError::Store(e)
+    }
+}
+
+/// ISS query parameters
+#[derive(Debug, Default, Deserialize)]
+pub struct IssQuery {
+    pub start: Option<u64>,
+    pub limit: Option<u64>,
+    pub from: Option<String>,
+    pub till: Option<String>,
+    pub interval: Option<u32>,
+    pub date: Option<String>,
+    pub tradeno: Option<u64>,
+    pub next_trade: Option<u8>,
+}
+
+type MirrorState = Arc<dyn MirrorStore>;
+
+/// Split `>resource<.>format<` path segment, only `resource` is accepted
+fn resource_format(segment: &str, resource: &str) -> Result<IssFormat, MirrorError> {
+    match segment.split_once('.') {
+        Some((name, "json")) if name == resource => Ok(IssFormat::Json),
+        Some((name, "csv")) if name == resource => Ok(IssFormat::Csv),
+        _ => Err(MirrorError::NotFound(format!(
+            "Unknown resource '{}'",
+            segment
+        ))),
+    }
+}
+
+/// Parse ISS date parameter
+fn parse_date(name: &str, value: &str) -> Result<Date, MirrorError> {
+    Date::parse(value, ISS_DATE)
+        .map_err(|e| MirrorError::BadRequest(format!("Invalid {} '{}': {}", name, value, e)))
+}
+
+/// Start of Moscow day
+fn day_start(date: Date) -> OffsetDateTime {
+    date.with_time(Time::MIDNIGHT).assume_offset(MOSCOW_OFFSET)
+}
+
+/// Page limit capped at ISS page size
+fn page_limit(query: &IssQuery, page_size: u64) -> u64 {
+    query.limit.unwrap_or(page_size).min(page_size)
+}
+
+/// Decimal as JSON number
+fn decimal(value: Decimal) -> Value {
+    json!(value.to_f64())
+}
+
+/// Date of the last board trade, today if board has no trades
+async fn last_trade_date(
+    store: &MirrorState,
+    engine: &str,
+    market: &str,
+    board: &str,
+) -> Result<Date, MirrorError> {
+    Ok(store
+        .last_trade_time(engine, market, board)
+        .await?
+        .unwrap_or_else(OffsetDateTime::now_utc)
+        .to_offset(MOSCOW_OFFSET)
+        .date())
+}
+
+/// `/iss/engines.>format<`
+async fn engines(
+    State(store): State<MirrorState>,
+    Path(resource): Path<String>,
+) -> Result<Response, MirrorError> {
+    let format = resource_format(&resource, "engines")?;
+    let data = store
+        .engines()
+        .await?
+        .into_iter()
+        .map(|e| vec![json!(e.id), json!(e.name), json!(e.title)])
+        .collect();
+    Ok(render(
+        &[Block {
+            name: "engines".into(),
+            columns: vec![("id", "int32"), ("name", "string"), ("title", "string")],
+            data,
+        }],
+        format,
+    ))
+}
+
+/// `/iss/engines/>engine</markets.>format<`
+async fn markets(
+    State(store): State<MirrorState>,
+    Path((engine, resource)): Path<(String, String)>,
+) -> Result<Response, MirrorError> {
+    let format = resource_format(&resource, "markets")?;
+    let data = store
+        .markets(&engine)
+        .await?
+        .into_iter()
+        .map(|m| vec![json!(m.id), json!(m.name), json!(m.title)])
+        .collect();
+    Ok(render(
+        &[Block {
+            name: "markets".into(),
+            columns: vec![("id", "int32"), ("NAME", "string"), ("title", "string")],
+            data,
+        }],
+        format,
+    ))
+}
+
+/// `/iss/engines/>engine</markets/>market</boards.>format<`
+async fn boards(
+    State(store): State<MirrorState>,
+    Path((engine, market, resource)): Path<(String, String, String)>,
+) -> Result<Response, MirrorError> {
+    let format = resource_format(&resource, "boards")?;
+    let data = store
+        .boards(&engine, &market)
+        .await?
+        .into_iter()
+        .map(|b| {
+            vec![
+                json!(b.id),
+                json!(b.board_group_id),
+                json!(b.boardid),
+                json!(b.title),
+                json!(b.is_traded as i32),
+            ]
+        })
+        .collect();
+    Ok(render(
+        &[Block {
+            name: "boards".into(),
+            columns: vec![
+                ("id", "int32"),
+                ("board_group_id", "int32"),
+                ("boardid", "string"),
+                ("title", "string"),
+                ("is_traded", "int32"),
+            ],
+            data,
+        }],
+        format,
+    ))
+}
+
+/// `/iss/engines/>engine</markets/>market</boards/>board</trades.>format<?start=&limit=`
+///
+/// `tradeno` selects trades starting from trade number instead of `start`, trades after it
+/// with `next_trade=1`. Also serves board `securities`.
+async fn trades(
+    State(store): State<MirrorState>,
+    Path((engine, market, board, resource)): Path<(String, String, String, String)>,
+    Query(query): Query<IssQuery>,
+) -> Result<Response, MirrorError> {
+    if resource.starts_with("securities.") {
+        return board_securities(store, engine, market, board, resource).await;
+    }
+
+    let format = resource_format(&resource, "trades")?;
+    let trades = fetch_trades(&store, &engine, &market, &board, None, &query).await?;
+    Ok(render_trades(&trades, format))
+}
+
+/// `/iss/engines/>engine</markets/>market</boards/>board</securities.>format<`
+///
+/// Securities traded on the date of the last board trade. Stores hold no security reference
+/// data, short names are secids and lot sizes are restored from trade values.
+async fn board_securities(
+    store: MirrorState,
+    engine: String,
+    market: String,
+    board: String,
+    resource: String,
+) -> Result<Response, MirrorError> {
+    let format = resource_format(&resource, "securities")?;
+    let date = last_trade_date(&store, &engine, &market, &board).await?;
+    let trades = store
+        .trades_between(
+            &engine,
+            &market,
+            &board,
+            None,
+            day_start(date),
+            day_start(date) + Duration::days(1),
+        )
+        .await?;
+
+    // Last trade per security
+    let securities: BTreeMap<&str, &Trade> = trades.iter().map(|t| (t.secid.as_str(), t)).collect();
+    let data = securities
+        .iter()
+        .map(|(secid, trade)| {
+            vec![
+                json!(secid),
+                json!(board),
+                json!(secid),
+                json!(trade.decimals),
+                json!(lot_size(trade)),
+            ]
+        })
+        .collect();
+    Ok(render(
+        &[Block {
+            name: "securities".into(),
+            columns: vec![
+                ("SECID", "string"),
+                ("BOARDID", "string"),
+                ("SHORTNAME", "string"),
+                ("DECIMALS", "int32"),
+                ("LOTSIZE", "int32"),
+            ],
+            data,
+        }],
+        format,
+    ))
+}
+
+/// Lot size restored from a trade, ISS trade value is price * quantity * lot size
+fn lot_size(trade: &Trade) -> u32 {
+    let lots = trade.price * Decimal::from(trade.quantity);
+    match lots.is_zero() {
+        true => 1,
+        false => (trade.value / lots).round().to_u32().unwrap_or(1).max(1),
+    }
+}
+
+/// `/iss/engines/>engine</markets/>market</boards/>board</securities/>secid</>resource<`
+///
+/// Serves security `trades` and `candles`
+async fn security_resource(
+    State(store): State<MirrorState>,
+    Path((engine, market, board, secid, resource)): Path<(String, String, String, String, String)>,
+    Query(query): Query<IssQuery>,
+) -> Result<Response, MirrorError> {
+    if !resource.starts_with("trades.") {
+        return security_candles(store, engine, market, board, secid, resource, query).await;
+    }
+
+    let format = resource_format(&resource, "trades")?;
+    let trades = fetch_trades(&store, &engine, &market, &board, Some(&secid), &query).await?;
+    Ok(render_trades(&trades, format))
+}
+
+/// Page of trades selected by `start` or `tradeno`
+async fn fetch_trades(
+    store: &MirrorState,
+    engine: &str,
+    market: &str,
+    board: &str,
+    secid: Option<&str>,
+    query: &IssQuery,
+) -> Result<Vec<Trade>, MirrorError> {
+    let limit = page_limit(query, TRADES_PAGE_SIZE as u64);
+    let trades = match query.tradeno {
+        Some(tradeno) => {
+            let after = match query.next_trade {
+                Some(1) => tradeno,
+                _ => tradeno.saturating_sub(1),
+            };
+            store
+                .trades_after(engine, market, board, secid, after, limit)
+                .await?
+        }
+        None => {
+            store
+                .trades_page(
+                    engine,
+                    market,
+                    board,
+                    secid,
+                    query.start.unwrap_or(0),
+                    limit,
+                )
+                .await?
+        }
+    };
+    Ok(trades)
+}
+
+/// Render trades as ISS `trades` block
+fn render_trades(trades: &[Trade], format: IssFormat) -> Response {
+    let data = trades
+        .iter()
+        .map(|t| {
+            let time = t.tradetime.to_offset(MOSCOW_OFFSET);
+            vec![
+                json!(t.tradeid),
+                json!(time.format(ISS_TIME).unwrap_or_default()),
+                json!(t.boardid),
+                json!(t.secid),
+                decimal(t.price),
+                json!(t.quantity),
+                decimal(t.value),
+                json!("N"),
+                json!(time.hour() as i64 * 100 + time.minute() as i64),
+                json!(time.format(ISS_DATETIME).unwrap_or_default()),
+                json!(t.buysell.as_str()),
+                json!(t.decimals),
+                json!(1),
+            ]
+        })
+        .collect();
+    render(
+        &[Block {
+            name: "trades".into(),
+            columns: vec![
+                ("TRADENO", "int64"),
+                ("TRADETIME", "time"),
+                ("BOARDID", "string"),
+                ("SECID", "string"),
+                ("PRICE", "double"),
+                ("QUANTITY", "int32"),
+                ("VALUE", "double"),
+                ("PERIOD", "string"),
+                ("TRADETIME_GRP", "int32"),
+                ("SYSTIME", "datetime"),
+                ("BUYSELL", "string"),
+                ("DECIMALS", "int32"),
+                ("TRADINGSESSION", "int32"),
+            ],
+            data,
+        }],
+        format,
+    )
+}
+
+/// Candle aggregated from trades
+struct Candle {
+    open: Decimal,
+    close: Decimal,
+    high: Decimal,
+    low: Decimal,
+    value: Decimal,
+    volume: i64,
+    begin: OffsetDateTime,
+    end: OffsetDateTime,
+}
+
+/// Aggregate trades ordered by trade id into candles of `interval`
+fn candles(trades: &[Trade], interval: Duration, daily: bool) -> Vec<Candle> {
+    let mut candles: BTreeMap<OffsetDateTime, Candle> = BTreeMap::new();
+    for trade in trades {
+        let time = trade.tradetime.to_offset(MOSCOW_OFFSET);
+        let begin = if daily {
+            day_start(time.date())
+        } else {
+            let since_day = time - day_start(time.date());
+            let buckets = since_day.whole_seconds() / interval.whole_seconds();
+            day_start(time.date()) + interval * buckets as i32
+        };
+        let candle = candles.entry(begin).or_insert(Candle {
+            open: trade.price,
+            close: trade.price,
+            high: trade.price,
+            low: trade.price,
+            value: Decimal::ZERO,
+            volume: 0,
+            begin,
+            end: time,
+        });
+        candle.close = trade.price;
+        candle.high = candle.high.max(trade.price);
+        candle.low = candle.low.min(trade.price);
+        candle.value += trade.value;
+        candle.volume += trade.quantity as i64;
+        candle.end = candle.end.max(time);
+    }
+    candles.into_values().collect()
+}
+
+/// `/iss/engines/>engine</markets/>market</boards/>board</securities/>secid</candles.>format<`
+///
+/// Candles are aggregated from stored trades, supported intervals are 1, 10 and 60 minutes
+/// and 24 for daily candles
+async fn security_candles(
+    store: MirrorState,
+    engine: String,
+    market: String,
+    board: String,
+    secid: String,
+    resource: String,
+    query: IssQuery,
+) -> Result<Response, MirrorError> {
+    let format = resource_format(&resource, "candles")?;
+    let (interval, daily) = match query.interval.unwrap_or(10) {
+        i @ (1 | 10 | 60) => (Duration::minutes(i as i64), false),
+        24 => (Duration::days(1), true),
+        i => {
+            return Err(MirrorError::BadRequest(format!(
+                "Unsupported interval {}",
+                i
+            )))
+        }
+    };
+    let from = match query.from {
+        Some(ref from) => parse_date("from", from)?,
+        None => last_trade_date(&store, &engine, &market, &board).await?,
+    };
+    let till = match query.till {
+        Some(ref till) => parse_date("till", till)?,
+        None => from,
+    };
+
+    let trades = store
+        .trades_between(
+            &engine,
+            &market,
+            &board,
+            Some(&secid),
+            day_start(from),
+            day_start(till) + Duration::days(1),
+        )
+        .await?;
+
+    let data = candles(&trades, interval, daily)
+        .into_iter()
+        .skip(query.start.unwrap_or(0) as usize)
+        .take(page_limit(&query, CANDLES_PAGE_SIZE) as usize)
+        .map(|c| {
+            let end = if daily {
+                c.begin + Duration::days(1) - Duration::SECOND
+            } else {
+                c.end
+            };
+            vec![
+                decimal(c.open),
+                decimal(c.close),
+                decimal(c.high),
+                decimal(c.low),
+                decimal(c.value),
+                json!(c.volume),
+                json!(c.begin.format(ISS_DATETIME).unwrap_or_default()),
+                json!(end.format(ISS_DATETIME).unwrap_or_default()),
+            ]
+        })
+        .collect();
+    Ok(render(
+        &[Block {
+            name: "candles".into(),
+            columns: vec![
+                ("open", "double"),
+                ("close", "double"),
+                ("high", "double"),
+                ("low", "double"),
+                ("value", "double"),
+                ("volume", "double"),
+                ("begin", "datetime"),
+                ("end", "datetime"),
+            ],
+            data,
+        }],
+        format,
+    ))
+}
+
+/// `/iss/history/engines/>engine</markets/>market</boards/>board</securities.>format<?date=`
+///
+/// Daily results per security are aggregated from stored trades of the date, volume is in
+/// securities as lot sizes are restored from trade values
+async fn board_history(
+    State(store): State<MirrorState>,
+    Path((engine, market, board, resource)): Path<(String, String, String, String)>,
+    Query(query): Query<IssQuery>,
+) -> Result<Response, MirrorError> {
+    let format = resource_format(&resource, "securities")?;
+    let date = match query.date {
+        Some(ref date) => parse_date("date", date)?,
+        None => last_trade_date(&store, &engine, &market, &board).await?,
+    };
+
+    let trades = store
+        .trades_between(
+            &engine,
+            &market,
+            &board,
+            None,
+            day_start(date),
+            day_start(date) + Duration::days(1),
+        )
+        .await?;
+
+    // Daily candle per security
+    let mut securities: BTreeMap<&str, Vec<Trade>> = BTreeMap::new();
+    for trade in &trades {
+        securities
+            .entry(trade.secid.as_str())
+            .or_default()
+            .push(trade.clone());
+    }
+    let total = securities.len() as u64;
+    let start = query.start.unwrap_or(0);
+    let date_str = date.format(ISS_DATE).unwrap_or_default();
+
+    let data = securities
+        .iter()
+        .skip(start as usize)
+        .take(page_limit(&query, HISTORY_PAGE_SIZE) as usize)
+        .filter_map(|(secid, trades)| {
+            let day = candles(trades, Duration::days(1), true).pop()?;
+            let decimals = trades.last().map_or(0, |t| t.decimals);
+            let volume = day.volume * trades.last().map_or(1, lot_size) as i64;
+            let waprice = match volume {
+                0 => Decimal::ZERO,
+                volume => (day.value / Decimal::from(volume)).round_dp(decimals as u32),
+            };
+            Some(vec![
+                json!(board),
+                json!(date_str),
+                json!(secid),
+                json!(secid),
+                json!(trades.len()),
+                decimal(day.value),
+                decimal(day.open),
+                decimal(day.low),
+                decimal(day.high),
+                decimal(day.close),
+                decimal(waprice),
+                decimal(day.close),
+                json!(volume),
+            ])
+        })
+        .collect();
+    Ok(render(
+        &[
+            Block {
+                name: "history".into(),
+                columns: vec![
+                    ("BOARDID", "string"),
+                    ("TRADEDATE", "date"),
+                    ("SHORTNAME", "string"),
+                    ("SECID", "string"),
+                    ("NUMTRADES", "double"),
+                    ("VALUE", "double"),
+                    ("OPEN", "double"),
+                    ("LOW", "double"),
+                    ("HIGH", "double"),
+                    ("LEGALCLOSEPRICE", "double"),
+                    ("WAPRICE", "double"),
+                    ("CLOSE", "double"),
+                    ("VOLUME", "double"),
+                ],
+                data,
+            },
+            Block::cursor("history", start, total, HISTORY_PAGE_SIZE),
+        ],
+        format,
+    ))
+}
+
+/// # ISS mirror routes
+pub fn router(store: MirrorState) -> Router {
+    Router::new()
+        .route("/iss/:resource", get(engines))
+        .route("/iss/engines/:engine/:resource", get(markets))
+        .route(
+            "/iss/engines/:engine/markets/:market/:resource",
+            get(boards),
+        )
+        .route(
+            "/iss/engines/:engine/markets/:market/boards/:board/:resource",
+            get(trades),
+        )
+        .route(
+            "/iss/engines/:engine/markets/:market/boards/:board/securities/:secid/:resource",
+            get(security_resource),
+        )
+        .route(
+            "/iss/history/engines/:engine/markets/:market/boards/:board/:resource",
+            get(board_history),
+        )
+        .with_state(store)
+}
+
+/// # Serve ISS mirror until the process is stopped
+pub async fn serve(listen: &str, store: MirrorState) -> Result<(), Box<dyn std::error::Error>> {
+    let listener = tokio::net::TcpListener::bind(listen).await?;
+    info!(url = %format!("http://{}/iss", listener.local_addr()?), "ISS Mirror listening");
+    axum::serve(listener, router(store)).await?;
+    Ok(())
+}
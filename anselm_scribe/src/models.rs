@@ -1,8 +1,20 @@
+use crate::config::IssEndpoint;
+use crate::iss::{
+    BoardRow, EngineRow, HistoryRow, IssBody, IssClient, IssError, IssResponseMeta, MarketRow,
+    SecurityRow, TradeRow,
+};
+use crate::metrics;
 use clickhouse::Row;
-use serde::Serialize;
-use std::collections::HashMap;
-use std::time::Instant;
-use time::{format_description::well_known::Iso8601, OffsetDateTime, PrimitiveDateTime, UtcOffset};
+use rust_decimal::prelude::FromPrimitive;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use time::format_description::FormatItem;
+use time::macros::{format_description, offset};
+use time::{Date, OffsetDateTime, PrimitiveDateTime, UtcOffset};
+use tracing::{debug, field, info_span, Instrument, Span};
 /// Data Struct for holding Engine data
 #[derive(Debug, Clone, Serialize, Row)]
 pub struct Engine {
@@ -35,8 +47,26 @@ pub struct Board {
     pub is_traded: bool,
 }
 
-/// Trade Record
-#[derive(Debug, Clone, Serialize, Row)]
+/// Data Struct for holding Security data of a board
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Security {
+    // Identifiers
+    pub engine: String,
+    pub market: String,
+    pub boardid: String,
+    pub secid: String,
+    pub shortname: String,
+    pub decimals: u8,
+    /// Number of securities in a lot, trade quantities are in lots
+    pub lotsize: u32,
+}
+
+/// # Trade Record
+///
+/// Serialized for disk files, Kafka and proofs with decimal strings and `B`/`S` side, so
+/// external formats round-trip exactly and stay readable. Clickhouse rows are
+/// `db::ClickhouseTrade`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Trade {
     // Identifiers
     pub engine: String,
@@ -45,14 +75,303 @@ pub struct Trade {
     pub boardid: String,
     // Main data
     pub tradeid: i64,
-    pub buysell: String,
+    pub buysell: Side,
     pub quantity: i32,
-    pub price: f64,
-    pub value: f64,
+    pub price: Decimal,
+    pub value: Decimal,
+    pub decimals: u8,
     #[serde(with = "clickhouse::serde::time::datetime")]
     pub tradetime: OffsetDateTime,
     #[serde(with = "clickhouse::serde::time::datetime")]
     pub systime: OffsetDateTime,
+    // Lineage
+    /// Id of the run that stored the trade, see `ingest_log` table
+    #[serde(default)]
+    pub run_id: String,
+}
+
+/// Daily History Record of a security on a board
+#[derive(Debug, Clone, Serialize, Deserialize, Row)]
+pub struct History {
+    // Identifiers
+    pub engine: String,
+    pub market: String,
+    pub secid: String,
+    pub boardid: String,
+    #[serde(with = "clickhouse::serde::time::date")]
+    pub tradedate: Date,
+    // Main data
+    pub numtrades: u32,
+    pub volume: u64,
+    #[serde(with = "decimal64")]
+    pub value: Decimal,
+    #[serde(with = "decimal64::option")]
+    pub open: Option<Decimal>,
+    #[serde(with = "decimal64::option")]
+    pub low: Option<Decimal>,
+    #[serde(with = "decimal64::option")]
+    pub high: Option<Decimal>,
+    #[serde(with = "decimal64::option")]
+    pub close: Option<Decimal>,
+    #[serde(with = "decimal64::option")]
+    pub waprice: Option<Decimal>,
+}
+
+/// ISS `TRADEDATE` format
+pub const ISS_DATE: &[FormatItem<'static>] = format_description!("[year]-[month]-[day]");
+
+/// ISS `SYSTIME` format, ISS times are in Moscow time
+pub const ISS_DATETIME: &[FormatItem<'static>] =
+    format_description!("[year]-[month]-[day] [hour]:[minute]:[second]");
+
+/// Moscow time offset
+pub const MOSCOW_OFFSET: UtcOffset = offset!(+3);
+
+/// Implementation for Trade data struct
+impl Trade {
+    /// Create trade from ISS `trades` row
+    pub fn from_iss_row(
+        engine: &str,
+        market: &str,
+        row: &TradeRow,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        // TODO: Make Date + Time merge
+        let trade_time =
+            PrimitiveDateTime::parse(&row.systime, ISS_DATETIME)?.assume_offset(MOSCOW_OFFSET);
+        // Round price to security decimals to restore exact tick-sized price
+        let price = Decimal::from_f64(row.price)
+            .ok_or_else(|| format!("Invalid price {} of trade {}", row.price, row.tradeno))?
+            .round_dp(row.decimals as u32);
+        // ISS value is in RUB and accounts for lot size, quantity is in lots
+        let value = Decimal::from_f64(row.value)
+            .ok_or_else(|| format!("Invalid value {} of trade {}", row.value, row.tradeno))?
+            .round_dp(decimal64::DECIMAL_SCALE);
+        Ok(Trade {
+            engine: engine.to_string(),
+            market: market.to_string(),
+            secid: row.secid.to_string(),
+            boardid: row.boardid.to_string(),
+            tradeid: row.tradeno,
+            buysell: row.buysell.parse()?,
+            quantity: row.quantity as i32,
+            price,
+            value,
+            decimals: row.decimals,
+            tradetime: trade_time,
+            systime: trade_time,
+            run_id: String::new(),
+        })
+    }
+}
+
+/// Implementation for History data struct
+impl History {
+    /// Create history record from ISS `history` row
+    pub fn from_iss_row(
+        engine: &str,
+        market: &str,
+        row: &HistoryRow,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let price = |value: f64| {
+            Decimal::from_f64(value)
+                .map(|d| d.round_dp(decimal64::DECIMAL_SCALE))
+                .ok_or_else(|| format!("Invalid price {} of security {}", value, row.secid))
+        };
+        Ok(History {
+            engine: engine.to_string(),
+            market: market.to_string(),
+            secid: row.secid.to_string(),
+            boardid: row.boardid.to_string(),
+            tradedate: Date::parse(&row.tradedate, ISS_DATE)?,
+            numtrades: row.numtrades as u32,
+            volume: row.volume as u64,
+            value: price(row.value)?,
+            open: row.open.map(price).transpose()?,
+            low: row.low.map(price).transpose()?,
+            high: row.high.map(price).transpose()?,
+            close: row.close.map(price).transpose()?,
+            waprice: row.waprice.map(price).transpose()?,
+        })
+    }
+}
+
+/// Trade side, serialized as `B`/`S` and mapped to Clickhouse `Enum8('B' = 1, 'S' = 2)` with
+/// `enum8`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[repr(i8)]
+pub enum Side {
+    /// Buy
+    B = 1,
+    /// Sell
+    S = 2,
+}
+
+/// Implementation for Side enum
+impl Side {
+    /// ISS representation of side
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Side::B => "B",
+            Side::S => "S",
+        }
+    }
+}
+
+impl FromStr for Side {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "B" => Ok(Side::B),
+            "S" => Ok(Side::S),
+            _ => Err(format!("Unknown trade side '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for Side {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// # Trade side serialization as Clickhouse `Enum8` code
+pub mod enum8 {
+    use super::Side;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(side: &Side, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i8(*side as i8)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Side, D::Error> {
+        match i8::deserialize(deserializer)? {
+            1 => Ok(Side::B),
+            2 => Ok(Side::S),
+            code => Err(de::Error::custom(format!(
+                "Unknown trade side code {}",
+                code
+            ))),
+        }
+    }
+}
+
+/// # Fixed-point decimal serialization as Clickhouse `Decimal64(DECIMAL_SCALE)`
+///
+/// Decimals are serialized as `i64` mantissa scaled to `DECIMAL_SCALE` places. Only used for
+/// Clickhouse rows, external formats serialize decimals as strings.
+pub mod decimal64 {
+    use rust_decimal::prelude::ToPrimitive;
+    use rust_decimal::Decimal;
+    use serde::{de, ser, Deserialize, Deserializer, Serializer};
+
+    /// Scale of Clickhouse `Decimal64` price and value columns
+    pub const DECIMAL_SCALE: u32 = 6;
+
+    /// Fixed-point mantissa of decimal at `DECIMAL_SCALE`, error if it overflows `i64`
+    pub fn mantissa(value: &Decimal) -> Result<i64, String> {
+        let mut scaled = *value;
+        scaled.rescale(DECIMAL_SCALE);
+        scaled
+            .mantissa()
+            .to_i64()
+            .ok_or_else(|| format!("Decimal {} overflows i64 mantissa", value))
+    }
+
+    pub fn serialize<S: Serializer>(value: &Decimal, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i64(mantissa(value).map_err(ser::Error::custom)?)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Decimal, D::Error> {
+        let mantissa = i64::deserialize(deserializer)?;
+        Decimal::try_from_i128_with_scale(mantissa as i128, DECIMAL_SCALE)
+            .map_err(de::Error::custom)
+    }
+
+    /// `Nullable(Decimal64(DECIMAL_SCALE))` columns
+    pub mod option {
+        use rust_decimal::Decimal;
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<S: Serializer>(
+            value: &Option<Decimal>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            match value {
+                Some(value) => super::serialize(value, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<Decimal>, D::Error> {
+            Option::<i64>::deserialize(deserializer)?
+                .map(|mantissa| {
+                    Decimal::try_from_i128_with_scale(mantissa as i128, super::DECIMAL_SCALE)
+                        .map_err(serde::de::Error::custom)
+                })
+                .transpose()
+        }
+    }
+}
+
+/// Number of trades ISS returns per page
+pub const TRADES_PAGE_SIZE: i32 = 5000;
+
+/// Raw ISS trades page
+#[derive(Debug, Clone)]
+pub struct TradesPage {
+    pub start: i32,
+    pub body: IssBody,
+    pub meta: IssResponseMeta,
+    pub time_req: Duration,
+}
+
+/// Failed ISS trades page request, metadata is kept for the ingestion log
+#[derive(Debug)]
+pub struct FailedPage {
+    pub start: i32,
+    pub meta: IssResponseMeta,
+    pub time_req: Duration,
+    pub error: IssError,
+}
+
+/// Implementation for FailedPage struct
+impl std::fmt::Display for FailedPage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+/// Implementation for FailedPage struct
+impl std::error::Error for FailedPage {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.error.source()
+    }
+}
+
+/// Trades page or the failed request for it
+fn trades_page(
+    start: i32,
+    meta: IssResponseMeta,
+    body: Result<IssBody, IssError>,
+    time_req: Duration,
+) -> Result<TradesPage, FailedPage> {
+    match body {
+        Ok(body) => Ok(TradesPage {
+            start,
+            body,
+            meta,
+            time_req,
+        }),
+        Err(error) => Err(FailedPage {
+            start,
+            meta,
+            time_req,
+            error,
+        }),
+    }
 }
 
 /// Implementation for Egnine data struct
@@ -76,70 +395,122 @@ impl Board {
     /// Fetch trades records
     pub async fn fetch_trades(
         &self,
-        engine: &String,
-        market: &String,
+        iss: &IssClient,
         start: i32,
     ) -> Result<Vec<Trade>, Box<dyn std::error::Error>> {
-        let url = format!(
-            "https://iss.moex.com/iss/engines/{}/markets/{}/boards/{}/trades.json",
-            engine, market, self.boardid
+        let page = self.fetch_trades_page(iss, start).await?;
+        let records = Board::parse_trades_page(&self.engine, &self.market, &self.boardid, &page)
+            .map_err(|e| e as Box<dyn std::error::Error>)?;
+        Ok(records)
+    }
+
+    /// Fetch raw trades page starting from `start`
+    pub async fn fetch_trades_page(
+        &self,
+        iss: &IssClient,
+        start: i32,
+    ) -> Result<TradesPage, FailedPage> {
+        let path = format!(
+            "engines/{}/markets/{}/boards/{}/trades",
+            self.engine, self.market, self.boardid
         );
 
-        // Create a client
-        let client = reqwest::Client::new();
+        let span = info_span!(
+            "fetch_page",
+            start,
+            status = field::Empty,
+            bytes = field::Empty,
+            fetch_ms = field::Empty
+        );
 
         // Time req
         let time_req: Instant = Instant::now();
 
         // Fetch response
-        let resp = client
-            .get(&url)
-            .query(&[("start", start.to_string())])
-            .send()
-            .await?
-            .json::<HashMap<String, serde_json::Value>>()
+        let (meta, body) = iss
+            .get_with_meta(
+                IssEndpoint::Trades,
+                &path,
+                &[
+                    ("start", start.to_string()),
+                    ("limit", TRADES_PAGE_SIZE.to_string()),
+                ],
+            )
+            .instrument(span.clone())
+            .await;
+        span.record("status", meta.status);
+        span.record("bytes", meta.bytes);
+        span.record("fetch_ms", time_req.elapsed().as_secs_f64() * 1e3);
+
+        trades_page(start, meta, body, time_req.elapsed())
+    }
+
+    /// Fetch daily history records of board securities for `date` starting from `start`
+    ///
+    /// Returns an empty page once `start` is past the last security
+    pub async fn fetch_history_page(
+        &self,
+        iss: &IssClient,
+        date: Date,
+        start: usize,
+    ) -> Result<Vec<History>, Box<dyn std::error::Error>> {
+        let path = format!(
+            "history/engines/{}/markets/{}/boards/{}/securities",
+            self.engine, self.market, self.boardid
+        );
+
+        let body = iss
+            .get(
+                IssEndpoint::History,
+                &path,
+                &[
+                    ("date", date.format(ISS_DATE)?),
+                    ("start", start.to_string()),
+                ],
+            )
             .await?;
-        let time_req = time_req.elapsed();
 
+        let rows: Vec<HistoryRow> = body.rows()?;
+        let records = rows
+            .iter()
+            .map(|row| History::from_iss_row(&self.engine, &self.market, row))
+            .collect::<Result<Vec<History>, _>>()
+            .map_err(|e| e as Box<dyn std::error::Error>)?;
+        Ok(records)
+    }
+
+    /// Fetch daily history records of all board securities for `date`
+    pub async fn fetch_history(
+        &self,
+        iss: &IssClient,
+        date: Date,
+    ) -> Result<Vec<History>, Box<dyn std::error::Error>> {
+        let mut records: Vec<History> = Vec::new();
+        loop {
+            let page = self.fetch_history_page(iss, date, records.len()).await?;
+            if page.is_empty() {
+                break;
+            }
+            records.extend(page);
+        }
+        Ok(records)
+    }
+
+    /// Parse raw trades page into trades records
+    pub fn parse_trades_page(
+        engine: &str,
+        market: &str,
+        boardid: &str,
+        page: &TradesPage,
+    ) -> Result<Vec<Trade>, Box<dyn std::error::Error + Send + Sync>> {
         // Time Parsing
         let time_parse: Instant = Instant::now();
 
-        // Convert response into an iterator
-        let resp_iter = resp["trades"]["data"]
-            .as_array()
-            .expect("Error parsing securities")
-            .iter();
-
-        // Define Timezone for MOEX
-        let moscow_offset = UtcOffset::from_hms(3, 0, 0)?;
-
-        // Parse iterator
-        let records: Vec<Trade> = resp_iter
-            .map(|x| {
-                // Replace space with to make it ISO8601 compliant
-                let iso_date = x[9].as_str().unwrap().replace(' ', "T");
-                // Parse date by converting first to primitive date
-                // Then to timezone aware datetime
-                let trade_time = PrimitiveDateTime::parse(&iso_date, &Iso8601::DEFAULT).unwrap();
-                let price = x[4].as_f64().unwrap();
-                let quantity = x[5].as_i64().unwrap();
-                let value = price * quantity as f64;
-                Trade {
-                    engine: engine.clone(),
-                    market: market.clone(),
-                    tradeid: x[0].as_i64().unwrap(),
-                    // TODO: Make Date + Time merge
-                    tradetime: trade_time.assume_offset(moscow_offset),
-                    boardid: x[2].as_str().unwrap().into(),
-                    secid: x[3].as_str().unwrap().into(),
-                    price,
-                    quantity: quantity as i32,
-                    value,
-                    systime: trade_time.assume_offset(moscow_offset),
-                    buysell: x[10].as_str().unwrap().into(),
-                }
-            })
-            .collect();
+        let rows: Vec<TradeRow> = page.body.rows()?;
+        let records = rows
+            .iter()
+            .map(|row| Trade::from_iss_row(engine, market, row))
+            .collect::<Result<Vec<Trade>, _>>()?;
 
         // Set time for first and last trade
         let first_trade = if !records.is_empty() {
@@ -153,121 +524,180 @@ impl Board {
             "none".to_string()
         };
 
-        println!(
-            "Trades[{}]: Board '{}' for Market '{}' for Engine '{}' from {} until {} start {} response {:?} parse {:?}",
-            records.len(),
-            self.boardid,
-            market,
-            engine,
-            first_trade,
-            last_trade,
-            start,
-            time_req,
-            time_parse.elapsed()
+        metrics::iss_parse(IssEndpoint::Trades, time_parse.elapsed());
+        metrics::rows_fetched(engine, market, boardid, records.len());
+
+        // Timings become attributes of the current page span when it declares them
+        let fetch_ms = page.time_req.as_secs_f64() * 1e3;
+        let parse_ms = time_parse.elapsed().as_secs_f64() * 1e3;
+        let span = Span::current();
+        span.record("trades", records.len());
+        span.record("fetch_ms", fetch_ms);
+        span.record("parse_ms", parse_ms);
+        debug!(
+            trades = records.len(),
+            %engine,
+            %market,
+            %boardid,
+            %first_trade,
+            %last_trade,
+            start = page.start,
+            fetch_ms,
+            parse_ms,
+            "Trades parsed"
         );
 
         Ok(records)
     }
 }
 
+/// Fetch raw trades page of board trades after trade number `tradeno`
+///
+/// Trades of a single security are fetched if `secid` is defined
+pub async fn fetch_trades_after(
+    iss: &IssClient,
+    engine: &str,
+    market: &str,
+    boardid: &str,
+    secid: Option<&str>,
+    tradeno: u64,
+) -> Result<TradesPage, FailedPage> {
+    let path = match secid {
+        Some(secid) => format!(
+            "engines/{}/markets/{}/boards/{}/securities/{}/trades",
+            engine, market, boardid, secid
+        ),
+        None => format!(
+            "engines/{}/markets/{}/boards/{}/trades",
+            engine, market, boardid
+        ),
+    };
+
+    // Time req
+    let time_req: Instant = Instant::now();
+
+    let (meta, body) = iss
+        .get_with_meta(
+            IssEndpoint::Trades,
+            &path,
+            &[
+                ("tradeno", tradeno.to_string()),
+                ("next_trade", "1".to_string()),
+                ("limit", TRADES_PAGE_SIZE.to_string()),
+            ],
+        )
+        .await;
+
+    trades_page(0, meta, body, time_req.elapsed())
+}
+
 /// Get engines
 /// TODO: Impliment as async trait for struct
-pub async fn get_engines() -> Result<Vec<Engine>, Box<dyn std::error::Error>> {
-    let url = "https://iss.moex.com/iss/engines.json";
+pub async fn get_engines(iss: &IssClient) -> Result<Vec<Engine>, Box<dyn std::error::Error>> {
+    let body = iss.get(IssEndpoint::Engines, "engines", &[]).await?;
 
-    let resp = reqwest::get(url)
-        .await?
-        .json::<HashMap<String, serde_json::Value>>()
-        .await?;
-
-    let resp_iter = resp["engines"]["data"]
-        .as_array()
-        .expect("Error parsing Engines API data")
-        .iter();
-
-    let records: Vec<Engine> = resp_iter
+    let rows: Vec<EngineRow> = body.rows()?;
+    let records: Vec<Engine> = rows
+        .into_iter()
         .map(|x| Engine {
-            id: x[0].as_i64().unwrap() as i32,
-            name: x[1].as_str().unwrap().into(),
-            title: x[2].as_str().unwrap().into(),
+            id: x.id,
+            name: x.name.into_owned(),
+            title: x.title.into_owned(),
         })
         .collect();
 
-    println!("API GET Engines[{}]", records.len());
+    debug!(engines = records.len(), "Engines fetched");
     Ok(records)
 }
 
 /// Get markets for a given engine
 /// TODO: Impliment as async trait for struct
-pub async fn get_markets(engine: &String) -> Result<Vec<Market>, Box<dyn std::error::Error>> {
-    let url = format!(
-        "https://iss.moex.com/iss/engines/{}/markets.json",
-        engine.as_str()
-    );
-
-    println!("{}", url);
-
-    let resp = reqwest::get(url)
-        .await?
-        .json::<HashMap<String, serde_json::Value>>()
-        .await?;
+pub async fn get_markets(
+    iss: &IssClient,
+    engine: &String,
+) -> Result<Vec<Market>, Box<dyn std::error::Error>> {
+    let path = format!("engines/{}/markets", engine.as_str());
 
-    let resp_iter = resp["markets"]["data"]
-        .as_array()
-        .expect("Error parsing Markets API data")
-        .iter();
+    let body = iss.get(IssEndpoint::Markets, &path, &[]).await?;
 
-    let records: Vec<Market> = resp_iter
+    let rows: Vec<MarketRow> = body.rows()?;
+    let records: Vec<Market> = rows
+        .into_iter()
         .map(|x| Market {
             engine: engine.to_string(),
-            id: x[0].as_i64().unwrap() as i32,
-            name: x[1].as_str().unwrap().into(),
-            title: x[2].as_str().unwrap().into(),
+            id: x.id,
+            name: x.name.into_owned(),
+            title: x.title.into_owned(),
         })
         .collect();
 
-    println!("API GET Markets[{}]: Engine '{}'", records.len(), engine);
+    debug!(markets = records.len(), %engine, "Markets fetched");
     Ok(records)
 }
 
 /// Get boards for a given engine and market
 /// TODO: Impliment as async trait for struct
 pub async fn get_boards(
+    iss: &IssClient,
     engine: &String,
     market: &String,
 ) -> Result<Vec<Board>, Box<dyn std::error::Error>> {
-    let url = format!("https://iss.moex.com/iss/engines/{engine}/markets/{market}/boards.json");
-
-    let resp = reqwest::get(url)
-        .await?
-        .json::<HashMap<String, serde_json::Value>>()
-        .await?;
+    let path = format!("engines/{engine}/markets/{market}/boards");
 
-    let resp_iter = resp["boards"]["data"]
-        .as_array()
-        .expect("Error parsing Boards API data")
-        .iter();
+    let body = iss.get(IssEndpoint::Boards, &path, &[]).await?;
 
-    let records: Vec<Board> = resp_iter
+    let rows: Vec<BoardRow> = body.rows()?;
+    let records: Vec<Board> = rows
+        .into_iter()
         .map(|x| Board {
             engine: engine.to_string(),
             market: market.to_string(),
-            // Convert the value to i64 first, then cast to i32
-            id: x[0].as_i64().unwrap() as i32,
-            // Convert the value to i64 first, then cast to i32
-            board_group_id: x[1].as_i64().unwrap() as i32,
-            boardid: x[2].as_str().unwrap().into(),
-            title: x[3].as_str().unwrap().into(),
+            id: x.id,
+            board_group_id: x.board_group_id,
+            boardid: x.boardid.into_owned(),
+            title: x.title.into_owned(),
             // Convert 0 or 1 to a bool
-            is_traded: x[4].as_i64().unwrap() != 0,
+            is_traded: x.is_traded != 0,
         })
         .collect();
 
-    println!(
-        "API GET Boards[{}]: Market '{}' for Engine '{}'",
-        records.len(),
-        market,
-        engine
+    debug!(boards = records.len(), %engine, %market, "Boards fetched");
+    Ok(records)
+}
+
+/// Get securities of a board
+pub async fn get_securities(
+    iss: &IssClient,
+    board: &Board,
+) -> Result<Vec<Security>, Box<dyn std::error::Error>> {
+    let path = format!(
+        "engines/{}/markets/{}/boards/{}/securities",
+        board.engine, board.market, board.boardid
     );
+    let query = [
+        ("iss.only", "securities".to_string()),
+        (
+            "securities.columns",
+            "SECID,BOARDID,SHORTNAME,DECIMALS,LOTSIZE".to_string(),
+        ),
+    ];
+
+    let body = iss.get(IssEndpoint::Securities, &path, &query).await?;
+
+    let rows: Vec<SecurityRow> = body.rows()?;
+    let records: Vec<Security> = rows
+        .into_iter()
+        .map(|x| Security {
+            engine: board.engine.clone(),
+            market: board.market.clone(),
+            boardid: x.boardid.into_owned(),
+            secid: x.secid.into_owned(),
+            shortname: x.shortname.into_owned(),
+            decimals: x.decimals,
+            lotsize: x.lotsize as u32,
+        })
+        .collect();
+
+    debug!(securities = records.len(), board = %board.boardid, "Securities fetched");
     Ok(records)
 }
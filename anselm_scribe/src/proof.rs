@@ -0,0 +1,629 @@
+use crate::disk::write_file_atomic;
+use crate::ledger::{trade_leaf, Ledger, LedgerEntry};
+use crate::merkle::{audit_path, from_hex, leaf_hash, merkle_root, root_from_path, to_hex, Hash};
+use crate::mirror::MirrorStore;
+use crate::models::{Trade, ISS_DATE};
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::PathBuf;
+use time::{Date, OffsetDateTime};
+use tracing::{debug, info};
+
+/// Version of manifests and proofs
+pub const PROOF_VERSION: u8 = 2;
+
+/// Domain separation prefix of signed daily roots
+const SIGNED_PREFIX: &[u8] = b"anselm daily root v1\0";
+
+/// # Sealed trades of a security listed in a daily manifest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub seq: u64,
+    pub engine: String,
+    pub market: String,
+    pub boardid: String,
+    pub secid: String,
+    pub trades: u64,
+    pub first_tradeid: i64,
+    pub last_tradeid: i64,
+    /// Merkle root of trades of the entry
+    pub root: String,
+    /// Unix time the trades were sealed into the ledger, missing in manifests of version 1
+    #[serde(default)]
+    pub sealed_at: i64,
+}
+
+/// Implementation for ManifestEntry struct
+impl ManifestEntry {
+    /// Merkle leaf of entry within daily root
+    pub fn leaf(&self) -> Result<Hash, String> {
+        let mut buf = Vec::with_capacity(128);
+        buf.push(PROOF_VERSION);
+        for value in [&self.engine, &self.market, &self.boardid, &self.secid] {
+            buf.extend_from_slice(&(value.len() as u32).to_be_bytes());
+            buf.extend_from_slice(value.as_bytes());
+        }
+        buf.extend_from_slice(&self.trades.to_be_bytes());
+        buf.extend_from_slice(&self.first_tradeid.to_be_bytes());
+        buf.extend_from_slice(&self.last_tradeid.to_be_bytes());
+        buf.extend_from_slice(&from_hex(&self.root)?);
+        buf.extend_from_slice(&self.sealed_at.to_be_bytes());
+        Ok(leaf_hash(&buf))
+    }
+}
+
+/// Implementation for ManifestEntry struct
+impl From<&LedgerEntry> for ManifestEntry {
+    fn from(entry: &LedgerEntry) -> Self {
+        ManifestEntry {
+            seq: entry.seq,
+            engine: entry.engine.clone(),
+            market: entry.market.clone(),
+            boardid: entry.boardid.clone(),
+            secid: entry.secid.clone(),
+            trades: entry.trades,
+            first_tradeid: entry.first_tradeid,
+            last_tradeid: entry.last_tradeid,
+            root: entry.root.clone(),
+            sealed_at: entry.sealed_at,
+        }
+    }
+}
+
+/// # Daily root, the part of a manifest covered by its signature
+///
+/// `root` is the Merkle root of manifest entries in ledger order. `ledger_hash` is the hash of
+/// the last ledger entry when the manifest was signed, tying the day to the chained ledger.
+/// `signed_at` is the signing time, trades were captured by `sealed_at` of the entries.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DailyRoot {
+    pub version: u8,
+    pub day: String,
+    pub entries: u64,
+    pub trades: u64,
+    pub root: String,
+    pub ledger_hash: String,
+    pub signed_at: i64,
+}
+
+/// Implementation for DailyRoot struct
+impl DailyRoot {
+    /// Signed message
+    pub fn message(&self) -> Result<Vec<u8>, String> {
+        let mut buf = Vec::with_capacity(128);
+        buf.extend_from_slice(SIGNED_PREFIX);
+        buf.push(self.version);
+        buf.extend_from_slice(&(self.day.len() as u32).to_be_bytes());
+        buf.extend_from_slice(self.day.as_bytes());
+        buf.extend_from_slice(&self.entries.to_be_bytes());
+        buf.extend_from_slice(&self.trades.to_be_bytes());
+        buf.extend_from_slice(&from_hex(&self.root)?);
+        buf.extend_from_slice(&from_hex(&self.ledger_hash)?);
+        buf.extend_from_slice(&self.signed_at.to_be_bytes());
+        Ok(buf)
+    }
+}
+
+/// # Daily dataset manifest
+///
+/// Saved to `manifests/>day<.json` of the ledger directory with a detached signature in
+/// `manifests/>day<.json.sig`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub daily: DailyRoot,
+    pub entries: Vec<ManifestEntry>,
+}
+
+/// # Detached Ed25519 signature of a daily root
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DetachedSignature {
+    pub public_key: String,
+    pub signature: String,
+}
+
+/// Implementation for DetachedSignature struct
+impl DetachedSignature {
+    /// Sign daily root
+    pub fn sign(key: &SigningKey, daily: &DailyRoot) -> Result<Self, String> {
+        Ok(DetachedSignature {
+            public_key: to_hex(key.verifying_key().as_bytes()),
+            signature: to_hex(&key.sign(&daily.message()?).to_bytes()),
+        })
+    }
+
+    /// Verify signature of daily root, returns signing key
+    pub fn verify(&self, daily: &DailyRoot) -> Result<VerifyingKey, String> {
+        let key = VerifyingKey::from_bytes(&from_hex(&self.public_key)?)
+            .map_err(|e| format!("Invalid public key: {}", e))?;
+        let signature = decode_hex(&self.signature)?;
+        let signature =
+            Signature::from_slice(&signature).map_err(|e| format!("Invalid signature: {}", e))?;
+        key.verify_strict(&daily.message()?, &signature)
+            .map_err(|_| "Signature does not match daily root".to_string())?;
+        Ok(key)
+    }
+}
+
+/// # Inclusion proof of a trade in a signed daily root
+///
+/// Self-contained, checked offline with [`verify_proof`]: the trade hashes up `trade_path` to
+/// the root of its manifest entry, the entry hashes up `entry_path` to the daily root and the
+/// daily root is signed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InclusionProof {
+    pub version: u8,
+    pub trade: Trade,
+    pub trade_index: u64,
+    pub trade_path: Vec<String>,
+    pub entry: ManifestEntry,
+    pub entry_index: u64,
+    pub entry_path: Vec<String>,
+    pub daily: DailyRoot,
+    pub signature: DetachedSignature,
+}
+
+/// Decode hex of any length
+fn decode_hex(hex: &str) -> Result<Vec<u8>, String> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return Err(format!("Invalid hex '{}'", hex));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|e| format!("Invalid hex: {}", e)))
+        .collect()
+}
+
+/// # Load Ed25519 signing key
+///
+/// Key file holds hex of the 32 byte secret key
+pub async fn load_signing_key(path: &str) -> Result<SigningKey, Box<dyn std::error::Error>> {
+    let hex = tokio::fs::read_to_string(path).await?;
+    Ok(SigningKey::from_bytes(&from_hex(hex.trim())?))
+}
+
+/// # Generate Ed25519 signing key, never overwrites an existing key
+pub async fn generate_signing_key(path: &str) -> Result<SigningKey, Box<dyn std::error::Error>> {
+    let mut secret = [0u8; 32];
+    getrandom::getrandom(&mut secret).map_err(|e| e.to_string())?;
+    let key = SigningKey::from_bytes(&secret);
+
+    let mut options = tokio::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    // Secret key is readable by owner only
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(path).await?;
+    tokio::io::AsyncWriteExt::write_all(&mut file, format!("{}\n", to_hex(&secret)).as_bytes())
+        .await?;
+    Ok(key)
+}
+
+/// Paths of manifest and its signature
+fn manifest_paths(ledger: &Ledger, day: &str) -> (PathBuf, PathBuf) {
+    let dir = ledger.dir().join("manifests");
+    (
+        dir.join(format!("{}.json", day)),
+        dir.join(format!("{}.json.sig", day)),
+    )
+}
+
+/// Load manifest of a day with its signature, `None` if the day is not signed
+pub async fn load_manifest(
+    ledger: &Ledger,
+    day: &str,
+) -> Result<Option<(Manifest, DetachedSignature)>, Box<dyn std::error::Error>> {
+    let (manifest_path, signature_path) = manifest_paths(ledger, day);
+    let manifest = match tokio::fs::read(&manifest_path).await {
+        Ok(data) => serde_json::from_slice(&data)?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let signature = serde_json::from_slice(&tokio::fs::read(&signature_path).await?)?;
+    Ok(Some((manifest, signature)))
+}
+
+/// # Sign manifests of sealed days within Moscow dates `[from, till)`
+///
+/// Days whose manifest already covers all their ledger entries are skipped, days sealed
+/// further are signed again. Returns number of signed days.
+pub async fn sign_manifests(
+    ledger: &Ledger,
+    key: &SigningKey,
+    from: Date,
+    till: Date,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let entries = ledger.entries().await?;
+    let ledger_hash = match entries.last() {
+        Some(last) => last.hash.clone(),
+        None => return Ok(0),
+    };
+    let mut signed = 0;
+
+    let mut day = from;
+    while day < till {
+        let day_str = day.format(ISS_DATE)?;
+        let manifest_entries: Vec<ManifestEntry> = entries
+            .iter()
+            .filter(|e| e.day == day_str)
+            .map(ManifestEntry::from)
+            .collect();
+        day = match day.next_day() {
+            Some(next) => next,
+            None => till,
+        };
+        if manifest_entries.is_empty() {
+            continue;
+        }
+
+        let leaves = manifest_entries
+            .iter()
+            .map(ManifestEntry::leaf)
+            .collect::<Result<Vec<Hash>, String>>()?;
+        let root = to_hex(&merkle_root(&leaves));
+        if let Some((manifest, _)) = load_manifest(ledger, &day_str).await? {
+            if manifest.daily.root == root {
+                debug!(day = %day_str, "Manifest already signed");
+                continue;
+            }
+        }
+
+        let manifest = Manifest {
+            daily: DailyRoot {
+                version: PROOF_VERSION,
+                day: day_str.clone(),
+                entries: manifest_entries.len() as u64,
+                trades: manifest_entries.iter().map(|e| e.trades).sum(),
+                root,
+                ledger_hash: ledger_hash.clone(),
+                signed_at: OffsetDateTime::now_utc().unix_timestamp(),
+            },
+            entries: manifest_entries,
+        };
+        let signature = DetachedSignature::sign(key, &manifest.daily)?;
+
+        let (manifest_path, signature_path) = manifest_paths(ledger, &day_str);
+        if let Some(dir) = manifest_path.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        write_file_atomic(&manifest_path, &serde_json::to_vec_pretty(&manifest)?).await?;
+        write_file_atomic(&signature_path, &serde_json::to_vec_pretty(&signature)?).await?;
+        info!(
+            day = %manifest.daily.day,
+            entries = manifest.daily.entries,
+            trades = manifest.daily.trades,
+            root = %manifest.daily.root,
+            public_key = %signature.public_key,
+            "Manifest signed"
+        );
+        signed += 1;
+    }
+
+    Ok(signed)
+}
+
+/// # Build inclusion proof of a sealed trade
+///
+/// Trade is looked up in the ledger by board, security and trade id and read from `store`.
+/// Fails if the day of the trade is not signed or the stored trade differs from the sealed one.
+pub async fn prove_trade(
+    store: &dyn MirrorStore,
+    ledger: &Ledger,
+    boardid: &str,
+    secid: &str,
+    tradeid: i64,
+) -> Result<InclusionProof, Box<dyn std::error::Error>> {
+    let entry = ledger
+        .entries()
+        .await?
+        .into_iter()
+        .find(|e| {
+            e.boardid == boardid
+                && e.secid == secid
+                && e.first_tradeid <= tradeid
+                && tradeid <= e.last_tradeid
+        })
+        .ok_or_else(|| {
+            format!(
+                "Trade {} of '{}' '{}' is not sealed",
+                tradeid, boardid, secid
+            )
+        })?;
+
+    let leaves = ledger.leaves(&entry).await?;
+    let trade_index = leaves
+        .iter()
+        .position(|(id, _)| *id == tradeid)
+        .ok_or_else(|| {
+            format!(
+                "Trade {} is not sealed in ledger entry {}",
+                tradeid, entry.seq
+            )
+        })?;
+    let trade_hashes: Vec<Hash> = leaves.iter().map(|(_, leaf)| *leaf).collect();
+
+    let trade = store
+        .trades_after(
+            &entry.engine,
+            &entry.market,
+            boardid,
+            Some(secid),
+            (tradeid - 1) as u64,
+            1,
+        )
+        .await
+        .map_err(|e| e as Box<dyn std::error::Error>)?
+        .into_iter()
+        .find(|t| t.tradeid == tradeid)
+        .ok_or_else(|| format!("Trade {} is missing from the store", tradeid))?;
+    if trade_leaf(&trade)? != trade_hashes[trade_index] {
+        return Err(format!("Trade {} was altered after sealing", tradeid).into());
+    }
+
+    let (manifest, signature) = load_manifest(ledger, &entry.day)
+        .await?
+        .ok_or_else(|| format!("Day {} is not signed, run sign first", entry.day))?;
+    let entry_index = manifest
+        .entries
+        .iter()
+        .position(|e| e.seq == entry.seq)
+        .ok_or_else(|| format!("Ledger entry {} is not in manifest, sign again", entry.seq))?;
+    let entry_hashes = manifest
+        .entries
+        .iter()
+        .map(ManifestEntry::leaf)
+        .collect::<Result<Vec<Hash>, String>>()?;
+
+    Ok(InclusionProof {
+        version: PROOF_VERSION,
+        trade,
+        trade_index: trade_index as u64,
+        trade_path: audit_path(&trade_hashes, trade_index)
+            .iter()
+            .map(|h| to_hex(h))
+            .collect(),
+        entry: manifest.entries[entry_index].clone(),
+        entry_index: entry_index as u64,
+        entry_path: audit_path(&entry_hashes, entry_index)
+            .iter()
+            .map(|h| to_hex(h))
+            .collect(),
+        daily: manifest.daily,
+        signature,
+    })
+}
+
+/// Decode audit path
+fn decode_path(path: &[String]) -> Result<Vec<Hash>, String> {
+    path.iter().map(|h| from_hex(h)).collect()
+}
+
+/// # Verify inclusion proof offline
+///
+/// Checks that the trade belongs to its manifest entry, the entry belongs to the daily root and
+/// the daily root is signed by `trusted` key. Returns key of the signer.
+pub fn verify_proof(proof: &InclusionProof, trusted: &str) -> Result<VerifyingKey, String> {
+    if proof.version != PROOF_VERSION || proof.daily.version != PROOF_VERSION {
+        return Err(format!("Unsupported proof version {}", proof.version));
+    }
+
+    let trade = &proof.trade;
+    let entry = &proof.entry;
+    if (&trade.engine, &trade.market, &trade.boardid, &trade.secid)
+        != (&entry.engine, &entry.market, &entry.boardid, &entry.secid)
+    {
+        return Err("Trade does not belong to security of manifest entry".to_string());
+    }
+    let trade_root = root_from_path(
+        &trade_leaf(trade)?,
+        proof.trade_index as usize,
+        entry.trades as usize,
+        &decode_path(&proof.trade_path)?,
+    );
+    if trade_root != Some(from_hex(&entry.root)?) {
+        return Err("Trade is not included in root of manifest entry".to_string());
+    }
+
+    let daily_root = root_from_path(
+        &entry.leaf()?,
+        proof.entry_index as usize,
+        proof.daily.entries as usize,
+        &decode_path(&proof.entry_path)?,
+    );
+    if daily_root != Some(from_hex(&proof.daily.root)?) {
+        return Err("Manifest entry is not included in daily root".to_string());
+    }
+
+    if !trusted.eq_ignore_ascii_case(&proof.signature.public_key) {
+        return Err(format!(
+            "Daily root is signed by untrusted key {}",
+            proof.signature.public_key
+        ));
+    }
+    proof.signature.verify(&proof.daily)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Side;
+    use rust_decimal::Decimal;
+    use time::macros::datetime;
+
+    fn trade(secid: &str, tradeid: i64) -> Trade {
+        Trade {
+            engine: "stock".to_string(),
+            market: "shares".to_string(),
+            secid: secid.to_string(),
+            boardid: "TQBR".to_string(),
+            tradeid,
+            buysell: Side::B,
+            quantity: 10,
+            price: Decimal::new(29128, 2),
+            value: Decimal::new(29128, 0),
+            decimals: 2,
+            tradetime: datetime!(2024-03-01 10:00:00 +3),
+            systime: datetime!(2024-03-01 10:00:00 +3),
+            run_id: String::new(),
+        }
+    }
+
+    /// Manifest entry sealing trades
+    fn entry(seq: u64, trades: &[Trade]) -> ManifestEntry {
+        let leaves: Vec<Hash> = trades.iter().map(|t| trade_leaf(t).unwrap()).collect();
+        ManifestEntry {
+            seq,
+            engine: trades[0].engine.clone(),
+            market: trades[0].market.clone(),
+            boardid: trades[0].boardid.clone(),
+            secid: trades[0].secid.clone(),
+            trades: trades.len() as u64,
+            first_tradeid: trades[0].tradeid,
+            last_tradeid: trades[trades.len() - 1].tradeid,
+            root: to_hex(&merkle_root(&leaves)),
+            sealed_at: 1_709_280_000,
+        }
+    }
+
+    /// Proof of the second SBER trade of a day signed by `key`
+    fn signed_proof(key: &SigningKey) -> InclusionProof {
+        let sber = [trade("SBER", 1), trade("SBER", 3), trade("SBER", 4)];
+        let gazp = [trade("GAZP", 2), trade("GAZP", 5)];
+        let entries = [entry(0, &gazp), entry(1, &sber)];
+
+        let trade_hashes: Vec<Hash> = sber.iter().map(|t| trade_leaf(t).unwrap()).collect();
+        let entry_hashes: Vec<Hash> = entries.iter().map(|e| e.leaf().unwrap()).collect();
+        let daily = DailyRoot {
+            version: PROOF_VERSION,
+            day: "2024-03-01".to_string(),
+            entries: entries.len() as u64,
+            trades: 5,
+            root: to_hex(&merkle_root(&entry_hashes)),
+            ledger_hash: to_hex(&[0xab; 32]),
+            signed_at: 1_709_290_000,
+        };
+        let hex_path = |path: Vec<Hash>| path.iter().map(|h| to_hex(h)).collect();
+        InclusionProof {
+            version: PROOF_VERSION,
+            trade: sber[1].clone(),
+            trade_index: 1,
+            trade_path: hex_path(audit_path(&trade_hashes, 1)),
+            entry: entries[1].clone(),
+            entry_index: 1,
+            entry_path: hex_path(audit_path(&entry_hashes, 1)),
+            signature: DetachedSignature::sign(key, &daily).unwrap(),
+            daily,
+        }
+    }
+
+    fn signing_key(seed: u8) -> SigningKey {
+        SigningKey::from_bytes(&[seed; 32])
+    }
+
+    fn public(key: &SigningKey) -> String {
+        to_hex(key.verifying_key().as_bytes())
+    }
+
+    /// Hex with its first digit changed
+    fn flip(hex: &str) -> String {
+        let first = match hex.starts_with('0') {
+            true => '1',
+            false => '0',
+        };
+        format!("{}{}", first, &hex[1..])
+    }
+
+    #[test]
+    fn valid_proof_verifies() {
+        let key = signing_key(7);
+        let proof = signed_proof(&key);
+        assert_eq!(verify_proof(&proof, &public(&key)), Ok(key.verifying_key()));
+        assert_eq!(
+            verify_proof(&proof, &public(&key).to_uppercase()),
+            Ok(key.verifying_key())
+        );
+
+        // Proofs are written and read as JSON
+        let json = serde_json::to_string(&proof).unwrap();
+        let proof: InclusionProof = serde_json::from_str(&json).unwrap();
+        assert!(verify_proof(&proof, &public(&key)).is_ok());
+    }
+
+    #[test]
+    fn altered_trade_rejected() {
+        let key = signing_key(7);
+        let alterations: [fn(&mut Trade); 7] = [
+            |t| t.tradeid += 1,
+            |t| t.buysell = Side::S,
+            |t| t.quantity += 1,
+            |t| t.price += Decimal::new(1, 2),
+            |t| t.value += Decimal::ONE,
+            |t| t.tradetime += time::Duration::seconds(1),
+            |t| t.secid = "GAZP".to_string(),
+        ];
+        for alter in alterations {
+            let mut proof = signed_proof(&key);
+            alter(&mut proof.trade);
+            assert!(verify_proof(&proof, &public(&key)).is_err());
+        }
+    }
+
+    #[test]
+    fn altered_roots_rejected() {
+        let key = signing_key(7);
+
+        let mut proof = signed_proof(&key);
+        proof.entry.root = flip(&proof.entry.root);
+        assert!(verify_proof(&proof, &public(&key)).is_err());
+
+        let mut proof = signed_proof(&key);
+        proof.entry.trades += 1;
+        assert!(verify_proof(&proof, &public(&key)).is_err());
+
+        let mut proof = signed_proof(&key);
+        proof.daily.root = flip(&proof.daily.root);
+        assert!(verify_proof(&proof, &public(&key)).is_err());
+
+        let mut proof = signed_proof(&key);
+        proof.trade_path[0] = flip(&proof.trade_path[0]);
+        assert!(verify_proof(&proof, &public(&key)).is_err());
+
+        let mut proof = signed_proof(&key);
+        proof.trade_index = 2;
+        assert!(verify_proof(&proof, &public(&key)).is_err());
+    }
+
+    #[test]
+    fn altered_signature_rejected() {
+        let key = signing_key(7);
+
+        let mut proof = signed_proof(&key);
+        proof.signature.signature = flip(&proof.signature.signature);
+        assert!(verify_proof(&proof, &public(&key)).is_err());
+
+        // Signed fields outside of the Merkle roots
+        let mut proof = signed_proof(&key);
+        proof.daily.signed_at += 1;
+        assert!(verify_proof(&proof, &public(&key)).is_err());
+
+        let mut proof = signed_proof(&key);
+        proof.daily.ledger_hash = flip(&proof.daily.ledger_hash);
+        assert!(verify_proof(&proof, &public(&key)).is_err());
+    }
+
+    #[test]
+    fn untrusted_key_rejected() {
+        let key = signing_key(7);
+        let other = signing_key(8);
+
+        // Valid proof signed by another key
+        let proof = signed_proof(&other);
+        assert!(verify_proof(&proof, &public(&other)).is_ok());
+        assert!(verify_proof(&proof, &public(&key)).is_err());
+
+        // Signature of another key passed off as the trusted one
+        let mut proof = signed_proof(&other);
+        proof.signature.public_key = public(&key);
+        assert!(verify_proof(&proof, &public(&key)).is_err());
+    }
+}
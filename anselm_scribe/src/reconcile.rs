@@ -0,0 +1,393 @@
+use crate::models::{decimal64, Board, History, Security};
+use clickhouse::Row;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use time::{Date, OffsetDateTime};
+
+/// Daily totals of a security aggregated from stored trades
+#[derive(Debug, Clone, Serialize, Deserialize, Row)]
+pub struct DailyTotals {
+    pub secid: String,
+    #[serde(with = "clickhouse::serde::time::date")]
+    pub day: Date,
+    pub numtrades: u64,
+    /// Traded lots
+    pub volume: u64,
+    /// Traded value in RUB
+    #[serde(with = "decimal64")]
+    pub value: Decimal,
+    #[serde(with = "decimal64")]
+    pub open: Decimal,
+    #[serde(with = "decimal64")]
+    pub low: Decimal,
+    #[serde(with = "decimal64")]
+    pub high: Decimal,
+    #[serde(with = "decimal64")]
+    pub close: Decimal,
+}
+
+/// # Reconciliation of a daily metric of a security
+///
+/// `ours` is computed from stored trades and `official` is taken from ISS daily history,
+/// either is empty if the security is missing on that side. Rows are stored in
+/// `reconciliation` table, a `ReplacingMergeTree(version)` queried with `FINAL`, so checking
+/// a day again replaces its results.
+#[derive(Debug, Clone, Serialize, Deserialize, Row)]
+pub struct Reconciliation {
+    // Identifiers
+    pub engine: String,
+    pub market: String,
+    pub boardid: String,
+    #[serde(with = "clickhouse::serde::time::date")]
+    pub day: Date,
+    pub secid: String,
+    pub metric: String,
+    // Main data
+    #[serde(with = "decimal64::option")]
+    pub ours: Option<Decimal>,
+    #[serde(with = "decimal64::option")]
+    pub official: Option<Decimal>,
+    /// Difference relative to official value
+    pub deviation: f64,
+    pub matched: bool,
+    #[serde(with = "clickhouse::serde::time::datetime")]
+    pub checked_at: OffsetDateTime,
+    pub version: u64,
+}
+
+/// # Reconciliation tolerances
+#[derive(Debug, Clone, Copy)]
+pub struct Tolerance {
+    /// Allowed relative deviation of trade and volume counts
+    pub counts: f64,
+    /// Allowed relative deviation of value and prices
+    pub prices: f64,
+}
+
+/// Daily metrics compared with ISS history
+struct Metrics {
+    numtrades: Option<Decimal>,
+    volume: Option<Decimal>,
+    value: Option<Decimal>,
+    vwap: Option<Decimal>,
+    open: Option<Decimal>,
+    low: Option<Decimal>,
+    high: Option<Decimal>,
+    close: Option<Decimal>,
+}
+
+/// Implementation for Metrics struct
+impl Metrics {
+    /// Metrics of stored trades, ISS counts volume in securities, so lots are multiplied by
+    /// `lotsize`. Volume and VWAP are empty if lot size of the security is unknown.
+    fn ours(totals: &DailyTotals, lotsize: Option<u32>) -> Self {
+        let volume = lotsize.map(|lotsize| Decimal::from(totals.volume) * Decimal::from(lotsize));
+        let vwap = volume
+            .filter(|volume| !volume.is_zero())
+            .map(|volume| totals.value / volume);
+        Metrics {
+            numtrades: Some(Decimal::from(totals.numtrades)),
+            volume,
+            value: Some(totals.value),
+            vwap,
+            open: Some(totals.open),
+            low: Some(totals.low),
+            high: Some(totals.high),
+            close: Some(totals.close),
+        }
+    }
+
+    fn official(history: &History) -> Self {
+        Metrics {
+            numtrades: Some(Decimal::from(history.numtrades)),
+            volume: Some(Decimal::from(history.volume)),
+            value: Some(history.value),
+            vwap: history.waprice,
+            open: history.open,
+            low: history.low,
+            high: history.high,
+            close: history.close,
+        }
+    }
+
+    fn none() -> Self {
+        Metrics {
+            numtrades: None,
+            volume: None,
+            value: None,
+            vwap: None,
+            open: None,
+            low: None,
+            high: None,
+            close: None,
+        }
+    }
+
+    /// Metrics by name, counts first
+    fn named(&self) -> [(&'static str, Option<Decimal>, bool); 8] {
+        [
+            ("numtrades", self.numtrades, true),
+            ("volume", self.volume, true),
+            ("value", self.value, false),
+            ("vwap", self.vwap, false),
+            ("open", self.open, false),
+            ("low", self.low, false),
+            ("high", self.high, false),
+            ("close", self.close, false),
+        ]
+    }
+}
+
+/// Relative deviation of `ours` from `official`
+fn deviation(ours: Option<Decimal>, official: Option<Decimal>) -> f64 {
+    match (ours, official) {
+        (Some(ours), Some(official)) if official.is_zero() => match ours.is_zero() {
+            true => 0.0,
+            false => f64::INFINITY,
+        },
+        (Some(ours), Some(official)) => ((ours - official) / official)
+            .abs()
+            .to_f64()
+            .unwrap_or(f64::INFINITY),
+        (None, None) => 0.0,
+        _ => f64::INFINITY,
+    }
+}
+
+/// # Reconcile daily totals of a board day against ISS history
+///
+/// Securities without trades in ISS history are skipped unless trades of them are stored.
+/// Lot sizes are taken from board `securities`.
+pub fn reconcile(
+    board: &Board,
+    day: Date,
+    ours: &[DailyTotals],
+    official: &[History],
+    securities: &[Security],
+    tolerance: &Tolerance,
+) -> Vec<Reconciliation> {
+    let lotsizes: HashMap<&str, u32> = securities
+        .iter()
+        .map(|s| (s.secid.as_str(), s.lotsize))
+        .collect();
+    let mut securities: BTreeMap<&str, (Metrics, Metrics)> = BTreeMap::new();
+    for totals in ours {
+        let lotsize = lotsizes.get(totals.secid.as_str()).copied();
+        securities.insert(
+            &totals.secid,
+            (Metrics::ours(totals, lotsize), Metrics::none()),
+        );
+    }
+    for history in official {
+        match securities.get_mut(history.secid.as_str()) {
+            Some((_, theirs)) => *theirs = Metrics::official(history),
+            None if history.numtrades > 0 => {
+                securities.insert(
+                    &history.secid,
+                    (Metrics::none(), Metrics::official(history)),
+                );
+            }
+            None => {}
+        }
+    }
+
+    let checked_at = OffsetDateTime::now_utc();
+    let version = checked_at.unix_timestamp_nanos() as u64;
+    let mut rows: Vec<Reconciliation> = Vec::new();
+    for (secid, (ours, official)) in &securities {
+        for ((metric, ours, count), (_, official, _)) in
+            ours.named().into_iter().zip(official.named())
+        {
+            let deviation = deviation(ours, official);
+            let allowed = match count {
+                true => tolerance.counts,
+                false => tolerance.prices,
+            };
+            rows.push(Reconciliation {
+                engine: board.engine.clone(),
+                market: board.market.clone(),
+                boardid: board.boardid.clone(),
+                day,
+                secid: secid.to_string(),
+                metric: metric.to_string(),
+                ours: ours.map(|v| v.round_dp(decimal64::DECIMAL_SCALE)),
+                official,
+                deviation,
+                matched: deviation <= allowed,
+                checked_at,
+                version,
+            });
+        }
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use time::macros::date;
+
+    const DAY: Date = date!(2024 - 03 - 01);
+
+    fn dec(value: &str) -> Decimal {
+        Decimal::from_str(value).unwrap()
+    }
+
+    fn board() -> Board {
+        Board {
+            engine: "stock".to_string(),
+            market: "shares".to_string(),
+            id: 57,
+            board_group_id: 57,
+            boardid: "TQBR".to_string(),
+            title: "Т+: Акции и ДР - безадрес.".to_string(),
+            is_traded: true,
+        }
+    }
+
+    fn security(secid: &str, lotsize: u32) -> Security {
+        Security {
+            engine: "stock".to_string(),
+            market: "shares".to_string(),
+            boardid: "TQBR".to_string(),
+            secid: secid.to_string(),
+            shortname: secid.to_string(),
+            decimals: 2,
+            lotsize,
+        }
+    }
+
+    /// Day of two SBER trades of 291.28 and 291.30, 10 and 20 lots of 10 shares
+    fn ours(secid: &str) -> DailyTotals {
+        DailyTotals {
+            secid: secid.to_string(),
+            day: DAY,
+            numtrades: 2,
+            volume: 30,
+            value: dec("87388"),
+            open: dec("291.28"),
+            low: dec("291.28"),
+            high: dec("291.30"),
+            close: dec("291.30"),
+        }
+    }
+
+    fn official(secid: &str) -> History {
+        History {
+            engine: "stock".to_string(),
+            market: "shares".to_string(),
+            secid: secid.to_string(),
+            boardid: "TQBR".to_string(),
+            tradedate: DAY,
+            numtrades: 2,
+            volume: 300,
+            value: dec("87388"),
+            open: Some(dec("291.28")),
+            low: Some(dec("291.28")),
+            high: Some(dec("291.30")),
+            close: Some(dec("291.30")),
+            waprice: Some(dec("291.29")),
+        }
+    }
+
+    const TOLERANCE: Tolerance = Tolerance {
+        counts: 0.0,
+        prices: 0.0001,
+    };
+
+    fn metric<'a>(rows: &'a [Reconciliation], secid: &str, metric: &str) -> &'a Reconciliation {
+        rows.iter()
+            .find(|r| r.secid == secid && r.metric == metric)
+            .unwrap()
+    }
+
+    #[test]
+    fn deviation_is_relative_to_official() {
+        assert_eq!(deviation(Some(dec("101")), Some(dec("100"))), 0.01);
+        assert_eq!(deviation(Some(dec("99")), Some(dec("100"))), 0.01);
+        assert_eq!(deviation(Some(dec("100")), Some(dec("100"))), 0.0);
+    }
+
+    #[test]
+    fn deviation_of_zero_official() {
+        assert_eq!(deviation(Some(Decimal::ZERO), Some(Decimal::ZERO)), 0.0);
+        assert_eq!(
+            deviation(Some(dec("1")), Some(Decimal::ZERO)),
+            f64::INFINITY
+        );
+    }
+
+    #[test]
+    fn deviation_of_missing_side() {
+        assert_eq!(deviation(Some(dec("1")), None), f64::INFINITY);
+        assert_eq!(deviation(None, Some(dec("1"))), f64::INFINITY);
+        assert_eq!(deviation(None, None), 0.0);
+    }
+
+    #[test]
+    fn reconcile_volume_in_securities() {
+        let rows = reconcile(
+            &board(),
+            DAY,
+            &[ours("SBER")],
+            &[official("SBER")],
+            &[security("SBER", 10)],
+            &TOLERANCE,
+        );
+
+        assert_eq!(rows.len(), 8);
+        assert!(rows.iter().all(|r| r.matched), "{:?}", rows);
+        assert_eq!(metric(&rows, "SBER", "volume").ours, Some(dec("300")));
+        assert_eq!(metric(&rows, "SBER", "vwap").ours, Some(dec("291.293333")));
+        assert!(rows
+            .iter()
+            .all(|r| r.engine == "stock" && r.boardid == "TQBR" && r.day == DAY));
+    }
+
+    #[test]
+    fn reconcile_unknown_lot_size() {
+        let rows = reconcile(
+            &board(),
+            DAY,
+            &[ours("SBER")],
+            &[official("SBER")],
+            &[],
+            &TOLERANCE,
+        );
+
+        let volume = metric(&rows, "SBER", "volume");
+        assert_eq!(volume.ours, None);
+        assert!(!volume.matched);
+        assert!(!metric(&rows, "SBER", "vwap").matched);
+        assert!(metric(&rows, "SBER", "value").matched);
+    }
+
+    #[test]
+    fn reconcile_security_missing_on_one_side() {
+        let mut idle = official("GAZP");
+        idle.numtrades = 0;
+        let rows = reconcile(
+            &board(),
+            DAY,
+            &[ours("SBER")],
+            &[official("LKOH"), idle],
+            &[security("SBER", 10), security("LKOH", 1)],
+            &TOLERANCE,
+        );
+
+        // Securities without trades on both sides are skipped
+        let mut secids: Vec<&str> = rows.iter().map(|r| r.secid.as_str()).collect();
+        secids.dedup();
+        assert_eq!(secids, ["LKOH", "SBER"]);
+
+        let sber = metric(&rows, "SBER", "numtrades");
+        assert_eq!((sber.ours, sber.official), (Some(dec("2")), None));
+        assert_eq!(sber.deviation, f64::INFINITY);
+        let lkoh = metric(&rows, "LKOH", "numtrades");
+        assert_eq!((lkoh.ours, lkoh.official), (None, Some(dec("2"))));
+        assert!(rows.iter().all(|r| !r.matched));
+    }
+}
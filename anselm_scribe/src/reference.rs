@@ -0,0 +1,233 @@
+use crate::models::{Board, Engine, Market};
+use clickhouse::Row;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use time::OffsetDateTime;
+
+/// # Versioned reference data record (slowly changing dimension type 2)
+///
+/// Every change of a record creates a new version valid from the time the change was observed.
+/// The previous version is closed by writing it again with `valid_to` set and a higher
+/// `version`, tables use `ReplacingMergeTree(version)` so the closed row replaces the open one.
+/// Records ISS no longer lists within their scope are closed the same way.
+///
+/// Tables must be queried with `FINAL`, current state is `WHERE valid_to IS NULL`:
+///
+/// ```sql
+/// -- Boards that were traded on 2024-03-01
+/// SELECT engine, market, boardid, title
+/// FROM md_moex.boards FINAL
+/// WHERE is_traded
+///     AND valid_from <= '2024-03-01 00:00:00'
+///     AND (valid_to IS NULL OR valid_to > '2024-03-01 00:00:00')
+/// ```
+pub trait VersionedRecord: Row + Serialize + DeserializeOwned + Clone {
+    /// Record as fetched from ISS
+    type Source;
+    /// Table name without database
+    const TABLE: &'static str;
+
+    /// Create new open version of source record
+    fn open(source: &Self::Source, valid_from: OffsetDateTime, version: u64) -> Self;
+
+    /// Key identifying the same record across versions
+    fn key(&self) -> String;
+
+    /// Key of source record
+    fn source_key(source: &Self::Source) -> String;
+
+    /// Scope of records ISS lists together, e.g. boards of a market
+    fn scope(&self) -> String;
+
+    /// Scope of source record
+    fn source_scope(source: &Self::Source) -> String;
+
+    /// Check whether source record differs from this version
+    fn changed(&self, source: &Self::Source) -> bool;
+
+    /// Close version
+    fn close(&mut self, valid_to: OffsetDateTime, version: u64);
+}
+
+/// Versioned Engine Record
+#[derive(Debug, Clone, Serialize, Deserialize, Row)]
+pub struct EngineVersion {
+    pub id: i32,
+    pub name: String,
+    pub title: String,
+    #[serde(with = "clickhouse::serde::time::datetime")]
+    pub valid_from: OffsetDateTime,
+    #[serde(with = "clickhouse::serde::time::datetime::option")]
+    pub valid_to: Option<OffsetDateTime>,
+    pub version: u64,
+}
+
+impl VersionedRecord for EngineVersion {
+    type Source = Engine;
+
+    const TABLE: &'static str = "engines";
+
+    fn open(e: &Engine, valid_from: OffsetDateTime, version: u64) -> Self {
+        Self {
+            id: e.id,
+            name: e.name.clone(),
+            title: e.title.clone(),
+            valid_from,
+            valid_to: None,
+            version,
+        }
+    }
+
+    fn key(&self) -> String {
+        format!("Engine '{}'", self.name)
+    }
+
+    fn source_key(e: &Engine) -> String {
+        format!("Engine '{}'", e.name)
+    }
+
+    fn scope(&self) -> String {
+        String::new()
+    }
+
+    fn source_scope(_e: &Engine) -> String {
+        String::new()
+    }
+
+    fn changed(&self, e: &Engine) -> bool {
+        self.id != e.id || self.title != e.title
+    }
+
+    fn close(&mut self, valid_to: OffsetDateTime, version: u64) {
+        self.valid_to = Some(valid_to);
+        self.version = version;
+    }
+}
+
+/// Versioned Market Record
+#[derive(Debug, Clone, Serialize, Deserialize, Row)]
+pub struct MarketVersion {
+    pub engine: String,
+    pub id: i32,
+    pub name: String,
+    pub title: String,
+    #[serde(with = "clickhouse::serde::time::datetime")]
+    pub valid_from: OffsetDateTime,
+    #[serde(with = "clickhouse::serde::time::datetime::option")]
+    pub valid_to: Option<OffsetDateTime>,
+    pub version: u64,
+}
+
+impl VersionedRecord for MarketVersion {
+    type Source = Market;
+
+    const TABLE: &'static str = "markets";
+
+    fn open(m: &Market, valid_from: OffsetDateTime, version: u64) -> Self {
+        Self {
+            engine: m.engine.clone(),
+            id: m.id,
+            name: m.name.clone(),
+            title: m.title.clone(),
+            valid_from,
+            valid_to: None,
+            version,
+        }
+    }
+
+    fn key(&self) -> String {
+        format!("Market '{}' for Engine '{}'", self.name, self.engine)
+    }
+
+    fn source_key(m: &Market) -> String {
+        format!("Market '{}' for Engine '{}'", m.name, m.engine)
+    }
+
+    fn scope(&self) -> String {
+        format!("Engine '{}'", self.engine)
+    }
+
+    fn source_scope(m: &Market) -> String {
+        format!("Engine '{}'", m.engine)
+    }
+
+    fn changed(&self, m: &Market) -> bool {
+        self.id != m.id || self.title != m.title
+    }
+
+    fn close(&mut self, valid_to: OffsetDateTime, version: u64) {
+        self.valid_to = Some(valid_to);
+        self.version = version;
+    }
+}
+
+/// Versioned Board Record
+#[derive(Debug, Clone, Serialize, Deserialize, Row)]
+pub struct BoardVersion {
+    pub engine: String,
+    pub market: String,
+    pub id: i32,
+    pub board_group_id: i32,
+    pub boardid: String,
+    pub title: String,
+    pub is_traded: bool,
+    #[serde(with = "clickhouse::serde::time::datetime")]
+    pub valid_from: OffsetDateTime,
+    #[serde(with = "clickhouse::serde::time::datetime::option")]
+    pub valid_to: Option<OffsetDateTime>,
+    pub version: u64,
+}
+
+impl VersionedRecord for BoardVersion {
+    type Source = Board;
+
+    const TABLE: &'static str = "boards";
+
+    fn open(b: &Board, valid_from: OffsetDateTime, version: u64) -> Self {
+        Self {
+            engine: b.engine.clone(),
+            market: b.market.clone(),
+            id: b.id,
+            board_group_id: b.board_group_id,
+            boardid: b.boardid.clone(),
+            title: b.title.clone(),
+            is_traded: b.is_traded,
+            valid_from,
+            valid_to: None,
+            version,
+        }
+    }
+
+    fn key(&self) -> String {
+        format!(
+            "Board '{}' for Market '{}' for Engine '{}'",
+            self.boardid, self.market, self.engine
+        )
+    }
+
+    fn source_key(b: &Board) -> String {
+        format!(
+            "Board '{}' for Market '{}' for Engine '{}'",
+            b.boardid, b.market, b.engine
+        )
+    }
+
+    fn scope(&self) -> String {
+        format!("Market '{}' for Engine '{}'", self.market, self.engine)
+    }
+
+    fn source_scope(b: &Board) -> String {
+        format!("Market '{}' for Engine '{}'", b.market, b.engine)
+    }
+
+    fn changed(&self, b: &Board) -> bool {
+        self.id != b.id
+            || self.board_group_id != b.board_group_id
+            || self.title != b.title
+            || self.is_traded != b.is_traded
+    }
+
+    fn close(&mut self, valid_to: OffsetDateTime, version: u64) {
+        self.valid_to = Some(valid_to);
+        self.version = version;
+    }
+}
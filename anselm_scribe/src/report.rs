@@ -0,0 +1,246 @@
+use crate::config::{Config, ReportArgs, ReportFormat};
+use crate::models::ISS_DATE;
+use serde::de::{MapAccess, Visitor};
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use std::fmt;
+use time::macros::date;
+use time::{Duration, OffsetDateTime};
+
+/// # Analytics report
+///
+/// Queries from `sql/` are embedded into the binary. They use Clickhouse query parameters
+/// instead of hard-coded names, so the same files still run with clickhouse-client:
+///
+/// ```sh
+/// clickhouse-client --param_db=md_moex --param_table=trades --queries-file sql/table_rows.sql
+/// ```
+///
+/// Parameters:
+/// - `db` configured Clickhouse database
+/// - `table` queried table, `--table` or report default
+/// - `from`, `till` date range `[from, till)`, everything until tomorrow by default
+pub struct Report {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub table: &'static str,
+    pub sql: &'static str,
+}
+
+/// All reports ordered by name
+pub const REPORTS: &[Report] = &[
+    Report {
+        name: "db-compression-stats",
+        description: "Compression of every table in database",
+        table: "",
+        sql: include_str!("../../sql/db_compression_stats.sql"),
+    },
+    Report {
+        name: "ingest-runs",
+        description: "ISS requests, trades and timings of ingestion runs per board",
+        table: "ingest_log",
+        sql: include_str!("../../sql/ingest_runs.sql"),
+    },
+    Report {
+        name: "reconciliation-summary",
+        description: "Mismatches of trades against ISS daily history per day",
+        table: "reconciliation",
+        sql: include_str!("../../sql/reconciliation_summary.sql"),
+    },
+    Report {
+        name: "table-disk-size",
+        description: "Disk size of table",
+        table: "trades",
+        sql: include_str!("../../sql/table_disk_size.sql"),
+    },
+    Report {
+        name: "table-rows",
+        description: "Total number of table rows",
+        table: "trades",
+        sql: include_str!("../../sql/table_rows.sql"),
+    },
+    Report {
+        name: "volume-date-range",
+        description: "Traded volume per day within date range",
+        table: "trades",
+        sql: include_str!("../../sql/volume_date_range.sql"),
+    },
+];
+
+/// Find report by name
+pub fn find_report(name: &str) -> Option<&'static Report> {
+    REPORTS.iter().find(|r| r.name == name)
+}
+
+/// Implementation for Report struct
+impl Report {
+    /// Query parameters of report
+    pub fn params(
+        &self,
+        conf: &Config,
+        args: &ReportArgs,
+    ) -> Result<Vec<(&'static str, String)>, Box<dyn std::error::Error>> {
+        let from = args.from.unwrap_or(date!(1970 - 01 - 01));
+        let till = match args.till {
+            Some(till) => till,
+            None => (OffsetDateTime::now_utc() + Duration::days(1)).date(),
+        };
+        Ok(vec![
+            ("db", conf.ch_db.clone()),
+            (
+                "table",
+                args.table.as_deref().unwrap_or(self.table).to_string(),
+            ),
+            ("from", from.format(ISS_DATE)?),
+            ("till", till.format(ISS_DATE)?),
+        ])
+    }
+}
+
+/// Report row with columns in query order
+struct ReportRow(Vec<(String, Value)>);
+
+impl<'de> Deserialize<'de> for ReportRow {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct RowVisitor;
+
+        impl<'de> Visitor<'de> for RowVisitor {
+            type Value = ReportRow;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a JSON object")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut cells = Vec::with_capacity(map.size_hint().unwrap_or(0));
+                while let Some(cell) = map.next_entry()? {
+                    cells.push(cell);
+                }
+                Ok(ReportRow(cells))
+            }
+        }
+
+        deserializer.deserialize_map(RowVisitor)
+    }
+}
+
+/// Report row serialized as JSON object with columns in query order
+struct JsonRow<'a>(&'a [String], &'a [Value]);
+
+impl Serialize for JsonRow<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (column, value) in self.0.iter().zip(self.1) {
+            map.serialize_entry(column, value)?;
+        }
+        map.end()
+    }
+}
+
+/// # Report result
+#[derive(Debug, Clone, Default)]
+pub struct ReportTable {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+/// Implementation for ReportTable struct
+impl ReportTable {
+    /// Collect rows formatted by Clickhouse as `JSONEachRow`
+    pub fn from_json_rows(rows: &[String]) -> Result<Self, serde_json::Error> {
+        let mut table = ReportTable::default();
+        for row in rows {
+            let ReportRow(cells) = serde_json::from_str(row)?;
+            if table.columns.is_empty() {
+                table.columns = cells.iter().map(|(name, _)| name.clone()).collect();
+            }
+            table
+                .rows
+                .push(cells.into_iter().map(|(_, value)| value).collect());
+        }
+        Ok(table)
+    }
+
+    /// Render report in format
+    pub fn render(&self, format: ReportFormat) -> Result<String, Box<dyn std::error::Error>> {
+        match format {
+            ReportFormat::Table => Ok(self.render_table()),
+            ReportFormat::Csv => {
+                let mut writer = csv::Writer::from_writer(Vec::new());
+                writer.write_record(&self.columns)?;
+                for row in &self.rows {
+                    writer.write_record(row.iter().map(cell))?;
+                }
+                Ok(String::from_utf8(writer.into_inner()?)?)
+            }
+            ReportFormat::Json => {
+                let rows: Vec<JsonRow> = self
+                    .rows
+                    .iter()
+                    .map(|row| JsonRow(&self.columns, row))
+                    .collect();
+                Ok(serde_json::to_string_pretty(&rows)? + "\n")
+            }
+        }
+    }
+
+    /// Aligned text table, numbers are aligned right
+    fn render_table(&self) -> String {
+        let cells: Vec<Vec<String>> = self
+            .rows
+            .iter()
+            .map(|row| row.iter().map(cell).collect())
+            .collect();
+        let widths: Vec<usize> = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, column)| {
+                cells
+                    .iter()
+                    .map(|row| row[i].chars().count())
+                    .chain([column.chars().count()])
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+
+        let mut out = String::new();
+        let header: Vec<String> = self
+            .columns
+            .iter()
+            .zip(&widths)
+            .map(|(column, width)| format!("{:<width$}", column, width = width))
+            .collect();
+        out.push_str(header.join("  ").trim_end());
+        out.push('\n');
+        let rule: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+        out.push_str(&rule.join("  "));
+        out.push('\n');
+
+        for (row, values) in cells.iter().zip(&self.rows) {
+            let line: Vec<String> = row
+                .iter()
+                .zip(values)
+                .zip(&widths)
+                .map(|((text, value), width)| match value {
+                    Value::Number(_) => format!("{:>width$}", text, width = width),
+                    _ => format!("{:<width$}", text, width = width),
+                })
+                .collect();
+            out.push_str(line.join("  ").trim_end());
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// Text of report cell, strings without quotes
+fn cell(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
@@ -1,161 +1,818 @@
-use crate::config::Config;
+use crate::checkpoint::Checkpoint;
+use crate::config::{
+    BackfillArgs, Config, CrawlArgs, Dataset, FollowArgs, MigrateAction, ProveArgs, ReconcileArgs,
+    ReportArgs, SealArgs, Selection, SignArgs, VerifyArgs, VerifyCheck, VerifyProofArgs,
+};
 use crate::db::ClickhouseDatabase;
-use crate::models::{get_boards, get_engines, get_markets, Board, Engine, Market, Trade};
-use std::time::Instant;
-use tokio::fs::File;
-use tokio::io::AsyncWriteExt;
+use crate::disk::write_file_atomic;
+use crate::gaps::{detect_gaps, repair_gap, GapStatus};
+use crate::ingest::{new_run_id, stamp_trades, IngestLog};
+use crate::iss::IssClient;
+use crate::ledger::{self, Ledger};
+use crate::merkle::to_hex;
+use crate::metrics;
+use crate::migrations::{latest_version, MIGRATIONS};
+use crate::mirror::open_store;
+use crate::models::{
+    get_boards, get_engines, get_markets, get_securities, Board, FailedPage, Trade, TradesPage,
+    MOSCOW_OFFSET, TRADES_PAGE_SIZE,
+};
+use crate::proof::{self, InclusionProof};
+use crate::reconcile::{reconcile, Reconciliation};
+use crate::report::{find_report, ReportTable};
+use crate::shutdown::Shutdown;
+use crate::sink::MarketDataSink;
+use futures::stream::FuturesOrdered;
+use futures::{FutureExt, StreamExt};
+use std::time::{Duration, Instant};
+use time::OffsetDateTime;
+use tokio::sync::mpsc;
+use tracing::{field, info, info_span, warn, Instrument};
 
-/// # Base runner for running on a single thread
-pub async fn base_runner(
+/// # Crawl runner for crawling selected datasets of selected boards once
+///
+/// Stops after the board being crawled once `shutdown` is requested
+pub async fn crawl_runner(
     conf: &Config,
-    db: &Option<ClickhouseDatabase>,
+    args: &CrawlArgs,
+    sinks: &[Box<dyn MarketDataSink>],
+    shutdown: &Shutdown,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let engines = get_engines().await?;
-    for chunk in engines.chunks(conf.chunks) {
-        // Save Engines to db if defined
-        if let Some(db) = db {
-            db.insert_engines(chunk).await?;
-        }
+    let iss = IssClient::new(conf);
 
-        // FIX: Implement trades format parsing for all types of Engines, Markets and Boards
-        let filtered: Vec<&Engine> = engines.iter().filter(|p| p.name == "stock").collect();
+    // Reference data is saved while boards are selected
+    let reference_sinks = if args.datasets.contains(&Dataset::Reference) {
+        sinks
+    } else {
+        &[]
+    };
+    let boards = select_boards(conf, &iss, &args.selection, reference_sinks).await?;
 
-        // Loop through all Engines and run them
-        for engine in filtered {
-            run_engine(conf, db, engine).await?;
+    if args.datasets.contains(&Dataset::Trades) {
+        let run_id = new_run_id();
+        info!(%run_id, "Run started");
+        for board in &boards {
+            if shutdown.requested() {
+                break;
+            }
+            run_board(conf, &iss, sinks, board, 0, &run_id, shutdown).await?;
         }
     }
 
     Ok(())
 }
 
-/// # Run Engine
-async fn run_engine(
+/// # Follow runner for crawling new trades of selected boards until stopped
+///
+/// Every board keeps the start of its next trades page between polls. ISS serves trades of
+/// the current trading day only, so starts are reset once the Moscow date changes. All polls
+/// share the run id of the process.
+///
+/// Starts are resumed from the checkpoint at `checkpoint_path` saved on the same trading day
+/// and saved there after every poll. Returns once `shutdown` is requested and the boards being
+/// polled are written.
+pub async fn follow_runner(
     conf: &Config,
-    db: &Option<ClickhouseDatabase>,
-    engine: &Engine,
+    args: &FollowArgs,
+    sinks: &[Box<dyn MarketDataSink>],
+    shutdown: &Shutdown,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let markets = get_markets(&engine.name).await?;
-    for chunk in markets.chunks(conf.chunks) {
-        // Save Markets to db if defined
-        if let Some(db) = db {
-            db.insert_markets(chunk).await?;
+    let iss = IssClient::new(conf);
+    let boards = select_boards(conf, &iss, &args.selection, sinks).await?;
+    let mut day = OffsetDateTime::now_utc().to_offset(MOSCOW_OFFSET).date();
+    let mut starts: Vec<i32> = match Checkpoint::load(&conf.checkpoint_path).await? {
+        Some(checkpoint) => {
+            info!(path = %conf.checkpoint_path, run_id = %checkpoint.run_id, "Checkpoint loaded");
+            checkpoint.starts(day, &boards)
         }
+        None => vec![0; boards.len()],
+    };
+    let run_id = new_run_id();
+    info!(%run_id, "Run started");
 
-        // FIX: Implement trades format parsing for all types of Engines, Markets and Boards
-        let filtered: Vec<&Market> = markets
-            .iter()
-            .filter(|p| p.engine == "stock" && p.name == "shares")
-            .collect();
+    while !shutdown.requested() {
+        let today = OffsetDateTime::now_utc().to_offset(MOSCOW_OFFSET).date();
+        if today != day {
+            info!(%today, "Trading day changed, restarting boards");
+            starts.iter_mut().for_each(|start| *start = 0);
+            day = today;
+        }
 
-        // Loop through all Markets and run them
-        for market in filtered {
-            run_market(conf, db, market).await?;
+        for (board, start) in boards.iter().zip(starts.iter_mut()) {
+            if shutdown.requested() {
+                break;
+            }
+            *start = run_board(conf, &iss, sinks, board, *start, &run_id, shutdown).await?;
+        }
+        Checkpoint::save(&conf.checkpoint_path, &run_id, day, &boards, &starts).await?;
+
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_secs(args.interval)) => {}
+            _ = shutdown.wait() => {}
         }
     }
 
+    info!(path = %conf.checkpoint_path, "Checkpoint saved, follow stopped");
     Ok(())
 }
 
-/// # Run Market
-async fn run_market(
+/// # Backfill runner for loading daily history of selected boards into Clickhouse
+///
+/// ISS serves trades of the current trading day only, older days are backfilled from daily
+/// results of every security. Stops after the day being saved once `shutdown` is requested.
+pub async fn backfill_runner(
     conf: &Config,
-    db: &Option<ClickhouseDatabase>,
-    market: &Market,
+    args: &BackfillArgs,
+    db: &ClickhouseDatabase,
+    shutdown: &Shutdown,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let boards = get_boards(&market.engine, &market.name).await?;
-    for chunk in boards.chunks(conf.chunks) {
-        // Save Board market data
-        if let Some(db) = db {
-            db.insert_boards(chunk).await?;
+    if args.from > args.to {
+        return Err(format!("Backfill range {} - {} is empty", args.from, args.to).into());
+    }
+
+    let iss = IssClient::new(conf);
+    let boards = select_boards(conf, &iss, &args.selection, &[]).await?;
+
+    for board in &boards {
+        let mut date = args.from;
+        while date <= args.to {
+            if shutdown.requested() {
+                warn!(boardid = %board.boardid, %date, "Backfill stopped");
+                return Ok(());
+            }
+            let time_date: Instant = Instant::now();
+            let records = board.fetch_history(&iss, date).await?;
+            if !records.is_empty() {
+                db.insert_history(&records).await?;
+            }
+            info!(
+                records = records.len(),
+                engine = %board.engine,
+                market = %board.market,
+                boardid = %board.boardid,
+                %date,
+                time_ms = time_date.elapsed().as_secs_f64() * 1e3,
+                "History saved"
+            );
+
+            date = match date.next_day() {
+                Some(next) => next,
+                None => break,
+            };
+        }
+    }
+
+    Ok(())
+}
+
+/// # Reconcile runner for checking daily trade totals of selected boards against ISS history
+///
+/// ISS history of every checked day is also saved to `history`. Returns `false` if any
+/// metric deviates beyond tolerance. Once `shutdown` is requested it stops after the day being
+/// reconciled and fails, as remaining days are not checked.
+pub async fn reconcile_runner(
+    conf: &Config,
+    args: &ReconcileArgs,
+    db: &ClickhouseDatabase,
+    shutdown: &Shutdown,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let iss = IssClient::new(conf);
+    let boards = select_boards(conf, &iss, &args.selection, &[]).await?;
+    let (from, till) = args.days.range();
+    let tolerance = args.tolerance();
+    let mut matched = true;
+
+    for board in &boards {
+        let securities = get_securities(&iss, board).await?;
+        let mut date = from;
+        while date < till {
+            if shutdown.requested() {
+                return Err(format!(
+                    "Reconcile stopped before {} of board {}",
+                    date, board.boardid
+                )
+                .into());
+            }
+            let official = board.fetch_history(&iss, date).await?;
+            if !official.is_empty() {
+                db.insert_history(&official).await?;
+            }
+            let ours = db
+                .daily_trade_totals(
+                    &board.engine,
+                    &board.market,
+                    &board.boardid,
+                    date.midnight().assume_offset(MOSCOW_OFFSET),
+                    date.midnight().assume_offset(MOSCOW_OFFSET) + time::Duration::days(1),
+                )
+                .await?;
+
+            let rows = reconcile(board, date, &ours, &official, &securities, &tolerance);
+            db.insert_reconciliation(&rows).await?;
+
+            // Summary of board day, rows are ordered by security
+            let mut checked: Vec<&str> = rows.iter().map(|r| r.secid.as_str()).collect();
+            checked.dedup();
+            let mismatches: Vec<&Reconciliation> = rows.iter().filter(|r| !r.matched).collect();
+            let mut securities: Vec<&str> = mismatches.iter().map(|r| r.secid.as_str()).collect();
+            securities.dedup();
+            info!(
+                securities = checked.len(),
+                engine = %board.engine,
+                market = %board.market,
+                boardid = %board.boardid,
+                %date,
+                mismatched_securities = securities.len(),
+                mismatched_metrics = mismatches.len(),
+                "Board day reconciled"
+            );
+            for row in &mismatches {
+                warn!(
+                    boardid = %board.boardid,
+                    %date,
+                    secid = %row.secid,
+                    metric = %row.metric,
+                    ours = %row.ours.map_or("-".to_string(), |v| v.normalize().to_string()),
+                    official = %row
+                        .official
+                        .map_or("-".to_string(), |v| v.normalize().to_string()),
+                    deviation = row.deviation,
+                    "Reconciliation mismatch"
+                );
+            }
+            matched &= mismatches.is_empty();
+
+            date = match date.next_day() {
+                Some(next) => next,
+                None => break,
+            };
+        }
+    }
+
+    Ok(matched)
+}
+
+/// # Select traded boards matching selection
+///
+/// Engines, markets and boards passed on the way are saved to `sinks`. Every listing is written
+/// as a single batch, so versioned sinks can close records ISS no longer lists. Securities of
+/// selected boards are fetched only when a sink wants them, see
+/// `MarketDataSink::wants_securities`.
+async fn select_boards(
+    conf: &Config,
+    iss: &IssClient,
+    selection: &Selection,
+    sinks: &[Box<dyn MarketDataSink>],
+) -> Result<Vec<Board>, Box<dyn std::error::Error>> {
+    let mut selected: Vec<Board> = Vec::new();
+
+    let engines = get_engines(iss).await?;
+    for sink in sinks {
+        sink.write_engines(&engines).await?;
+    }
+
+    for engine in engines.iter().filter(|e| selection.engine(&e.name)) {
+        let markets = get_markets(iss, &engine.name).await?;
+        for sink in sinks {
+            sink.write_markets(&markets).await?;
         }
 
-        // FIX: Implement trades format parsing for all types of Engines, Markets and Boards
-        let filtered: Vec<&Board> = boards
-            .iter()
+        for market in markets.iter().filter(|m| selection.market(&m.name)) {
+            let boards = get_boards(iss, &market.engine, &market.name).await?;
+            for sink in sinks {
+                sink.write_boards(&boards).await?;
+            }
+
             // Note: is_traded is necessary
-            .filter(|p| {
-                p.is_traded && p.engine == "stock" && p.market == "shares" && p.boardid == "TQBR"
-            })
-            .collect();
+            selected.extend(
+                boards
+                    .into_iter()
+                    .filter(|b| b.is_traded && selection.board(&b.boardid)),
+            );
+        }
+    }
 
-        // Loop through all Boards and run them
-        for board in filtered {
-            run_board(conf, db, board).await?;
+    if sinks.iter().any(|s| s.wants_securities()) {
+        for board in &selected {
+            let securities = get_securities(iss, board).await?;
+            for chunk in securities.chunks(conf.chunks) {
+                for sink in sinks {
+                    sink.write_securities(chunk).await?;
+                }
+            }
         }
     }
-    Ok(())
+
+    for sink in sinks {
+        sink.flush().await?;
+    }
+    info!(boards = selected.len(), "Boards selected");
+    Ok(selected)
 }
 
+/// Trades page with the start and page size it was requested with
+type FetchedPage = (i32, i32, Result<TradesPage, FailedPage>);
+
+/// Trades of a page with its start and ingestion log record, or log record of the failed page
+type ParsedPage = Result<(i32, Vec<Trade>, IngestLog), (IngestLog, Box<dyn std::error::Error>)>;
+
 /// # Run Board
+///
+/// Trades are gathered by a pipeline of three stages connected with bounded channels, so ISS
+/// requests, parsing and writing to sinks run concurrently:
+///
+/// - fetch: requests up to `md_prefetch` pages ahead of the page being parsed
+/// - parse: parses raw pages on the blocking thread pool, stops on the first empty page
+/// - write: writes parsed pages to every sink in order
+///
+/// Pages are prefetched at offsets one page size apart. The page size starts at
+/// `TRADES_PAGE_SIZE` and follows the number of trades ISS actually returns: once a page holds
+/// fewer or more trades than expected, e.g. because ISS caps `limit` or the last page was
+/// reached, pages prefetched at other offsets are dropped and fetching resumes right after the
+/// received trades with the received count as page size.
+///
+/// Every channel holds at most `md_pipeline_buffer` pages which keeps memory bounded when
+/// sinks are slower than ISS. A board is gathered about as fast as its slowest stage, so the
+/// gain over fetching and writing in turn is largest when ISS latency is close to sink write
+/// time.
+///
+/// Trades are gathered from `start` and stamped with `run_id`, every page is written to the
+/// ingestion log of sinks. A page that fails to be fetched or parsed is logged with its error
+/// before gathering stops. Stages run within the `board` span, every page gets its own
+/// `fetch_page`, `parse_page` and `write_page` span carrying its timings. Returns start of the
+/// page following the saved trades.
+///
+/// Once `shutdown` is requested no new pages are fetched and requests in flight are dropped,
+/// pages fetched before are still parsed and written in order.
 async fn run_board(
     conf: &Config,
-    db: &Option<ClickhouseDatabase>,
+    iss: &IssClient,
+    sinks: &[Box<dyn MarketDataSink>],
     board: &Board,
-) -> Result<(), Box<dyn std::error::Error>> {
-    // Insert trades for each board
-    let mut start: i32 = 0;
-    let mut loop_num: i32 = 1;
-    'outer: loop {
-        let trades = board
-            .fetch_trades(&board.engine, &board.market, start)
+    start: i32,
+    run_id: &str,
+    shutdown: &Shutdown,
+) -> Result<i32, Box<dyn std::error::Error>> {
+    let span = info_span!(
+        "board",
+        %run_id,
+        engine = %board.engine,
+        market = %board.market,
+        boardid = %board.boardid
+    );
+    let buffer = conf.md_pipeline_buffer.max(1);
+    let (page_tx, page_rx) = mpsc::channel::<FetchedPage>(buffer);
+    let (trades_tx, mut trades_rx) = mpsc::channel::<ParsedPage>(buffer);
+    // Start and page size fetching resumes from, sent by the parse stage
+    let (resume_tx, resume_rx) = mpsc::unbounded_channel::<(i32, i32)>();
+
+    // Fetch pages ahead, pages are returned in order of start. Errors are passed on to the
+    // parse stage since pages prefetched past the last page may fail, e.g. when replaying
+    let fetch = async {
+        let (page_tx, mut resume_rx) = (page_tx, resume_rx);
+        let prefetch = conf.md_prefetch.max(1);
+        let (mut next, mut page_size) = (start, TRADES_PAGE_SIZE);
+        let mut pages = FuturesOrdered::new();
+
+        loop {
+            while pages.len() < prefetch {
+                let at = next;
+                pages.push_back(
+                    board
+                        .fetch_trades_page(iss, at)
+                        .map(move |page| (at, page_size, page)),
+                );
+                next += page_size;
+            }
+
+            tokio::select! {
+                resume = resume_rx.recv() => match resume {
+                    Some((at, size)) => {
+                        // Requests in flight are dropped
+                        pages = FuturesOrdered::new();
+                        (next, page_size) = (at, size);
+                    }
+                    None => break,
+                },
+                Some(page) = pages.next() => {
+                    if page_tx.send(page).await.is_err() {
+                        break;
+                    }
+                }
+                _ = shutdown.wait() => break,
+            }
+        }
+        Ok::<(), Box<dyn std::error::Error>>(())
+    };
+
+    // Parse pages in order of start until the first empty page
+    let parse = async {
+        // Channels are owned, so fetching stops once parsing stopped
+        let (mut page_rx, trades_tx, resume_tx) = (page_rx, trades_tx, resume_tx);
+        let mut expected = start;
+        while let Some((at, page_size, page)) = page_rx.recv().await {
+            // Drop pages prefetched at offsets before fetching was resumed
+            if at != expected {
+                continue;
+            }
+
+            let log = IngestLog::new(run_id, &board.engine, &board.market, &board.boardid, None);
+            let page = match page {
+                Ok(page) => page,
+                Err(failed) => {
+                    let log = log
+                        .request(failed.start, &failed.meta, failed.time_req)
+                        .error(&failed);
+                    let _ = trades_tx.send(Err((log, failed.into()))).await;
+                    break;
+                }
+            };
+            let (engine, market, boardid) = (
+                board.engine.clone(),
+                board.market.clone(),
+                board.boardid.clone(),
+            );
+            // Spans are not passed to the blocking thread pool, page span is entered there
+            let page_span = info_span!(
+                "parse_page",
+                start = at,
+                trades = field::Empty,
+                first_tradeid = field::Empty,
+                last_tradeid = field::Empty,
+                fetch_ms = field::Empty,
+                parse_ms = field::Empty
+            );
+            let parse_span = page_span.clone();
+            let (trades, page, time_parse) = tokio::task::spawn_blocking(move || {
+                parse_span.in_scope(|| {
+                    let time_parse = Instant::now();
+                    let trades = Board::parse_trades_page(&engine, &market, &boardid, &page);
+                    (trades, page, time_parse.elapsed())
+                })
+            })
             .await?;
+            let mut trades = match trades {
+                Ok(trades) => trades,
+                Err(e) => {
+                    let log = log.trades_page(&page, &[], time_parse).error(&e);
+                    let _ = trades_tx
+                        .send(Err((log, e as Box<dyn std::error::Error>)))
+                        .await;
+                    break;
+                }
+            };
+            if let (Some(first), Some(last)) = (trades.first(), trades.last()) {
+                page_span.record("first_tradeid", first.tradeid);
+                page_span.record("last_tradeid", last.tradeid);
+            }
+            stamp_trades(&mut trades, run_id);
+            let log = log.trades_page(&page, &trades, time_parse);
 
-        if trades.is_empty() {
-            println!(
-                "Board '{}' STOP Gathering: Market '{}' for Engine '{}' loop {}",
-                board.boardid, board.engine, board.market, loop_num
+            let received = trades.len() as i32;
+            let last = received == 0;
+            expected = at + received;
+            if !last && received != page_size {
+                let _ = resume_tx.send((expected, received));
+            }
+            if trades_tx.send(Ok((at, trades, log))).await.is_err() || last {
+                break;
+            }
+        }
+        Ok::<(), Box<dyn std::error::Error>>(())
+    };
+
+    // Save market data to every sink
+    let write = async {
+        let mut loop_num: i32 = 1;
+        let mut next_start = start;
+        while let Some(parsed) = trades_rx.recv().await {
+            let (start, trades, log) = match parsed {
+                Ok(parsed) => parsed,
+                Err((log, e)) => {
+                    for sink in sinks {
+                        sink.write_ingest_log(std::slice::from_ref(&log)).await?;
+                    }
+                    return Err(e);
+                }
+            };
+            // Empty pages are only logged
+            if trades.is_empty() {
+                for sink in sinks {
+                    sink.write_ingest_log(std::slice::from_ref(&log)).await?;
+                }
+                metrics::board_ingested(&board.engine, &board.market, &board.boardid, None);
+                continue;
+            }
+
+            let page_span = info_span!(
+                "write_page",
+                start,
+                trades = trades.len(),
+                sinks = sinks.len(),
+                write_ms = field::Empty
             );
-            break 'outer;
-        }
-
-        // Save market data
-        let time_trade: Instant = Instant::now();
-        if let Some(db) = db {
-            // Insert trades into the database
-            let mut chunk_count = 0;
-            for chunk in trades.chunks(conf.chunks) {
-                db.insert_trades(chunk).await?;
-                chunk_count += 1;
-                println!(
-                    "Trades[{}] chunk saved to DB: chunk {} chunk_size {} time {:.2?}",
-                    chunk.len(),
-                    chunk_count,
-                    conf.chunks,
-                    time_trade.elapsed()
-                );
+            let time_trade: Instant = Instant::now();
+            async {
+                for sink in sinks {
+                    sink.write_trades(&trades).await?;
+                    sink.write_ingest_log(std::slice::from_ref(&log)).await?;
+                    if !sink.counts_inserted() {
+                        metrics::rows_inserted(
+                            &board.engine,
+                            &board.market,
+                            &board.boardid,
+                            sink.name(),
+                            trades.len(),
+                        );
+                    }
+                }
+                Ok::<(), Box<dyn std::error::Error>>(())
             }
-        } else {
-            println!("Not inserting trades into db");
-            // Otherwise Save market data as JSON to disk
-            let file_path = format!(
-                "{}/{}-{}-{}.json",
-                conf.md_path, board.engine, board.market, loop_num
+            .instrument(page_span.clone())
+            .await?;
+            let write_ms = time_trade.elapsed().as_secs_f64() * 1e3;
+            page_span.record("write_ms", write_ms);
+            metrics::board_ingested(
+                &board.engine,
+                &board.market,
+                &board.boardid,
+                trades.last().map(|t| t.tradetime),
+            );
+            info!(parent: &page_span, loop_num, "Trades saved");
+            loop_num += 1;
+            next_start = start + trades.len() as i32;
+        }
+
+        info!(loop_num, next_start, "Board gathering stopped");
+        Ok::<i32, Box<dyn std::error::Error>>(next_start)
+    };
+
+    async {
+        let (_, _, next_start) = tokio::try_join!(fetch, parse, write)?;
+
+        // Flush board market data
+        for sink in sinks {
+            sink.flush().await?;
+        }
+        Ok(next_start)
+    }
+    .instrument(span)
+    .await
+}
+
+/// # Migrate runner for managing Clickhouse schema
+pub async fn migrate_runner(
+    db: &ClickhouseDatabase,
+    action: &MigrateAction,
+) -> Result<(), Box<dyn std::error::Error>> {
+    db.init_database().await?;
+
+    match action {
+        MigrateAction::Up => {
+            let applied = db.migrate_up().await?;
+            info!(
+                migrations = applied.len(),
+                schema_version = db.schema_version().await?,
+                "Migrations applied"
             );
-            save_trades_to_file(&file_path, &trades).await?;
+        }
+        MigrateAction::Status => {
+            let applied = db.applied_migrations().await?;
+            for migration in MIGRATIONS {
+                match applied.iter().find(|a| a.version == migration.version) {
+                    Some(a) => println!(
+                        "{:04} {:<24} applied {}",
+                        migration.version, migration.name, a.applied_at
+                    ),
+                    None => println!("{:04} {:<24} pending", migration.version, migration.name),
+                }
+            }
             println!(
-                "Trades[{}] saved to file: {} loop {} start {} time {:.2?}",
-                trades.len(),
-                file_path,
-                loop_num,
-                start,
-                time_trade.elapsed()
+                "Schema version {} expected {}",
+                db.schema_version().await?,
+                latest_version()
             );
         }
+    }
 
-        start += trades.len() as i32;
-        loop_num += 1;
+    Ok(())
+}
+
+/// # Status runner for showing schema version and stored trades per board
+///
+/// Returns `false` if database schema is older than expected
+pub async fn status_runner(db: &ClickhouseDatabase) -> Result<bool, Box<dyn std::error::Error>> {
+    db.init_database().await?;
+
+    let version = db.schema_version().await?;
+    let pending = MIGRATIONS.iter().filter(|m| m.version > version).count();
+    println!(
+        "Schema version {} expected {} pending {}",
+        version,
+        latest_version(),
+        pending
+    );
+    if pending > 0 {
+        return Ok(false);
     }
+
+    for board in db.board_status().await? {
+        println!(
+            "{:<8} {:<12} {:<8} trades {:>12} last trade {}",
+            board.engine, board.market, board.boardid, board.trades, board.last_trade
+        );
+    }
+
+    Ok(true)
+}
+
+/// # Report runner for running analytics report from `sql/`
+pub async fn report_runner(
+    conf: &Config,
+    db: &ClickhouseDatabase,
+    args: &ReportArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let report =
+        find_report(&args.name).ok_or_else(|| format!("Unknown report '{}'", args.name))?;
+    let rows = db
+        .fetch_report(report.sql, &report.params(conf, args)?)
+        .await?;
+    let table = ReportTable::from_json_rows(&rows)?;
+    print!("{}", table.render(args.format)?);
+
     Ok(())
 }
 
-/// Save trades to a JSON file
-async fn save_trades_to_file(
-    file_path: &str,
-    candles: &Vec<Trade>,
+/// # Verify runner for checking integrity of stored market data
+///
+/// Finds duplicate trades and gaps in trades of checked dates, open gaps are repaired with
+/// `--repair`. Returns `false` if duplicates or open gaps remain. Once `shutdown` is requested
+/// it stops after the check or gap repair in progress and fails, remaining gaps are left open.
+pub async fn verify_runner(
+    conf: &Config,
+    db: &ClickhouseDatabase,
+    args: &VerifyArgs,
+    shutdown: &Shutdown,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let mut verified = true;
+    if args.check(VerifyCheck::Duplicates) || args.check(VerifyCheck::Gaps) {
+        db.init().await?;
+    }
+
+    if args.check(VerifyCheck::Duplicates) {
+        let duplicates = db.duplicate_trades().await?;
+        info!(duplicates, "Duplicate trades checked");
+        verified &= duplicates == 0;
+    }
+
+    if args.check(VerifyCheck::Gaps) {
+        if shutdown.requested() {
+            return Err("Verify stopped before checking gaps".into());
+        }
+        let (from, till) = args.range();
+        let detected = detect_gaps(db, &args.rules(), from, till).await?;
+        info!(gaps = detected.len(), %from, %till, "Gaps detected");
+
+        if args.repair {
+            let iss = IssClient::new(conf);
+            let run_id = new_run_id();
+            info!(%run_id, "Run started");
+            for gap in db.data_gaps(from, till, Some(GapStatus::Open)).await? {
+                if shutdown.requested() {
+                    return Err("Gap repair stopped, remaining gaps are left open".into());
+                }
+                repair_gap(db, &iss, &gap, &run_id).await?;
+            }
+        }
+
+        let open = db.data_gaps(from, till, Some(GapStatus::Open)).await?;
+        info!(gaps = open.len(), %from, %till, "Gaps open");
+        verified &= open.is_empty();
+    }
+
+    if args.check(VerifyCheck::Ledger) {
+        if shutdown.requested() {
+            return Err("Verify stopped before checking ledger".into());
+        }
+        let store = open_store(conf, args.source).await?;
+        let ledger = Ledger::new(&conf.ledger_path);
+        let problems = ledger::verify(store.as_ref(), &ledger, args.ledger_range()).await?;
+        verified &= problems == 0;
+    }
+
+    Ok(verified)
+}
+
+/// # Seal runner
+///
+/// Seals daily Merkle roots of trades of selected boards into the ledger, stops after the entry
+/// being appended once `shutdown` is requested
+pub async fn seal_runner(
+    conf: &Config,
+    args: &SealArgs,
+    shutdown: &Shutdown,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let mut file = File::create(file_path).await?;
-    let candles_json = serde_json::to_string(&candles)?;
-    file.write_all(candles_json.as_bytes()).await?;
+    let (from, till) = args.days.range();
+    let store = open_store(conf, args.source).await?;
+    let ledger = Ledger::new(&conf.ledger_path);
+    let sealed = ledger::seal(
+        store.as_ref(),
+        &ledger,
+        &args.selection,
+        from,
+        till,
+        shutdown,
+    )
+    .await?;
+    info!(entries = sealed, %from, %till, "Ledger entries sealed");
     Ok(())
 }
+
+/// Path of signing key
+fn signing_key_path(conf: &Config) -> Result<&str, Box<dyn std::error::Error>> {
+    conf.signing_key
+        .as_deref()
+        .ok_or_else(|| "Signing key is not defined, set --signing-key".into())
+}
+
+/// # Keygen runner
+///
+/// Generates signing key and prints its public key to be handed to verifiers
+pub async fn keygen_runner(conf: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    let path = signing_key_path(conf)?;
+    let key = proof::generate_signing_key(path).await?;
+    println!("Signing key saved to {}", path);
+    println!("Public key: {}", to_hex(key.verifying_key().as_bytes()));
+    Ok(())
+}
+
+/// # Sign runner
+///
+/// Signs manifests of sealed days
+pub async fn sign_runner(conf: &Config, args: &SignArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let key = proof::load_signing_key(signing_key_path(conf)?).await?;
+    let ledger = Ledger::new(&conf.ledger_path);
+    let (from, till) = args.days.range();
+    let signed = proof::sign_manifests(&ledger, &key, from, till).await?;
+    info!(manifests = signed, %from, %till, "Manifests signed");
+    Ok(())
+}
+
+/// # Prove runner
+///
+/// Exports inclusion proof of a sealed trade
+pub async fn prove_runner(
+    conf: &Config,
+    args: &ProveArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let store = open_store(conf, args.source).await?;
+    let ledger = Ledger::new(&conf.ledger_path);
+    let proof = proof::prove_trade(
+        store.as_ref(),
+        &ledger,
+        &args.board,
+        &args.secid,
+        args.tradeid,
+    )
+    .await?;
+
+    let json = serde_json::to_string_pretty(&proof)?;
+    match &args.output {
+        Some(path) => {
+            write_file_atomic(path, json.as_bytes()).await?;
+            info!(tradeid = args.tradeid, %path, "Proof saved");
+        }
+        None => println!("{}", json),
+    }
+    Ok(())
+}
+
+/// # Verify proof runner
+///
+/// Checks inclusion proof offline, returns whether it is valid
+pub async fn verify_proof_runner(
+    args: &VerifyProofArgs,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let proof: InclusionProof = serde_json::from_slice(&tokio::fs::read(&args.proof).await?)?;
+    match proof::verify_proof(&proof, &args.public_key) {
+        Ok(key) => {
+            println!(
+                "Proof valid: trade {} of '{}' '{}' day {} sealed at {} belongs to daily root {} \
+                signed at {} by {}",
+                proof.trade.tradeid,
+                proof.trade.boardid,
+                proof.trade.secid,
+                proof.daily.day,
+                proof.entry.sealed_at,
+                proof.daily.root,
+                proof.daily.signed_at,
+                to_hex(key.as_bytes())
+            );
+            Ok(true)
+        }
+        Err(e) => {
+            println!("Proof invalid: {}", e);
+            Ok(false)
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use crate::config::Config;
+use crate::models::{Board, Engine, Market, Trade};
+use crate::sink::MarketDataSink;
+use async_trait::async_trait;
+use futures::future::try_join_all;
+use object_store::aws::{AmazonS3, AmazonS3Builder};
+use object_store::path::Path;
+use object_store::{MultipartUpload, ObjectStore, PutPayload, RetryConfig};
+use serde::Serialize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use time::OffsetDateTime;
+use tracing::{debug, warn};
+
+/// Size of parts used for multipart uploads, S3 requires at least 5 MiB per part
+const PART_SIZE: usize = 8 * 1024 * 1024;
+
+/// # S3 sink struct
+///
+/// Uploads trades as the same JSON files `DiskSink` writes into S3 compatible object storage
+/// (MinIO, S3). Each run writes into its own `>prefix</>run<` directory together with a
+/// `manifest.json` listing every uploaded file, written once the sink is closed.
+///
+/// Failed requests are retried by the store client with exponential backoff, uploads still
+/// failing after that are aborted so no orphaned parts are left in the bucket.
+///
+/// Credentials and other settings not exposed in `Config` are read from the standard
+/// `AWS_*` environment variables.
+pub struct S3Sink {
+    store: AmazonS3,
+    prefix: String,
+    file_num: AtomicUsize,
+    manifest: Mutex<Vec<ManifestEntry>>,
+}
+
+/// Uploaded file record in manifest
+#[derive(Debug, Clone, Serialize)]
+struct ManifestEntry {
+    path: String,
+    trades: usize,
+    bytes: usize,
+    first_tradeid: i64,
+    last_tradeid: i64,
+}
+
+/// # Implementation for S3Sink Struct
+impl S3Sink {
+    /// # S3Sink instance factory
+    ///
+    /// `url` has the form `s3://>bucket</>prefix<`
+    pub fn new(conf: &Config, url: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let (bucket, prefix) = url
+            .strip_prefix("s3://")
+            .map(|rest| rest.split_once('/').unwrap_or((rest, "")))
+            .ok_or_else(|| format!("Invalid S3 URL '{}', expected s3://bucket/prefix", url))?;
+
+        let mut builder = AmazonS3Builder::from_env()
+            .with_bucket_name(bucket)
+            .with_retry(RetryConfig {
+                max_retries: 10,
+                retry_timeout: Duration::from_secs(180),
+                ..Default::default()
+            });
+        if let Some(ref endpoint) = conf.md_s3_endpoint {
+            builder = builder
+                .with_endpoint(endpoint)
+                .with_allow_http(endpoint.starts_with("http://"))
+                // MinIO and most self hosted stores do not support virtual hosted buckets
+                .with_virtual_hosted_style_request(false);
+        }
+        if let Some(ref region) = conf.md_s3_region {
+            builder = builder.with_region(region);
+        }
+
+        let run = OffsetDateTime::now_utc().unix_timestamp();
+        let prefix = prefix.trim_end_matches('/');
+        let prefix = if prefix.is_empty() {
+            run.to_string()
+        } else {
+            format!("{}/{}", prefix, run)
+        };
+
+        Ok(Self {
+            store: builder.build()?,
+            prefix,
+            file_num: AtomicUsize::new(1),
+            manifest: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// # Upload file using multipart upload
+    ///
+    /// Parts are uploaded concurrently, the upload is aborted if any part or the completion
+    /// fails
+    async fn upload(&self, path: &Path, data: &[u8]) -> Result<(), object_store::Error> {
+        let mut upload = self.store.put_multipart(path).await?;
+        let parts: Vec<_> = data
+            .chunks(PART_SIZE)
+            .map(|part| upload.put_part(PutPayload::from(part.to_vec())))
+            .collect();
+        let result = match try_join_all(parts).await {
+            Ok(_) => upload.complete().await.map(|_| ()),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            if let Err(abort) = upload.abort().await {
+                warn!(%path, error = %abort, "Aborting S3 upload failed");
+            }
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// # Write manifest of uploaded files
+    ///
+    /// Manifest is written with a single PUT, so readers see either no manifest or the
+    /// complete one
+    async fn write_manifest(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let manifest_json = {
+            let manifest = self.manifest.lock().unwrap();
+            serde_json::to_vec(&*manifest)?
+        };
+        let path = Path::from(format!("{}/manifest.json", self.prefix));
+        self.store
+            .put(&path, PutPayload::from(manifest_json))
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait(?Send)]
+impl MarketDataSink for S3Sink {
+    fn name(&self) -> &str {
+        "s3"
+    }
+
+    async fn init(&self) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    async fn write_engines(&self, _engines: &[Engine]) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    async fn write_markets(&self, _markets: &[Market]) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    async fn write_boards(&self, _boards: &[Board]) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    /// Upload trades as JSON to a new numbered file
+    async fn write_trades(&self, trades: &[Trade]) -> Result<(), Box<dyn std::error::Error>> {
+        let (Some(first), Some(last)) = (trades.first(), trades.last()) else {
+            return Ok(());
+        };
+        let time_upload: Instant = Instant::now();
+        let file_num = self.file_num.fetch_add(1, Ordering::Relaxed);
+        let path = Path::from(format!(
+            "{}/{}-{}-{}.json",
+            self.prefix, first.engine, first.market, file_num
+        ));
+
+        let trades_json = serde_json::to_vec(trades)?;
+        self.upload(&path, &trades_json).await?;
+
+        self.manifest.lock().unwrap().push(ManifestEntry {
+            path: path.to_string(),
+            trades: trades.len(),
+            bytes: trades_json.len(),
+            first_tradeid: first.tradeid,
+            last_tradeid: last.tradeid,
+        });
+        debug!(
+            trades = trades.len(),
+            %path,
+            time_ms = time_upload.elapsed().as_secs_f64() * 1e3,
+            "Trades uploaded to S3"
+        );
+        Ok(())
+    }
+
+    /// Write manifest of all uploaded files
+    async fn close(&self) -> Result<(), Box<dyn std::error::Error>> {
+        self.write_manifest().await
+    }
+}
@@ -0,0 +1,73 @@
+use std::io;
+use tokio::sync::watch;
+use tracing::{error, warn};
+
+/// Exit code when shutdown is forced with a second signal
+const EXIT_FORCED: i32 = 130;
+
+/// # Shutdown request
+///
+/// Set once SIGINT or SIGTERM is received. Runners stop scheduling new work when requested and
+/// finish what they are writing, so sinks can be flushed and closed before exit.
+#[derive(Debug, Clone)]
+pub struct Shutdown {
+    rx: watch::Receiver<bool>,
+}
+
+/// Implementation for Shutdown struct
+impl Shutdown {
+    /// # Listen for SIGINT and SIGTERM
+    ///
+    /// The first signal requests shutdown, the second one exits immediately. Must be called
+    /// within Tokio runtime.
+    pub fn listen() -> Self {
+        let (tx, rx) = watch::channel(false);
+        tokio::spawn(async move {
+            if let Err(e) = wait_signal().await {
+                error!(error = %e, "Listening for shutdown signals failed");
+                return;
+            }
+            warn!("Shutdown requested, finishing in-flight pages, signal again to exit now");
+            let _ = tx.send(true);
+
+            if wait_signal().await.is_ok() {
+                error!("Shutdown forced");
+                std::process::exit(EXIT_FORCED);
+            }
+        });
+        Self { rx }
+    }
+
+    /// Whether shutdown was requested
+    pub fn requested(&self) -> bool {
+        *self.rx.borrow()
+    }
+
+    /// Wait until shutdown is requested
+    pub async fn wait(&self) {
+        let mut rx = self.rx.clone();
+        while !*rx.borrow_and_update() {
+            // Listener is gone, shutdown can not be requested anymore
+            if rx.changed().await.is_err() {
+                std::future::pending::<()>().await;
+            }
+        }
+    }
+}
+
+/// Wait for SIGINT or SIGTERM
+async fn wait_signal() -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut terminate = signal(SignalKind::terminate())?;
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result,
+            _ = terminate.recv() => Ok(()),
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        tokio::signal::ctrl_c().await
+    }
+}
@@ -0,0 +1,121 @@
+use crate::config::Config;
+use crate::db::ClickhouseDatabase;
+use crate::disk::DiskSink;
+use crate::ingest::IngestLog;
+use crate::kafka::KafkaSink;
+use crate::models::{Board, Engine, Market, Security, Trade};
+use crate::pg::PostgresDatabase;
+use crate::s3::S3Sink;
+use crate::sqlite::SqliteDatabase;
+use async_trait::async_trait;
+
+/// # Market data sink
+///
+/// Destination for gathered market data. Runners write every batch to each configured sink,
+/// so adding a new destination only requires implementing this trait and registering it in
+/// `sinks_from_config`.
+#[async_trait(?Send)]
+pub trait MarketDataSink {
+    /// Name of sink used in logs
+    fn name(&self) -> &str;
+
+    /// Whether the sink counts stored trades in `anselm_rows_inserted_total` itself, sinks
+    /// batching writes count trades once the batch is committed
+    fn counts_inserted(&self) -> bool {
+        false
+    }
+
+    /// Whether the sink stores securities, securities of selected boards are fetched from ISS
+    /// only if some sink wants them
+    fn wants_securities(&self) -> bool {
+        false
+    }
+
+    /// Init sink schema, tables, directories etc.
+    async fn init(&self) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Write a batch of Engine Records
+    async fn write_engines(&self, engines: &[Engine]) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Write a batch of Market Records
+    async fn write_markets(&self, markets: &[Market]) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Write a batch of Board Records
+    async fn write_boards(&self, boards: &[Board]) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Write a batch of Security Records, sinks without securities ignore them
+    async fn write_securities(
+        &self,
+        _securities: &[Security],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    /// Write a batch of Trade Records
+    async fn write_trades(&self, trades: &[Trade]) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// # Trade ids of board already stored within `[from, till]`
+    ///
+    /// Used by import to skip stored trades, sinks that can not look trades up return none
+    async fn stored_tradeids(
+        &self,
+        _engine: &str,
+        _market: &str,
+        _boardid: &str,
+        _from: i64,
+        _till: i64,
+    ) -> Result<Vec<i64>, Box<dyn std::error::Error>> {
+        Ok(Vec::new())
+    }
+
+    /// Write a batch of ingestion log records, sinks without lineage ignore them
+    async fn write_ingest_log(
+        &self,
+        _entries: &[IngestLog],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    /// Flush buffered data
+    async fn flush(&self) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    /// Flush buffered data and release resources
+    async fn close(&self) -> Result<(), Box<dyn std::error::Error>> {
+        self.flush().await
+    }
+}
+
+/// # Create market data sinks defined in config
+///
+/// - `--md-disk` saves market data to disk instead of Clickhouse
+/// - `--md-archive` saves market data to disk in addition to Clickhouse
+/// - `--md-s3-url` uploads disk market data files to S3 instead of `md_path`
+/// - `--pg-url` saves market data to PostgreSQL in addition to other sinks
+/// - `--md-sqlite` saves market data to a SQLite file in addition to other sinks
+/// - `--kafka-brokers` publishes market data to Kafka in addition to other sinks
+pub fn sinks_from_config(
+    conf: &Config,
+) -> Result<Vec<Box<dyn MarketDataSink>>, Box<dyn std::error::Error>> {
+    let mut sinks: Vec<Box<dyn MarketDataSink>> = Vec::new();
+    if !conf.md_disk {
+        sinks.push(Box::new(ClickhouseDatabase::new(conf)));
+    }
+    if conf.md_disk || conf.md_archive {
+        match conf.md_s3_url {
+            Some(ref url) => sinks.push(Box::new(S3Sink::new(conf, url)?)),
+            None => sinks.push(Box::new(DiskSink::new(conf))),
+        }
+    }
+    if let Some(ref url) = conf.pg_url {
+        sinks.push(Box::new(PostgresDatabase::new(conf, url)));
+    }
+    if let Some(ref path) = conf.md_sqlite {
+        sinks.push(Box::new(SqliteDatabase::new(path)?));
+    }
+    if let Some(ref brokers) = conf.kafka_brokers {
+        sinks.push(Box::new(KafkaSink::new(conf, brokers)?));
+    }
+    Ok(sinks)
+}
@@ -0,0 +1,263 @@
+use crate::models::{decimal64, Board, Engine, Market, Trade};
+use crate::sink::MarketDataSink;
+use async_trait::async_trait;
+use rusqlite::{params, Connection, Result};
+use std::sync::Mutex;
+use std::time::Instant;
+use tracing::debug;
+
+/// # SQLite Database struct
+///
+/// Embedded file database for laptop-scale use, tables and columns mirror the schema created
+/// by `ClickhouseDatabase::init`. Prices and values are `INTEGER` mantissas at
+/// `decimal64::DECIMAL_SCALE` like Clickhouse `Decimal64` columns, so they sum and compare as
+/// numbers. Trades are unique per board and trade id, repeated trades are ignored like
+/// duplicates are skipped by live ingestion into Clickhouse.
+///
+/// Reports in `sql/` use Clickhouse functions, trades reports adapted to SQLite are in
+/// `sql/sqlite/` and run with the `sqlite3` shell, dates are UTC:
+///
+/// ```sh
+/// sqlite3 md_moex.sqlite -cmd ".parameter set :from \"'2024-03-01'\"" \
+///     -cmd ".parameter set :till \"'2024-04-01'\"" < sql/sqlite/volume_date_range.sql
+/// ```
+///
+/// SQLite was chosen over DuckDB since `rusqlite` bundles a small C library that builds
+/// quickly on every platform, while the DuckDB crate compiles a large C++ library and adds
+/// tens of megabytes to a binary built for size. For columnar analytics DuckDB reads the file
+/// directly with its SQLite extension: `ATTACH 'md_moex.sqlite' AS md_moex (TYPE sqlite)`.
+///
+/// Queries are executed synchronously, which is fine for the single file use case.
+pub struct SqliteDatabase {
+    conn: Mutex<Connection>,
+}
+
+/// # Implementation for SqliteDatabase Struct
+impl SqliteDatabase {
+    /// # SqliteDatabase instance factory
+    ///
+    /// Database file is created if it does not exist
+    pub fn new(path: &str) -> Result<Self> {
+        let conn = Connection::open(path)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    /// # Init database with required tables
+    ///
+    /// ## Steps
+    /// Create defined database tables if they do not exist:
+    /// - `engines`
+    /// - `markets`
+    /// - `boards`
+    /// - `trades`
+    ///
+    pub fn init(&self) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute_batch(
+            "
+            PRAGMA journal_mode = WAL;
+            PRAGMA synchronous = NORMAL;
+
+            CREATE TABLE IF NOT EXISTS engines(
+                id               INTEGER NOT NULL,
+                name             TEXT NOT NULL PRIMARY KEY,
+                title            TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS markets(
+                engine           TEXT NOT NULL,
+                id               INTEGER NOT NULL,
+                name             TEXT NOT NULL,
+                title            TEXT NOT NULL,
+                PRIMARY KEY (engine, name)
+            );
+
+            CREATE TABLE IF NOT EXISTS boards(
+                engine           TEXT NOT NULL,
+                market           TEXT NOT NULL,
+                id               INTEGER NOT NULL,
+                board_group_id   INTEGER NOT NULL,
+                boardid          TEXT NOT NULL,
+                title            TEXT NOT NULL,
+                is_traded        INTEGER NOT NULL,
+                PRIMARY KEY (engine, market, boardid)
+            );
+
+            CREATE TABLE IF NOT EXISTS trades(
+                engine     TEXT NOT NULL,
+                market     TEXT NOT NULL,
+                secid      TEXT NOT NULL,
+                boardid    TEXT NOT NULL,
+                tradeid    INTEGER NOT NULL,
+                buysell    TEXT NOT NULL,
+                quantity   INTEGER NOT NULL,
+                price      INTEGER NOT NULL,
+                value      INTEGER NOT NULL,
+                decimals   INTEGER NOT NULL,
+                tradetime  TEXT NOT NULL,
+                systime    TEXT NOT NULL,
+                run_id     TEXT NOT NULL DEFAULT ''
+            );
+
+            CREATE UNIQUE INDEX IF NOT EXISTS trades_tradeid_idx
+                ON trades (engine, market, boardid, tradeid);
+
+            CREATE INDEX IF NOT EXISTS trades_secid_idx
+                ON trades (engine, market, secid, boardid, tradeid);
+            ",
+        )
+    }
+
+    /// # Insert a batch of Engine Records into database
+    ///
+    /// Existing Engines are skipped
+    pub fn insert_engines(&self, engines: &[Engine]) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        {
+            let mut stmt =
+                tx.prepare("INSERT OR IGNORE INTO engines (id, name, title) VALUES (?1, ?2, ?3)")?;
+            for engine in engines {
+                stmt.execute(params![engine.id, engine.name, engine.title])?;
+            }
+        }
+        tx.commit()
+    }
+
+    /// # Insert a batch of Market Records into database
+    ///
+    /// Existing Markets are skipped
+    pub fn insert_markets(&self, markets: &[Market]) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT OR IGNORE INTO markets (engine, id, name, title) VALUES (?1, ?2, ?3, ?4)",
+            )?;
+            for market in markets {
+                stmt.execute(params![market.engine, market.id, market.name, market.title])?;
+            }
+        }
+        tx.commit()
+    }
+
+    /// # Insert a batch of Board Records into database
+    ///
+    /// Existing Boards are skipped
+    pub fn insert_boards(&self, boards: &[Board]) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT OR IGNORE INTO boards
+                    (engine, market, id, board_group_id, boardid, title, is_traded)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            )?;
+            for board in boards {
+                stmt.execute(params![
+                    board.engine,
+                    board.market,
+                    board.id,
+                    board.board_group_id,
+                    board.boardid,
+                    board.title,
+                    board.is_traded,
+                ])?;
+            }
+        }
+        tx.commit()
+    }
+
+    /// # Insert a batch of Trade Records into database
+    ///
+    /// Trades already stored are skipped, batch is inserted in a single transaction
+    pub fn insert_trades(
+        &self,
+        trades: &[Trade],
+    ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT OR IGNORE INTO trades
+                    (engine, market, secid, boardid, tradeid, buysell,
+                     quantity, price, value, decimals, tradetime, systime, run_id)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+            )?;
+            for trade in trades {
+                stmt.execute(params![
+                    trade.engine,
+                    trade.market,
+                    trade.secid,
+                    trade.boardid,
+                    trade.tradeid,
+                    trade.buysell.as_str(),
+                    trade.quantity,
+                    // SQLite has no decimal type, store decimals as mantissas
+                    decimal64::mantissa(&trade.price)?,
+                    decimal64::mantissa(&trade.value)?,
+                    trade.decimals,
+                    trade.tradetime,
+                    trade.systime,
+                    trade.run_id,
+                ])?;
+            }
+        }
+        Ok(tx.commit()?)
+    }
+}
+
+#[async_trait(?Send)]
+impl MarketDataSink for SqliteDatabase {
+    fn name(&self) -> &str {
+        "sqlite"
+    }
+
+    async fn init(&self) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        Ok(SqliteDatabase::init(self)?)
+    }
+
+    async fn write_engines(
+        &self,
+        engines: &[Engine],
+    ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        Ok(self.insert_engines(engines)?)
+    }
+
+    async fn write_markets(
+        &self,
+        markets: &[Market],
+    ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        Ok(self.insert_markets(markets)?)
+    }
+
+    async fn write_boards(
+        &self,
+        boards: &[Board],
+    ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        Ok(self.insert_boards(boards)?)
+    }
+
+    async fn write_trades(
+        &self,
+        trades: &[Trade],
+    ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let time_insert: Instant = Instant::now();
+        self.insert_trades(trades)?;
+        debug!(
+            trades = trades.len(),
+            time_ms = time_insert.elapsed().as_secs_f64() * 1e3,
+            "Trades saved to SQLite"
+        );
+        Ok(())
+    }
+
+    /// Checkpoint write-ahead log into database file
+    async fn close(&self) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE);")?;
+        Ok(())
+    }
+}
@@ -0,0 +1,102 @@
+use crate::config::{Config, LogFormat};
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
+use std::io::IsTerminal;
+use tracing_subscriber::filter::filter_fn;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, EnvFilter, Layer};
+
+/// Targets of crates used by the OTLP exporter itself, their spans are not exported
+const EXPORTER_TARGETS: [&str; 5] = ["h2", "hyper", "opentelemetry", "tonic", "tower"];
+
+/// # Installed logging and tracing
+///
+/// Spans not yet exported are lost unless `shutdown` is called before exit
+pub struct Telemetry {
+    provider: Option<SdkTracerProvider>,
+}
+
+/// Implementation for Telemetry struct
+impl Telemetry {
+    /// Export pending spans and stop the exporter
+    pub async fn shutdown(self) {
+        let Some(provider) = self.provider else {
+            return;
+        };
+        // Shutdown blocks until the exporter thread has exported pending spans
+        match tokio::task::spawn_blocking(move || provider.shutdown()).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => eprintln!("Trace export shutdown failed: {}", e),
+            Err(e) => eprintln!("Trace export shutdown failed: {}", e),
+        }
+    }
+}
+
+/// # Install global logging and tracing
+///
+/// Events are written to stderr as text or JSON lines filtered by `RUST_LOG` or `log_level`,
+/// stdout is left to command output. Spans are also exported to the OTLP collector at
+/// `otlp_endpoint` as service `service` when it is defined. Must be called within Tokio
+/// runtime.
+pub fn init_telemetry(
+    conf: &Config,
+    service: &str,
+) -> Result<Telemetry, Box<dyn std::error::Error>> {
+    let filter = match std::env::var("RUST_LOG") {
+        Ok(directives) if !directives.is_empty() => EnvFilter::try_new(directives)?,
+        _ => EnvFilter::try_new(&conf.log_level)?,
+    };
+
+    let fmt_layer = match conf.log_format {
+        LogFormat::Text => fmt::layer()
+            .with_ansi(std::io::stderr().is_terminal())
+            .with_writer(std::io::stderr)
+            .boxed(),
+        LogFormat::Json => fmt::layer()
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .with_writer(std::io::stderr)
+            .boxed(),
+    };
+
+    let provider = match &conf.otlp_endpoint {
+        Some(endpoint) => {
+            let exporter = SpanExporter::builder()
+                .with_tonic()
+                .with_endpoint(endpoint)
+                .build()?;
+            Some(
+                SdkTracerProvider::builder()
+                    .with_batch_exporter(exporter)
+                    .with_resource(
+                        Resource::builder()
+                            .with_service_name(service.to_string())
+                            .build(),
+                    )
+                    .build(),
+            )
+        }
+        None => None,
+    };
+    let otel_layer = provider.as_ref().map(|provider| {
+        tracing_opentelemetry::layer()
+            .with_tracer(provider.tracer(service.to_string()))
+            .with_filter(filter_fn(|meta| {
+                !EXPORTER_TARGETS
+                    .iter()
+                    .any(|target| meta.target().starts_with(target))
+            }))
+    });
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt_layer)
+        .with(otel_layer)
+        .try_init()?;
+
+    Ok(Telemetry { provider })
+}
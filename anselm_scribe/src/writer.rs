@@ -0,0 +1,173 @@
+use crate::config::Config;
+use crate::db::ClickhouseTrade;
+use crate::metrics;
+use crate::models::Trade;
+use clickhouse::{error::Result, inserter::Inserter, inserter::Quantities, Client};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+/// # Batching Clickhouse writer settings
+#[derive(Debug, Clone)]
+pub struct WriterConfig {
+    /// Flush after this many rows
+    pub max_rows: u64,
+    /// Flush after this many uncompressed bytes
+    pub max_bytes: u64,
+    /// Flush after this much time since the batch was started
+    pub period: Duration,
+    /// Use Clickhouse server side `async_insert`
+    pub async_insert: bool,
+}
+
+/// Implementation for WriterConfig struct
+impl WriterConfig {
+    /// # WriterConfig factory from config
+    pub fn new(conf: &Config) -> Self {
+        Self {
+            max_rows: conf.ch_batch_rows,
+            max_bytes: conf.ch_batch_bytes,
+            period: Duration::from_secs(conf.ch_batch_period),
+            async_insert: conf.ch_async_insert,
+        }
+    }
+}
+
+/// # Statistics of a single flush
+#[derive(Debug, Clone)]
+pub struct FlushStats {
+    pub rows: u64,
+    pub bytes: u64,
+    pub elapsed: Duration,
+}
+
+/// Implementation for FlushStats struct
+impl FlushStats {
+    /// Rows flushed per second since the batch was started
+    pub fn rows_per_sec(&self) -> f64 {
+        self.rows as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+
+    /// Megabytes flushed per second since the batch was started
+    pub fn mb_per_sec(&self) -> f64 {
+        self.bytes as f64 / 1_000_000.0 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+/// # Long-lived batching Trade writer
+///
+/// Wraps a Clickhouse `Inserter` that keeps a single `INSERT` open and ends it when the
+/// batch reaches `max_rows`, `max_bytes` or `period`, so many small ISS pages produce few
+/// large parts instead of "too many parts" errors. Every flush is logged with its throughput
+/// and counted in `anselm_clickhouse_flush*` metrics. Trades are counted in
+/// `anselm_rows_inserted_total` per board once the batch holding them is committed.
+pub struct TradeWriter {
+    inserter: Inserter<ClickhouseTrade>,
+    /// Trades per board written since the last commit
+    pending: HashMap<(String, String, String), usize>,
+    batch_start: Instant,
+    total_rows: u64,
+    total_flushes: u64,
+}
+
+/// Implementation for TradeWriter struct
+impl TradeWriter {
+    /// # TradeWriter instance factory
+    pub fn new(client: &Client, table: &str, conf: &WriterConfig) -> Result<Self> {
+        let client = if conf.async_insert {
+            client
+                .clone()
+                .with_option("async_insert", "1")
+                .with_option("wait_for_async_insert", "1")
+        } else {
+            client.clone()
+        };
+
+        let inserter = client
+            .inserter::<ClickhouseTrade>(table)?
+            .with_max_rows(conf.max_rows)
+            .with_max_bytes(conf.max_bytes)
+            .with_period(Some(conf.period));
+
+        Ok(Self {
+            inserter,
+            pending: HashMap::new(),
+            batch_start: Instant::now(),
+            total_rows: 0,
+            total_flushes: 0,
+        })
+    }
+
+    /// # Write trades, flushing the batch if any of the limits was reached
+    pub async fn write(&mut self, trades: &[Trade]) -> Result<Option<FlushStats>> {
+        for trade in trades {
+            self.inserter.write(&ClickhouseTrade::from(trade))?;
+            let board = (
+                trade.engine.clone(),
+                trade.market.clone(),
+                trade.boardid.clone(),
+            );
+            *self.pending.entry(board).or_default() += 1;
+        }
+        let time_commit = Instant::now();
+        let quantities = observe(self.inserter.commit().await, time_commit)?;
+        Ok(self.record(quantities))
+    }
+
+    /// # Flush the current batch regardless of limits
+    pub async fn flush(&mut self) -> Result<Option<FlushStats>> {
+        let time_commit = Instant::now();
+        let quantities = observe(self.inserter.force_commit().await, time_commit)?;
+        Ok(self.record(quantities))
+    }
+
+    /// # Flush the current batch and end the insert
+    pub async fn end(mut self) -> Result<Option<FlushStats>> {
+        let stats = self.flush().await?;
+        let time_end = Instant::now();
+        observe(self.inserter.end().await, time_end)?;
+        Ok(stats)
+    }
+
+    /// Record flush statistics and trades committed by it, returns `None` if nothing was
+    /// flushed
+    fn record(&mut self, quantities: Quantities) -> Option<FlushStats> {
+        if quantities.rows == 0 {
+            return None;
+        }
+
+        let stats = FlushStats {
+            rows: quantities.rows,
+            bytes: quantities.bytes,
+            elapsed: self.batch_start.elapsed(),
+        };
+        self.batch_start = Instant::now();
+        self.total_rows += stats.rows;
+        self.total_flushes += 1;
+        metrics::clickhouse_flush("trades", stats.rows, stats.bytes);
+        for ((engine, market, boardid), rows) in self.pending.drain() {
+            metrics::rows_inserted(&engine, &market, &boardid, "clickhouse", rows);
+        }
+
+        info!(
+            trades = stats.rows,
+            flush = self.total_flushes,
+            bytes = stats.bytes,
+            time_ms = stats.elapsed.as_secs_f64() * 1e3,
+            rows_per_sec = stats.rows_per_sec(),
+            mb_per_sec = stats.mb_per_sec(),
+            total = self.total_rows,
+            "Trades flushed to Clickhouse"
+        );
+        Some(stats)
+    }
+}
+
+/// Record commit of trades in metrics, commits that flushed nothing are not timed
+fn observe(result: Result<Quantities>, started: Instant) -> Result<Quantities> {
+    match &result {
+        Ok(quantities) if quantities.rows == 0 => {}
+        _ => metrics::clickhouse_insert("trades", started.elapsed(), result.is_err()),
+    }
+    result
+}
@@ -0,0 +1,113 @@
+//! ISS cassette replay tests on stored responses in `tests/fixtures/iss`
+
+use anselm_scribe::config::Cli;
+use anselm_scribe::iss::{IssClient, IssError};
+use anselm_scribe::models::{self, Board, Trade};
+use clap::Parser;
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/iss");
+
+/// ISS client replaying the fixture cassette, `iss_csv` endpoints are requested as CSV
+fn replay_client(iss_csv: &str) -> IssClient {
+    let mut args = vec!["anselm_scribe", "--iss-replay", FIXTURES];
+    if !iss_csv.is_empty() {
+        args.extend(["--iss-csv", iss_csv]);
+    }
+    args.push("status");
+    IssClient::new(&Cli::parse_from(args).conf)
+}
+
+/// Select TQBR board through replayed engines, markets and boards
+async fn select_tqbr(iss: &IssClient) -> Board {
+    let engines = models::get_engines(iss).await.unwrap();
+    let engine = engines.iter().find(|e| e.name == "stock").unwrap();
+    assert_eq!(engine.title, "Фондовый рынок и рынок депозитов");
+
+    let markets = models::get_markets(iss, &engine.name).await.unwrap();
+    let market = markets.iter().find(|m| m.name == "shares").unwrap();
+
+    let boards = models::get_boards(iss, &engine.name, &market.name)
+        .await
+        .unwrap();
+    assert_eq!(boards.iter().filter(|b| b.is_traded).count(), 6);
+    boards.into_iter().find(|b| b.boardid == "TQBR").unwrap()
+}
+
+#[tokio::test]
+async fn replay_json() {
+    let iss = replay_client("");
+    let board = select_tqbr(&iss).await;
+    assert_eq!(board.title, "Т+: Акции и ДР - безадрес.");
+
+    let page = board.fetch_trades_page(&iss, 0).await.unwrap();
+    assert!(page.meta.replayed);
+    assert!(page.meta.url.ends_with("/trades.json"));
+    assert_eq!(page.meta.params, "start=0&limit=5000");
+
+    let trades = board.fetch_trades(&iss, 0).await.unwrap();
+    assert_eq!(trades.len(), 5000);
+    assert_eq!(trades[0].tradeid, 11076497005);
+    assert_eq!(trades[0].secid, "TATN");
+    assert_eq!(trades[0].price.to_string(), "637");
+    assert!(trades.windows(2).all(|w| w[0].tradeid < w[1].tradeid));
+}
+
+#[tokio::test]
+async fn replay_csv_matches_json() {
+    let json = replay_client("");
+    let csv = replay_client("trades,securities");
+    let board = select_tqbr(&json).await;
+
+    let page = board.fetch_trades_page(&csv, 0).await.unwrap();
+    assert!(page.meta.url.ends_with("/trades.csv"));
+
+    let json_trades = board.fetch_trades(&json, 0).await.unwrap();
+    let csv_trades = board.fetch_trades(&csv, 0).await.unwrap();
+    assert_eq!(csv_trades.len(), json_trades.len());
+    let key = |t: &Trade| {
+        (
+            t.tradeid,
+            t.secid.clone(),
+            t.buysell.as_str(),
+            t.quantity,
+            t.price,
+            t.value,
+            t.decimals,
+            t.tradetime,
+            t.systime,
+        )
+    };
+    assert!(csv_trades
+        .iter()
+        .zip(&json_trades)
+        .all(|(c, j)| key(c) == key(j)));
+
+    let securities = models::get_securities(&csv, &board).await.unwrap();
+    let vtbr = securities.iter().find(|s| s.secid == "VTBR").unwrap();
+    assert_eq!(vtbr.shortname, "ВТБ ао");
+    assert_eq!(vtbr.decimals, 6);
+    assert_eq!(vtbr.lotsize, 10000);
+}
+
+#[tokio::test]
+async fn replay_missing_response() {
+    let iss = replay_client("");
+    let board = select_tqbr(&iss).await;
+
+    let failed = match board.fetch_trades_page(&iss, 5000).await {
+        Err(failed) => failed,
+        Ok(page) => panic!("Expected cassette error, got page {}", page.start),
+    };
+    match failed.error {
+        IssError::Cassette(key, _) => assert_eq!(
+            key,
+            "engines_stock_markets_shares_boards_TQBR_trades.json__start=5000__limit=5000"
+        ),
+        other => panic!("Expected cassette error, got {:?}", other),
+    }
+    // Failed requests keep their metadata for the ingestion log
+    assert_eq!(failed.start, 5000);
+    assert!(failed.meta.replayed);
+    assert_eq!(failed.meta.status, 0);
+    assert_eq!(failed.meta.params, "start=5000&limit=5000");
+}
@@ -1 +0,0 @@
-SELECT COUNT(*) AS total_candle FROM md_moex.candles
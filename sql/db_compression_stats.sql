@@ -1,3 +1,4 @@
+-- Parameters: db
 SELECT
     database,
     table,
@@ -7,7 +8,7 @@ SELECT
     sum(rows) AS rows,
     count() AS part_count
 FROM system.parts
-WHERE (active = 1) AND (database LIKE '%') AND (table LIKE '%')
+WHERE (active = 1) AND (database = {db:String})
 GROUP BY
     database,
     table
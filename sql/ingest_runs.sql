@@ -0,0 +1,31 @@
+-- Parameters: db, from, till
+SELECT
+    run_id,
+    engine,
+    market,
+    boardid,
+    min(logged_at) AS started_at,
+    max(logged_at) AS finished_at,
+    count() AS requests,
+    countIf(replayed) AS replayed,
+    countIf(status != 200 OR error != '') AS failed,
+    sum(rows) AS trades,
+    sum(bytes) AS bytes,
+    minIf(first_tradeid, rows > 0) AS first_tradeid,
+    max(last_tradeid) AS last_tradeid,
+    round(sum(fetch_us) / 1000) AS fetch_ms,
+    round(sum(parse_us) / 1000) AS parse_ms
+FROM
+    {db:Identifier}.ingest_log
+WHERE
+    toDate(logged_at) >= {from:Date} AND toDate(logged_at) < {till:Date}
+GROUP BY
+    run_id,
+    engine,
+    market,
+    boardid
+ORDER BY
+    started_at,
+    engine,
+    market,
+    boardid;
@@ -0,0 +1,25 @@
+-- Parameters: db, from, till
+SELECT
+    day,
+    engine,
+    market,
+    boardid,
+    uniqExact(secid) AS securities,
+    uniqExactIf(secid, NOT matched) AS mismatched_securities,
+    countIf(NOT matched) AS mismatched_metrics,
+    arrayStringConcat(groupUniqArrayIf(metric, NOT matched), ',') AS metrics,
+    max(checked_at) AS checked_at
+FROM
+    {db:Identifier}.reconciliation FINAL
+WHERE
+    day >= {from:Date} AND day < {till:Date}
+GROUP BY
+    day,
+    engine,
+    market,
+    boardid
+ORDER BY
+    day,
+    engine,
+    market,
+    boardid;
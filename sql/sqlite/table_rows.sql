@@ -0,0 +1,2 @@
+-- SQLite version of sql/table_rows.sql
+SELECT COUNT(*) AS total_rows FROM trades;
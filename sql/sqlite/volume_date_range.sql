@@ -0,0 +1,12 @@
+-- SQLite version of sql/volume_date_range.sql, parameters: from, till
+SELECT
+    date(tradetime) AS day,
+    SUM(quantity) AS total_volume
+FROM
+    trades
+WHERE
+    tradetime >= :from AND tradetime < :till
+GROUP BY
+    day
+ORDER BY
+    day;
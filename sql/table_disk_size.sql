@@ -1,3 +1,4 @@
+-- Parameters: db, table
 SELECT
     table,
     formatReadableSize(sum(bytes_on_disk)) AS bytes_on_disk,
@@ -7,10 +8,9 @@ SELECT
 FROM
     system.parts
 WHERE
-    database = 'md_moex'
-    AND table = 'candles'
+    database = {db:String}
+    AND table = {table:String}
     AND active = 1
 GROUP BY
     table;
 
-
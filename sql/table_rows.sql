@@ -0,0 +1,2 @@
+-- Parameters: db, table
+SELECT COUNT(*) AS total_rows FROM {db:Identifier}.{table:Identifier}
@@ -1,10 +1,11 @@
+-- Parameters: db, table, from, till
 SELECT
-    toDate(begin) AS day,
-    formatReadableQuantity(sum(volume)) AS total_volume
+    toDate(tradetime) AS day,
+    formatReadableQuantity(sum(quantity)) AS total_volume
 FROM
-    md_moex.trades
+    {db:Identifier}.{table:Identifier}
 WHERE
-    begin >= '2024-01-01' AND begin < '2024-06-01'
+    tradetime >= {from:Date} AND tradetime < {till:Date}
 GROUP BY
     day
 ORDER BY